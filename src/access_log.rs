@@ -0,0 +1,88 @@
+use crate::config::{AccessLogConfig, AccessLogFormat, Config};
+use crate::metrics;
+use actix_web::middleware::Logger;
+use env_logger::Builder;
+use regex::Regex;
+use std::io::Write;
+
+/// Log target for access log lines, printed without the usual log prefix.
+const TARGET: &str = "access";
+
+const COMMON_FORMAT: &str = r#"%a - - [%t] "%r" %s %b"#;
+const COMBINED_FORMAT: &str = r#"%a - - [%t] "%r" %s %b "%{Referer}i" "%{User-Agent}i""#;
+/// `method` and `path` are filled in already JSON-encoded, see `logger`.
+const JSON_FORMAT: &str = r#"{"time":"%t","remote_addr":"%a","method":%{method}xi,"path":%{path}xi,"status":%s,"duration_ms":%D,"bytes":%b}"#;
+
+/// Checks the `exclude_regex` patterns up front; actix panics on invalid ones.
+pub fn validate(config: &AccessLogConfig) -> Result<(), regex::Error> {
+    for pattern in &config.exclude_regex {
+        Regex::new(pattern)?;
+    }
+    Ok(())
+}
+
+/// Sets up `env_logger`. With a configured format, access log lines are
+/// always printed, bare, so log pipelines can parse them.
+pub fn init(config: &AccessLogConfig) {
+    let mut builder = Builder::from_default_env();
+    // Without `RUST_LOG` only errors are shown; keep the server's own
+    // warnings, such as slow renders
+    if std::env::var_os("RUST_LOG").is_none() {
+        builder.filter_module("hbs_composer", log::LevelFilter::Warn);
+    }
+
+    if config.format.is_some() {
+        builder
+            .filter_module(TARGET, log::LevelFilter::Info)
+            .format(|buf, record| {
+                if record.target() == TARGET {
+                    return writeln!(buf, "{}", record.args());
+                }
+                writeln!(
+                    buf,
+                    "[{} {} {}] {}",
+                    buf.timestamp(),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                )
+            });
+    }
+
+    builder.init();
+}
+
+/// Builds the access log middleware for the configured format. Scrapes of
+/// `/metrics` are never logged.
+pub fn logger(config: &Config) -> Logger {
+    let mut logger = match config.access_log.format {
+        Some(format) => formatted_logger(format),
+        None => Logger::default(),
+    };
+    if metrics::is_enabled(config) {
+        logger = logger.exclude(metrics::ENDPOINT);
+    }
+    for path in &config.access_log.exclude {
+        logger = logger.exclude(path.as_str());
+    }
+    for pattern in &config.access_log.exclude_regex {
+        logger = logger.exclude_regex(pattern.as_str());
+    }
+
+    logger
+}
+
+fn formatted_logger(format: AccessLogFormat) -> Logger {
+    match format {
+        AccessLogFormat::Common => Logger::new(COMMON_FORMAT),
+        AccessLogFormat::Combined => Logger::new(COMBINED_FORMAT),
+        AccessLogFormat::Json => Logger::new(JSON_FORMAT)
+            .custom_request_replace("method", |req| {
+                serde_json::Value::from(req.method().as_str()).to_string()
+            })
+            .custom_request_replace("path", |req| {
+                serde_json::Value::from(req.path()).to_string()
+            }),
+    }
+    .log_target(TARGET)
+}
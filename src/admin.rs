@@ -0,0 +1,157 @@
+//! Operator endpoints under `/_admin`, routed when `token` is set under
+//! `[admin]` and requiring it as a bearer token. Unlike previews they are
+//! served in every environment.
+
+use crate::auth;
+use crate::build;
+use crate::config::Config;
+use crate::data::Globals;
+use crate::remote_partials;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const RELOAD_ENDPOINT: &str = "/_admin/reload";
+
+pub const SELFTEST_ENDPOINT: &str = "/_admin/selftest";
+
+/// When the last self-test of each site started, by site root, to space
+/// runs `selftest_interval` apart.
+static LAST_SELFTEST: Mutex<BTreeMap<PathBuf, Instant>> = Mutex::new(BTreeMap::new());
+
+pub fn is_enabled(config: &Config) -> bool {
+    config.admin.token.is_some()
+}
+
+pub fn is_authorized(req: &HttpRequest, config: &Config) -> bool {
+    config
+        .admin
+        .token
+        .as_deref()
+        .is_some_and(|token| auth::has_bearer_token(req, token))
+}
+
+/// Fetches the remote partials again; a changed one also invalidates the
+/// render cache. Responds 502 when any partial failed to fetch; those keep
+/// their previous source.
+pub async fn reload(req: HttpRequest, config: web::Data<Config>) -> Result<HttpResponse> {
+    if !is_authorized(&req, &config) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+
+    let failed = remote_partials::refresh(&config).await;
+    let reloaded: Vec<&str> = config
+        .remote_partials
+        .iter()
+        .map(|partial| partial.name.as_str())
+        .filter(|name| !failed.contains_key(*name))
+        .collect();
+
+    let body = json!({ "reloaded": reloaded, "failed": failed });
+    Ok(match failed.is_empty() {
+        true => HttpResponse::Ok().json(body),
+        false => HttpResponse::BadGateway().json(body),
+    })
+}
+
+/// Renders every page against the current data in strict mode and reports
+/// each outcome, responding 500 when any page failed. Runs for a site are
+/// spaced `selftest_interval` seconds apart; requests in between get 429.
+pub async fn selftest(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    globals: web::Data<Globals>,
+) -> Result<HttpResponse> {
+    if !is_authorized(&req, &config) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+
+    {
+        let mut last = LAST_SELFTEST.lock().unwrap_or_else(|e| e.into_inner());
+        let interval = Duration::from_secs(config.admin.selftest_interval);
+        if let Some(wait) = last
+            .get(&config.root)
+            .and_then(|started| interval.checked_sub(started.elapsed()))
+        {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after))
+                .json(json!({ "error": "rate_limited", "retry_after": retry_after })));
+        }
+        last.insert(config.root.clone(), Instant::now());
+    }
+
+    let started = Instant::now();
+    let body = match build::check(&config, &globals).await {
+        Ok(pages) => {
+            let failed = pages.iter().filter(|page| page.error.is_some()).count();
+            json!({
+                "passed": failed == 0,
+                "failed": failed,
+                "duration_ms": started.elapsed().as_millis() as u64,
+                "pages": pages,
+            })
+        }
+        Err(e) => json!({ "passed": false, "error": e.to_string() }),
+    };
+    Ok(match body["passed"].as_bool() == Some(true) {
+        true => HttpResponse::Ok().json(body),
+        false => HttpResponse::InternalServerError().json(body),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    fn site(name: &str) -> Config {
+        let root = std::env::temp_dir().join(format!(
+            "hbs-composer-selftest-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(root.join("templates/pages")).unwrap();
+        std::fs::write(root.join("templates/pages/index.hbs"), "index").unwrap();
+        let mut config = Config {
+            root,
+            ..Config::default()
+        };
+        config.admin.token = Some("secret".to_string());
+        config
+    }
+
+    async fn run(config: &Config) -> StatusCode {
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer secret"))
+            .to_http_request();
+        selftest(
+            req,
+            web::Data::new(config.clone()),
+            web::Data::new(Globals::default()),
+        )
+        .await
+        .unwrap()
+        .status()
+    }
+
+    #[actix_web::test]
+    async fn selftests_are_spaced_per_site() {
+        let (first, second) = (site("first"), site("second"));
+        assert_eq!(run(&first).await, StatusCode::OK);
+        assert_eq!(run(&first).await, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(run(&second).await, StatusCode::OK);
+
+        std::fs::remove_dir_all(&first.root).unwrap();
+        std::fs::remove_dir_all(&second.root).unwrap();
+    }
+}
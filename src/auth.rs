@@ -0,0 +1,22 @@
+use actix_web::HttpRequest;
+use actix_web::http::header;
+
+/// Compares without returning early, so response timing doesn't reveal how
+/// much of the token matched.
+pub(crate) fn tokens_match(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Whether the request carries `Authorization: Bearer <expected>`.
+pub fn has_bearer_token(req: &HttpRequest, expected: &str) -> bool {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| tokens_match(given.as_bytes(), expected.as_bytes()))
+}
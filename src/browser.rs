@@ -0,0 +1,67 @@
+//! `--open`: once the server is listening, opens its address in the default
+//! browser with the platform's launcher, `open` on macOS, `start` on Windows
+//! and `xdg-open` elsewhere. Without a launcher or a display it only says so.
+
+use std::net::SocketAddr;
+use std::process::{Command, Stdio};
+
+/// The URL for a bound address. Wildcard addresses are opened on loopback,
+/// which they include.
+pub fn url(address: SocketAddr) -> String {
+    let host = match address.ip() {
+        ip if ip.is_unspecified() => "localhost".to_string(),
+        ip if ip.is_ipv6() => format!("[{}]", ip),
+        ip => ip.to_string(),
+    };
+    format!("http://{}:{}/", host, address.port())
+}
+
+fn launcher(url: &str) -> Command {
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("open");
+        command.arg(url);
+        command
+    }
+    #[cfg(windows)]
+    {
+        // `start` is built into cmd; its first quoted argument is a title
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "", url]);
+        command
+    }
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        let mut command = Command::new("xdg-open");
+        command.arg(url);
+        command
+    }
+}
+
+pub fn open(url: &str) {
+    // A server over SSH or in a container has no browser to show
+    #[cfg(not(any(target_os = "macos", windows)))]
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        eprintln!("Not opening a browser: no display is available");
+        return;
+    }
+
+    let spawned = launcher(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        Ok(mut child) => {
+            // Reap the launcher, which exits once it has handed the URL over
+            let url = url.to_string();
+            std::thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => {
+                    eprintln!("Not opening a browser: the launcher failed to open {}", url)
+                }
+                _ => {}
+            });
+        }
+        Err(e) => eprintln!("Not opening a browser: no launcher found ({})", e),
+    }
+}
@@ -0,0 +1,353 @@
+use crate::compress;
+use crate::config::Config;
+use crate::data::Globals;
+use crate::limits;
+use crate::live_reload;
+use crate::pages::{self, AMP_PATH, AMP_SUFFIX, PAGES_DIR, Page, TEMPLATE_EXTENSION};
+use crate::post_process::RenderedPage;
+use crate::render::{self, Site, TEMPLATES_DIR};
+use crate::signed_urls;
+use crate::static_files::STATIC_DIR;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::fs;
+
+/// Written to the output root; lists every file the build produced.
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// How often `--watch` checks the sources for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the sources must stay unchanged before a rebuild starts, so a
+/// burst of saves triggers a single build.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+type CollectFilesResult = Result<Vec<PathBuf>, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    /// Path relative to the output directory.
+    output: String,
+    /// `sha256-` followed by the hex digest of the output file.
+    hash: String,
+    /// Compressed variants written with `--compress`, by encoding.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    compressed: BTreeMap<&'static str, String>,
+}
+
+/// Keyed by source path. `BTreeMap`s keep the output sorted, so identical
+/// inputs produce an identical manifest.
+#[derive(Debug, Serialize)]
+struct Manifest {
+    version: &'static str,
+    /// Unix seconds: `SOURCE_DATE_EPOCH` if set, otherwise when the sources
+    /// last changed.
+    timestamp: u64,
+    pages: BTreeMap<String, ManifestEntry>,
+    assets: BTreeMap<String, ManifestEntry>,
+}
+
+fn content_hash(content: &[u8]) -> String {
+    let digest = Sha256::digest(content);
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256-{}", hex)
+}
+
+/// Forward-slash form of a relative path, so the manifest is portable.
+pub fn manifest_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+async fn build_timestamp(config: &Config) -> u64 {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+    {
+        return epoch;
+    }
+
+    live_reload::site_modified(config)
+        .await
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Every file below `dir`, relative to it.
+pub fn collect_files<'a>(
+    dir: &'a Path,
+    relative: PathBuf,
+) -> Pin<Box<dyn Future<Output = CollectFilesResult> + 'a>> {
+    Box::pin(async move {
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(dir.join(&relative)).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = relative.join(entry.file_name());
+            if entry.metadata().await?.is_dir() {
+                files.extend(collect_files(dir, path).await?);
+            } else {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    })
+}
+
+async fn write_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, content).await
+}
+
+/// Renders a single page, as requested at `/<page>`, with live reload off.
+/// Used by the `render` command.
+pub async fn render_page(
+    config: &Config,
+    globals: &Globals,
+    page: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut config = config.clone();
+    config.html.live_reload = Some(false);
+
+    let site = Site::load(&config).await?;
+    let page = page.trim_matches('/');
+    let page = match page.is_empty() {
+        true => config.routing.default_page(),
+        false => page,
+    };
+    let (template_name, _) = pages::resolve_template(&site.handlebars, page)
+        .ok_or_else(|| format!("page '{}' not found", page))?;
+
+    let context = site.context(&config, globals, page).await?;
+    let scoped = site.scoped_registry(&template_name);
+    let handlebars = scoped.as_ref().unwrap_or(&*site.handlebars);
+    let rendered = limits::render(handlebars, &config, &template_name, &context)?;
+
+    let content_type = site
+        .page(&template_name)
+        .map_or(pages::DEFAULT_CONTENT_TYPE, Page::content_type);
+    let page = RenderedPage {
+        template_name: &template_name,
+        content_type,
+        panel: None,
+    };
+    Ok(render::post_process(&config, &page, rendered))
+}
+
+/// The pages `build` writes and `check` renders: every non-private page,
+/// with the route whose data it gets and its output path. AMP variants get
+/// their page's data and are written below `amp/`.
+fn exported_pages(site: &Site) -> impl Iterator<Item = (&Page, &str, String)> {
+    site.pages
+        .iter()
+        .filter(|page| !page.is_private())
+        .map(|page| match page.name.strip_suffix(AMP_SUFFIX) {
+            Some(name) => (page, name, format!("{}/{}.html", AMP_PATH, name)),
+            None => (page, page.name.as_str(), format!("{}.html", page.name)),
+        })
+}
+
+/// The outcome of rendering one page in `check`.
+#[derive(Debug, Serialize)]
+pub struct PageCheck {
+    pub page: String,
+    pub template: String,
+    /// `ok` or `failed`.
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Renders every non-private page as `build` would, in strict mode and
+/// without writing anything, reporting how each went. Fails only when the
+/// templates can't be loaded at all.
+pub async fn check(
+    config: &Config,
+    globals: &Globals,
+) -> Result<Vec<PageCheck>, Box<dyn std::error::Error>> {
+    let mut config = config.clone();
+    config.html.live_reload = Some(false);
+    config.render.strict = Some(true);
+
+    let site = Site::load(&config).await?;
+    let mut checks = Vec::new();
+    for (page, route, _) in exported_pages(&site) {
+        let template_name = page.template_name();
+        let rendered = match site.context(&config, globals, route).await {
+            Ok(context) => {
+                let scoped = site.scoped_registry(&template_name);
+                let handlebars = scoped.as_ref().unwrap_or(&*site.handlebars);
+                limits::render(handlebars, &config, &template_name, &context)
+                    .map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        checks.push(PageCheck {
+            page: page.name.clone(),
+            template: template_name,
+            status: match rendered {
+                Ok(_) => "ok",
+                Err(_) => "failed",
+            },
+            error: rendered.err(),
+        });
+    }
+    Ok(checks)
+}
+
+/// Renders every non-private page to `<name>.html` under `out_dir`, copies
+/// the static directory and writes `manifest.json`.
+pub async fn build(
+    config: &Config,
+    globals: &Globals,
+    out_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Exported pages are never served by this process
+    let mut config = config.clone();
+    config.html.live_reload = Some(false);
+
+    let site = Site::load(&config).await?;
+    let mut manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION"),
+        timestamp: build_timestamp(&config).await,
+        pages: BTreeMap::new(),
+        assets: BTreeMap::new(),
+    };
+    let mut failures = 0;
+
+    for (page, route, output) in exported_pages(&site) {
+        let template_name = page.template_name();
+        let context = site.context(&config, globals, route).await?;
+        let scoped = site.scoped_registry(&template_name);
+        let handlebars = scoped.as_ref().unwrap_or(&*site.handlebars);
+        let rendered = match limits::render(handlebars, &config, &template_name, &context) {
+            Ok(rendered) => {
+                let processed = RenderedPage {
+                    template_name: &template_name,
+                    content_type: page.content_type(),
+                    panel: None,
+                };
+                render::post_process(&config, &processed, rendered)
+            }
+            Err(e) => {
+                eprintln!("Failed to render '{}': {}", template_name, e);
+                failures += 1;
+                continue;
+            }
+        };
+
+        write_file(&out_dir.join(&output), rendered.as_bytes()).await?;
+        let compressed =
+            compress::write_variants(&config.build, out_dir, &output, rendered.as_bytes()).await?;
+        println!("Built {}", output);
+
+        let source = format!(
+            "{}/{}/{}.{}",
+            TEMPLATES_DIR, PAGES_DIR, page.name, TEMPLATE_EXTENSION
+        );
+        manifest.pages.insert(
+            source,
+            ManifestEntry {
+                output,
+                hash: content_hash(rendered.as_bytes()),
+                compressed,
+            },
+        );
+    }
+
+    let static_dir = config.static_dir();
+    if static_dir.exists() {
+        for file in collect_files(&static_dir, PathBuf::new()).await? {
+            // A static host can't check signatures, so gated files stay out
+            if signed_urls::is_signed(&config, &manifest_path(&file)) {
+                continue;
+            }
+            let content = fs::read(static_dir.join(&file)).await?;
+            let relative = Path::new(STATIC_DIR).join(&file);
+            write_file(&out_dir.join(&relative), &content).await?;
+
+            let path = manifest_path(&relative);
+            let compressed =
+                compress::write_variants(&config.build, out_dir, &path, &content).await?;
+            manifest.assets.insert(
+                path.clone(),
+                ManifestEntry {
+                    output: path,
+                    hash: content_hash(&content),
+                    compressed,
+                },
+            );
+        }
+    }
+
+    let mut manifest_json = serde_json::to_string_pretty(&manifest)?;
+    manifest_json.push('\n');
+    write_file(&out_dir.join(MANIFEST_FILE), manifest_json.as_bytes()).await?;
+
+    println!(
+        "Built {} pages and {} static files into {}",
+        manifest.pages.len(),
+        manifest.assets.len(),
+        out_dir.display()
+    );
+
+    match failures {
+        0 => Ok(()),
+        _ => Err(format!("{} pages failed to render", failures).into()),
+    }
+}
+
+/// Builds once, then rebuilds everything whenever the sources change. The
+/// globals file is reloaded for each build. Failed builds are reported and
+/// watching continues.
+pub async fn watch(config: &Config, out_dir: &Path) {
+    let mut built = live_reload::site_modified(config).await;
+    rebuild(config, out_dir).await;
+    println!("Watching for changes (Ctrl-C to stop)");
+
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        let mut modified = live_reload::site_modified(config).await;
+        if modified == built {
+            continue;
+        }
+
+        // Wait for the sources to settle
+        loop {
+            tokio::time::sleep(WATCH_DEBOUNCE).await;
+            let latest = live_reload::site_modified(config).await;
+            if latest == modified {
+                break;
+            }
+            modified = latest;
+        }
+
+        println!("Change detected, rebuilding");
+        built = modified;
+        rebuild(config, out_dir).await;
+    }
+}
+
+async fn rebuild(config: &Config, out_dir: &Path) {
+    let globals = match Globals::load(&config.data).await {
+        Ok(globals) => globals,
+        Err(e) => {
+            eprintln!("Failed to load globals file: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = build(config, &globals, out_dir).await {
+        eprintln!("Build failed: {}", e);
+    }
+}
@@ -0,0 +1,209 @@
+//! How well the in-memory caches are doing, for tuning them: `/_debug/cache`
+//! reports hits, misses, sizes and when each cache was last reloaded as
+//! JSON. It is routed when `token` is set under `[admin]`, and requires it.
+//! `/metrics` includes the same numbers, see `src/metrics.rs`.
+
+use crate::admin;
+use crate::config::Config;
+use crate::helpers;
+use crate::remote_partials;
+use crate::render_cache::RenderCache;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value, json};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DEBUG_ENDPOINT: &str = "/_debug/cache";
+
+/// Counted on every lookup, so they are plain atomics rather than locks.
+#[derive(Default)]
+pub struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// Milliseconds since the Unix epoch, or 0 before the first reload.
+    reloaded_at: AtomicU64,
+}
+
+impl Counters {
+    pub const fn new() -> Counters {
+        Counters {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            reloaded_at: AtomicU64::new(0),
+        }
+    }
+
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that entries were loaded or dropped just now.
+    pub fn reloaded(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        self.reloaded_at.store(now, Ordering::Relaxed);
+    }
+
+    /// The counts so far, for a cache holding `entries` of `bytes` in total.
+    pub fn stats(&self, name: &'static str, entries: usize, bytes: Option<usize>) -> CacheStats {
+        let reloaded_at = match self.reloaded_at.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(UNIX_EPOCH + Duration::from_millis(millis)),
+        };
+        CacheStats {
+            name,
+            hits: Some(self.hits.load(Ordering::Relaxed)),
+            misses: Some(self.misses.load(Ordering::Relaxed)),
+            entries,
+            bytes,
+            reloaded_at,
+        }
+    }
+}
+
+/// A snapshot of one cache.
+pub struct CacheStats {
+    pub name: &'static str,
+    /// Unset for caches that aren't looked up, such as remote partials.
+    pub hits: Option<u64>,
+    pub misses: Option<u64>,
+    pub entries: usize,
+    /// Unset where the size of an entry isn't meaningful.
+    pub bytes: Option<usize>,
+    /// When entries were last loaded or dropped.
+    pub reloaded_at: Option<SystemTime>,
+}
+
+impl CacheStats {
+    fn to_json(&self) -> Value {
+        let hit_ratio = match (self.hits, self.misses) {
+            (Some(hits), Some(misses)) if hits + misses > 0 => {
+                Value::from(hits as f64 / (hits + misses) as f64)
+            }
+            _ => Value::Null,
+        };
+        json!({
+            "hits": self.hits,
+            "misses": self.misses,
+            "hit_ratio": hit_ratio,
+            "entries": self.entries,
+            "bytes": self.bytes,
+            "reloaded_at": self.reloaded_at.map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+        })
+    }
+}
+
+/// Every cache of the requested site, in the order they are reported.
+fn collect(render_cache: &RenderCache) -> Vec<CacheStats> {
+    let mut stats = vec![render_cache.stats()];
+    stats.extend(helpers::cache_stats());
+    stats.push(remote_partials::stats());
+    stats
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+        .finish()
+}
+
+pub async fn debug(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    render_cache: web::Data<RenderCache>,
+) -> Result<HttpResponse> {
+    if !admin::is_authorized(&req, &config) {
+        return Ok(unauthorized());
+    }
+    let caches: Map<String, Value> = collect(&render_cache)
+        .iter()
+        .map(|stats| (stats.name.to_string(), stats.to_json()))
+        .collect();
+    Ok(HttpResponse::Ok().json(json!({ "caches": caches })))
+}
+
+/// Appends one metric family, with a sample per cache that has a value.
+fn push_family(
+    text: &mut String,
+    stats: &[CacheStats],
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: impl Fn(&CacheStats) -> Option<f64>,
+) {
+    text.push_str(&format!(
+        "# HELP {} {}\n# TYPE {} {}\n",
+        name, help, name, kind
+    ));
+    for cache in stats {
+        if let Some(value) = value(cache) {
+            text.push_str(&format!("{}{{cache=\"{}\"}} {}\n", name, cache.name, value));
+        }
+    }
+}
+
+/// Appends the cache metric families to a Prometheus text exposition.
+pub fn write_metrics(text: &mut String, render_cache: &RenderCache) {
+    let stats = collect(render_cache);
+    push_family(
+        text,
+        &stats,
+        "hbs_composer_cache_hits_total",
+        "counter",
+        "Lookups answered from the cache.",
+        |cache| cache.hits.map(|hits| hits as f64),
+    );
+    push_family(
+        text,
+        &stats,
+        "hbs_composer_cache_misses_total",
+        "counter",
+        "Lookups that had to load or render.",
+        |cache| cache.misses.map(|misses| misses as f64),
+    );
+    push_family(
+        text,
+        &stats,
+        "hbs_composer_cache_hit_ratio",
+        "gauge",
+        "Share of lookups answered from the cache since startup.",
+        |cache| match (cache.hits?, cache.misses?) {
+            (0, 0) => None,
+            (hits, misses) => Some(hits as f64 / (hits + misses) as f64),
+        },
+    );
+    push_family(
+        text,
+        &stats,
+        "hbs_composer_cache_entries",
+        "gauge",
+        "Entries currently cached.",
+        |cache| Some(cache.entries as f64),
+    );
+    push_family(
+        text,
+        &stats,
+        "hbs_composer_cache_bytes",
+        "gauge",
+        "Size of the cached entries.",
+        |cache| cache.bytes.map(|bytes| bytes as f64),
+    );
+    push_family(
+        text,
+        &stats,
+        "hbs_composer_cache_reloaded_timestamp_seconds",
+        "gauge",
+        "When entries were last loaded or dropped.",
+        |cache| {
+            let since = cache.reloaded_at?.duration_since(UNIX_EPOCH).ok()?;
+            Some(since.as_secs_f64())
+        },
+    );
+}
@@ -0,0 +1,79 @@
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+fn split_key_value(arg: &str) -> Result<(String, &str), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value)),
+        _ => Err("expected KEY=VALUE".to_string()),
+    }
+}
+
+fn parse_data_file(arg: &str) -> Result<(String, PathBuf), String> {
+    let (key, path) = split_key_value(arg)?;
+    Ok((key, PathBuf::from(path)))
+}
+
+fn parse_data(arg: &str) -> Result<(String, Value), String> {
+    let (key, json) = split_key_value(arg)?;
+    let value = serde_json::from_str(json)
+        .map_err(|e| format!("invalid JSON ({}); quote strings, e.g. key='\"text\"'", e))?;
+    Ok((key, value))
+}
+
+/// Handlebars development server.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Render every page into DIR as static files and exit instead of serving
+    #[arg(long, value_name = "DIR")]
+    pub build: Option<PathBuf>,
+
+    /// With --build, keep running and rebuild whenever the sources change
+    #[arg(long, requires = "build")]
+    pub watch: bool,
+
+    /// With --build, also write compressed variants of each output file, as
+    /// set under `[build]`
+    #[arg(long, requires = "build")]
+    pub compress: bool,
+
+    /// Number of worker threads, overriding `workers` under `[server]`
+    #[arg(long, value_name = "N")]
+    pub workers: Option<NonZeroUsize>,
+
+    /// Open the server's address in the default browser once it is listening
+    #[arg(long, conflicts_with = "build")]
+    pub open: bool,
+
+    /// Listen on a Unix domain socket at PATH, overriding `uds` under `[server]`
+    #[arg(long, value_name = "PATH")]
+    pub bind_uds: Option<PathBuf>,
+
+    /// Merge the file at PATH, parsed by its extension, into the context under
+    /// KEY; repeatable
+    #[arg(long = "data-file", value_name = "KEY=PATH", value_parser = parse_data_file, global = true)]
+    pub data_files: Vec<(String, PathBuf)>,
+
+    /// Merge a JSON value into the context under KEY; repeatable
+    #[arg(long = "data", value_name = "KEY=JSON", value_parser = parse_data, global = true)]
+    pub data: Vec<(String, Value)>,
+
+    /// Merge a JSON object read from stdin into the context, over --data and
+    /// --data-file values; for `render` and --build
+    #[arg(long, global = true)]
+    pub stdin: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Render one page to stdout and exit, e.g. `render blog/post`
+    Render {
+        /// Page path as in its URL, without the leading slash
+        page: String,
+    },
+}
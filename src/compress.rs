@@ -0,0 +1,78 @@
+//! Compressed variants of static-export output, written beside each file
+//! with `--compress`, e.g. `about.html.gz` and `about.html.br`, for hosts
+//! that serve them directly.
+
+use crate::config::{BuildConfig, CompressEncoding};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use tokio::fs;
+
+/// Files that are variants themselves, such as a `.br` file already in
+/// `static/`, are never compressed again.
+const COMPRESSED_EXTENSIONS: [&str; 2] = ["gz", "br"];
+
+/// Brotli's highest quality; the build runs once, so ratio wins over speed.
+const BROTLI_QUALITY: u32 = 11;
+const BROTLI_WINDOW: u32 = 22;
+
+fn encode(encoding: CompressEncoding, content: &[u8]) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        CompressEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+            encoder.write_all(content)?;
+            encoder.finish()
+        }
+        CompressEncoding::Br => {
+            let mut compressed = Vec::new();
+            {
+                let mut encoder = brotli::CompressorWriter::new(
+                    &mut compressed,
+                    4096,
+                    BROTLI_QUALITY,
+                    BROTLI_WINDOW,
+                );
+                encoder.write_all(content)?;
+            }
+            Ok(compressed)
+        }
+    }
+}
+
+/// Writes the configured variants of `content`, which was written to
+/// `out_dir/relative`. Returns the variants' paths relative to `out_dir`, by
+/// encoding name, leaving out those that wouldn't save at least
+/// `compress_min_saving` of the size.
+pub async fn write_variants(
+    config: &BuildConfig,
+    out_dir: &Path,
+    relative: &str,
+    content: &[u8],
+) -> std::io::Result<BTreeMap<&'static str, String>> {
+    let mut variants = BTreeMap::new();
+    let is_variant = Path::new(relative).extension().is_some_and(|extension| {
+        COMPRESSED_EXTENSIONS
+            .iter()
+            .any(|known| extension == *known)
+    });
+    if !config.compress || is_variant || content.is_empty() {
+        return Ok(variants);
+    }
+
+    for &encoding in &config.compress_encodings {
+        let compressed = encode(encoding, content)?;
+        let saving = 1.0 - compressed.len() as f64 / content.len() as f64;
+        let output = format!("{}.{}", relative, encoding.extension());
+        if saving < config.compress_min_saving {
+            // A variant from an earlier build would no longer match
+            let _ = fs::remove_file(out_dir.join(&output)).await;
+            continue;
+        }
+
+        fs::write(out_dir.join(&output), &compressed).await?;
+        variants.insert(encoding.name(), output);
+    }
+    Ok(variants)
+}
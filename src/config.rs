@@ -0,0 +1,1412 @@
+use crate::data::{DATA_DIR, LOCALES_DIR};
+use crate::render::TEMPLATES_DIR;
+use crate::static_files::STATIC_DIR;
+use chrono_tz::Tz;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Optional configuration file, read from the working directory at startup.
+pub const CONFIG_FILE: &str = "hbs-composer.toml";
+
+/// Selects the environment, and so the config overlay, e.g. `prod`.
+const ENVIRONMENT_VAR: &str = "HBS_COMPOSER_ENV";
+
+/// Overrides `signing_secret` under `[static]`, to keep it out of the file.
+const SIGNING_SECRET_VAR: &str = "HBS_COMPOSER_SIGNING_SECRET";
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+const DEFAULT_RESERVED_PREFIX: &str = "_";
+
+/// Appended to the keys of text files with `collisions = "namespace"`.
+const DEFAULT_TEXT_SUFFIX: &str = "_text";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Unset keeps every environment default off, as before environments
+    /// existed, and loads no overlay.
+    pub environment: Option<Environment>,
+    pub server: ServerConfig,
+    pub site: SiteConfig,
+    pub data: DataConfig,
+    pub routing: RoutingConfig,
+    pub forms: Vec<FormConfig>,
+    pub html: HtmlConfig,
+    pub render: RenderConfig,
+    pub preview: PreviewConfig,
+    pub admin: AdminConfig,
+    pub render_api: RenderApiConfig,
+    pub access_log: AccessLogConfig,
+    pub metrics: MetricsConfig,
+    pub llms_txt: LlmsTxtConfig,
+    pub maintenance: MaintenanceConfig,
+    pub rate_limit: RateLimitConfig,
+    #[serde(rename = "static")]
+    pub static_files: StaticConfig,
+    pub build: BuildConfig,
+    pub icons: IconsConfig,
+    pub i18n: I18nConfig,
+    pub time_ago: TimeAgoConfig,
+    pub reading_time: ReadingTimeConfig,
+    pub title: TitleConfig,
+    pub images: ImagesConfig,
+    pub derived: Vec<DerivedConfig>,
+    /// Feature flags for the `feature` helper, e.g. `new-nav = true`.
+    pub flags: BTreeMap<String, bool>,
+    pub transforms: Vec<TransformEntry>,
+    /// Helpers declared without code, registered alongside the built-ins.
+    pub helpers: Vec<DeclaredHelperConfig>,
+    pub remote_partials: Vec<RemotePartialConfig>,
+    /// Serves `/manifest.webmanifest` when set.
+    pub manifest: Option<ManifestConfig>,
+    /// Further sites, each served from its own directory to the hosts it
+    /// lists. Other hosts get the site in the working directory.
+    pub sites: Vec<HostSiteConfig>,
+    /// Directory holding `templates/`, `data/` and `static/`. Empty for the
+    /// working directory; set for each of `sites`.
+    #[serde(skip)]
+    pub root: PathBuf,
+    /// The files this config was read from, base first.
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+/// Selects the defaults for minification, strict rendering, error details
+/// and live reload. Each can still be set individually.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    /// Readable output, strict rendering, detailed errors and live reload.
+    Dev,
+    /// Minified output, lenient rendering and terse errors.
+    Prod,
+}
+
+impl Environment {
+    pub fn name(self) -> &'static str {
+        match self {
+            Environment::Dev => "dev",
+            Environment::Prod => "prod",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Environment> {
+        match name {
+            "dev" => Some(Environment::Dev),
+            "prod" => Some(Environment::Prod),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Worker threads. Defaults to one per CPU.
+    pub workers: Option<NonZeroUsize>,
+    /// TCP address to listen on. Defaults to `127.0.0.1:8080` unless only a
+    /// Unix socket is configured.
+    pub address: Option<String>,
+    /// Unix domain socket to listen on, in addition to `address` if both are set.
+    pub uds: Option<PathBuf>,
+    /// Octal permissions for the socket file, e.g. `660`.
+    pub uds_mode: Option<String>,
+    /// Refuse to start when `templates/pages/` is missing or has no
+    /// templates, instead of only warning.
+    pub require_pages: bool,
+    /// Render `smoke_test_page` once at startup and log the outcome.
+    pub smoke_test: bool,
+    /// The page rendered by the smoke test, as in its URL. Defaults to
+    /// `default_page` under `[routing]`.
+    pub smoke_test_page: Option<String>,
+    /// Refuse to start when the smoke test fails, instead of only logging.
+    pub require_smoke_test: bool,
+    /// Seconds clients are asked to wait, with `Retry-After`, before
+    /// retrying a page whose data is unavailable, or during maintenance.
+    pub retry_after: u64,
+    /// Also accept cleartext HTTP/2 on `address`, for a proxy that speaks
+    /// it with prior knowledge. HTTP/1.1 is still served.
+    pub h2c: bool,
+    /// Also serve HTTPS, with HTTP/2 negotiated through ALPN.
+    pub tls: Option<TlsConfig>,
+}
+
+/// An HTTPS listener, declared with `[server.tls]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// TCP address to listen on, e.g. `0.0.0.0:8443`.
+    pub address: String,
+    /// PEM certificate chain, leaf first.
+    pub cert: PathBuf,
+    /// PEM private key for the leaf certificate.
+    pub key: PathBuf,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            workers: None,
+            address: None,
+            uds: None,
+            uds_mode: None,
+            require_pages: false,
+            smoke_test: true,
+            smoke_test_page: None,
+            require_smoke_test: false,
+            retry_after: 30,
+            h2c: false,
+            tls: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Settings that can't work together.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.h2c && self.tcp_address().is_none() {
+            return Err(
+                "h2c needs a TCP address; the Unix socket only serves HTTP/1.1".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The TCP address to bind, if any.
+    pub fn tcp_address(&self) -> Option<&str> {
+        match (&self.address, &self.uds) {
+            (Some(address), _) => Some(address),
+            (None, Some(_)) => None,
+            (None, None) => Some(DEFAULT_ADDRESS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Public origin used for absolute URLs, e.g. `https://example.com`.
+    /// Defaults to the scheme and host of the incoming request.
+    pub base_url: Option<String>,
+    /// IANA timezone for the `now` helper, e.g. `Europe/Berlin`. Defaults to UTC.
+    pub timezone: Option<Tz>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DataConfig {
+    /// Merge the contents of every subdirectory into its parent instead of
+    /// nesting them under the directory name. A directory's `_config.json`
+    /// can override this for that directory.
+    pub flatten: bool,
+    /// Glob patterns, relative to the data root, a file must match to be
+    /// loaded. An empty list includes every file.
+    pub include: Vec<String>,
+    /// Glob patterns, relative to the data root, for files that are never
+    /// loaded. Exclusions take precedence over `include`.
+    pub exclude: Vec<String>,
+    /// JSON or YAML file of site-wide values, loaded once at startup and
+    /// merged beneath the data directory.
+    pub globals: Option<PathBuf>,
+    /// Only load the subdirectories of `data/` that lie on the requested
+    /// route, e.g. `data/blog` for `/blog/post`, plus the `shared` ones.
+    /// Files at the top of `data/` are always loaded.
+    pub scope_by_route: bool,
+    /// Directories, relative to the data root, loaded for every route when
+    /// `scope_by_route` is on.
+    pub shared: Vec<PathBuf>,
+    /// Serve `/_debug/data`, listing the files behind each context key.
+    /// Never served in `prod`.
+    pub provenance: bool,
+    /// What happens when two files or directories load into the same key,
+    /// such as `about.txt` and `about.json`.
+    pub collisions: CollisionPolicy,
+    /// With `collisions = "namespace"`, appended to the key of every `.txt`
+    /// file. Defaults to `_text`.
+    pub text_suffix: Option<String>,
+    /// How a file's name becomes its key.
+    pub keys: KeyStyle,
+    /// What happens to a file or directory whose key contains a dot, which
+    /// dotted paths such as `{{site.config.title}}` can't reach.
+    pub dotted_keys: DottedKeyPolicy,
+    /// Data files larger than this many bytes are never read whole, see
+    /// `oversize`. Unset reads every file.
+    pub max_file_bytes: Option<u64>,
+    /// What happens to a file over `max_file_bytes`.
+    pub oversize: OversizePolicy,
+    /// Warn when a page's assembled context serializes to more than this
+    /// many bytes. Unset never checks.
+    pub context_budget_bytes: Option<usize>,
+    /// Directories, relative to the data root, whose `.html`, `.htm` and
+    /// `.txt` files are registered as raw partials instead of being loaded.
+    pub fragments: Vec<PathBuf>,
+    /// Values beneath the globals and the data directory, so a project
+    /// without data files still has them.
+    pub defaults: Map<String, Value>,
+    /// Values from `--data-file` and `--data`, merged over the data directory.
+    #[serde(skip)]
+    pub overrides: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Warn, and keep the value loaded last.
+    #[default]
+    LastWins,
+    /// Fail the data load.
+    Error,
+    /// Load `.txt` files under their name plus `text_suffix`, so they never
+    /// collide with other formats; other collisions warn as with `LastWins`.
+    Namespace,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStyle {
+    /// The name without its last extension: `site.config.json` is
+    /// `site.config`.
+    #[default]
+    LastExtension,
+    /// The name up to its first dot: `site.config.json` is `site`.
+    AllExtensions,
+    /// The name without its last extension, slugified: `site.config.json`
+    /// is `site-config`. Directory names are slugified too.
+    Slug,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DottedKeyPolicy {
+    /// Load the entry as it is.
+    Allow,
+    /// Load the entry, warning once.
+    #[default]
+    Warn,
+    /// Leave the entry out of the context, warning once.
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizePolicy {
+    /// Leave the file out of the context, with a warning.
+    #[default]
+    Skip,
+    /// Stream a JSON file whose top level is an array and keep the items
+    /// read within the limit. Other files are skipped.
+    Truncate,
+}
+
+impl DataConfig {
+    pub fn text_suffix(&self) -> &str {
+        self.text_suffix.as_deref().unwrap_or(DEFAULT_TEXT_SUFFIX)
+    }
+
+    /// Whether `relative_path`, relative to the data root, lies in one of
+    /// the `fragments` directories.
+    pub fn is_fragment(&self, relative_path: &Path) -> bool {
+        self.fragments
+            .iter()
+            .any(|dir| relative_path.starts_with(dir))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub trailing_slash: TrailingSlashPolicy,
+    /// Redirect the other form of a page URL to the canonical one with a
+    /// 301. When off, both forms serve the page.
+    pub redirect: bool,
+    pub root: RootPolicy,
+    /// Status of the redirect from `/`. Defaults to 302 in `dev`, so browsers
+    /// don't cache it while the root is being changed, and 301 elsewhere.
+    pub root_status: Option<RedirectStatus>,
+    /// Paths of retired pages, answered with a 410 Gone.
+    pub gone: GonePaths,
+    /// The page served for an empty path, e.g. `home`.
+    pub default_page: String,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> RoutingConfig {
+        RoutingConfig {
+            trailing_slash: TrailingSlashPolicy::default(),
+            redirect: true,
+            root: RootPolicy::default(),
+            root_status: None,
+            gone: GonePaths::default(),
+            default_page: "index".to_string(),
+        }
+    }
+}
+
+impl RoutingConfig {
+    /// `default_page` without surrounding slashes, `index` if that leaves
+    /// nothing.
+    pub fn default_page(&self) -> &str {
+        match self.default_page.trim_matches('/') {
+            "" => "index",
+            page => page,
+        }
+    }
+
+    /// Where `/` redirects to, unless it is rendered.
+    pub fn root_redirect(&self) -> Option<String> {
+        match &self.root {
+            RootPolicy::Redirect(Some(path)) => Some(path.clone()),
+            RootPolicy::Redirect(None) => Some(format!("/{}", self.default_page())),
+            RootPolicy::Render => None,
+        }
+    }
+}
+
+/// Glob patterns matched against the request path, e.g. `/blog/2019/**`.
+/// `*` stays within a path segment; `**` spans them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct GonePaths(GlobSet);
+
+impl GonePaths {
+    /// Whether `path`, starting with `/` and without a trailing slash, was
+    /// retired.
+    pub fn contains(&self, path: &str) -> bool {
+        self.0.is_match(path)
+    }
+}
+
+impl TryFrom<Vec<String>> for GonePaths {
+    type Error = globset::Error;
+
+    fn try_from(patterns: Vec<String>) -> Result<GonePaths, globset::Error> {
+        Ok(GonePaths(path_glob_set(&patterns)?))
+    }
+}
+
+/// Whether the request path `path` is `prefix` or below it, so `/static`
+/// covers `/static/app.css` but not `/staticky`. A prefix ending in `/`
+/// only covers what is below it.
+pub fn is_path_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Patterns over request paths, with `*` within a segment and `**` across.
+fn path_glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = match pattern.trim_end_matches('/') {
+            "" => "/",
+            pattern => pattern,
+        };
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    builder.build()
+}
+
+/// The page paths a transform runs for, as patterns like those of
+/// `GonePaths`. No patterns means every page.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct RoutePatterns(Option<GlobSet>);
+
+impl RoutePatterns {
+    /// Whether `path`, starting with `/` and without a trailing slash, is
+    /// covered.
+    pub fn matches(&self, path: &str) -> bool {
+        self.0
+            .as_ref()
+            .is_none_or(|patterns| patterns.is_match(path))
+    }
+}
+
+impl TryFrom<Vec<String>> for RoutePatterns {
+    type Error = globset::Error;
+
+    fn try_from(patterns: Vec<String>) -> Result<RoutePatterns, globset::Error> {
+        match patterns.is_empty() {
+            true => Ok(RoutePatterns(None)),
+            false => Ok(RoutePatterns(Some(path_glob_set(&patterns)?))),
+        }
+    }
+}
+
+/// A redirect status code: 301, 302, 307 or 308.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub struct RedirectStatus(u16);
+
+impl RedirectStatus {
+    pub const PERMANENT: RedirectStatus = RedirectStatus(301);
+    pub const FOUND: RedirectStatus = RedirectStatus(302);
+
+    pub fn code(self) -> u16 {
+        self.0
+    }
+}
+
+impl TryFrom<u16> for RedirectStatus {
+    type Error = String;
+
+    fn try_from(code: u16) -> Result<RedirectStatus, String> {
+        match code {
+            301 | 302 | 307 | 308 => Ok(RedirectStatus(code)),
+            _ => Err(format!(
+                "redirect status must be 301, 302, 307 or 308, got {}",
+                code
+            )),
+        }
+    }
+}
+
+/// How `/` is served: `"redirect"`, `"render"`, or a path to redirect to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum RootPolicy {
+    /// A redirect, with `root_status`, to the given path, or by default to
+    /// the default page.
+    Redirect(Option<String>),
+    /// The default page is rendered at `/` with a 200.
+    Render,
+}
+
+impl Default for RootPolicy {
+    fn default() -> RootPolicy {
+        RootPolicy::Redirect(None)
+    }
+}
+
+impl TryFrom<String> for RootPolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<RootPolicy, String> {
+        match value.as_str() {
+            "redirect" => Ok(RootPolicy::default()),
+            "render" => Ok(RootPolicy::Render),
+            path if path.starts_with('/') && path != "/" => Ok(RootPolicy::Redirect(Some(value))),
+            _ => Err(format!(
+                "root must be \"redirect\", \"render\" or a path other than /, got \"{}\"",
+                value
+            )),
+        }
+    }
+}
+
+/// Canonical form of page URLs; the other form is redirected to it. The
+/// home page is always `/`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlashPolicy {
+    /// No page URL ends with a slash: `/blog/` redirects to `/blog`.
+    #[default]
+    Remove,
+    /// Directory index pages end with a slash (`/blog` redirects to `/blog/`),
+    /// other pages don't.
+    Add,
+    /// Every page URL ends with a slash: `/about` redirects to `/about/`.
+    Always,
+    /// Both forms are served as requested; only repeated slashes are merged.
+    /// Generated links have no trailing slash.
+    Merge,
+}
+
+impl TrailingSlashPolicy {
+    /// Whether the canonical URL of a page ends with a slash, or `None` when
+    /// either form is canonical.
+    pub fn wants_slash(self, is_index: bool) -> Option<bool> {
+        match self {
+            TrailingSlashPolicy::Remove => Some(false),
+            TrailingSlashPolicy::Add => Some(is_index),
+            TrailingSlashPolicy::Always => Some(true),
+            TrailingSlashPolicy::Merge => None,
+        }
+    }
+}
+
+/// Post-processing applied to rendered pages.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HtmlConfig {
+    /// Give `<h2>`–`<h4>` headings without an `id` one derived from their text.
+    pub heading_ids: bool,
+    /// Append a `#` link to each heading (requires `heading_ids`).
+    pub heading_anchors: bool,
+    /// Minify rendered pages. Defaults to on in `prod`.
+    pub minify: Option<bool>,
+    /// Reload open pages when templates, data or static files change.
+    /// Defaults to on in `dev`.
+    pub live_reload: Option<bool>,
+    /// Add a panel listing the render context to served pages. Only added
+    /// in `dev`.
+    pub inspector: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// Fail rendering on missing variables. Defaults to on in `dev`.
+    pub strict: Option<bool>,
+    /// Include the underlying error in error responses. Defaults to on in `dev`.
+    pub error_details: Option<bool>,
+    /// Respond with 500 when a page renders to nothing but whitespace,
+    /// instead of a blank 200. Defaults to `strict`.
+    pub fail_on_empty: Option<bool>,
+    /// Prefix for the context keys the server injects, such as `pages`.
+    /// Defaults to `_`; set it to `""` for the unprefixed names.
+    pub reserved_prefix: Option<String>,
+    /// Stream pages whose output grows beyond this many bytes instead of
+    /// buffering them. Unset never streams.
+    pub stream_threshold: Option<usize>,
+    /// Reuse rendered pages until the sources change. Pages that must be
+    /// rendered on every request opt out with `cache: false` in front matter.
+    pub cache: bool,
+    /// `Cache-Control: max-age` in seconds for pages whose front matter has
+    /// no `cache`, which also limits how long the render cache keeps them.
+    /// Unset sends no `Cache-Control`.
+    pub max_age: Option<u64>,
+    /// What other requests for a page do while one re-renders it after its
+    /// cached render expired.
+    pub on_expiry: ExpiryPolicy,
+    /// The most renders the cache keeps; past it, the least recently used
+    /// is dropped.
+    pub cache_capacity: usize,
+    /// Query parameters cached renders are keyed by. Cacheable pages don't
+    /// see any others in `query`.
+    pub cache_query: Vec<String>,
+    /// Log pages whose data load or render takes longer than this, and keep
+    /// them for `/_debug/slow`. Unset logs none.
+    pub slow_threshold_ms: Option<u64>,
+    /// Fail a render whose output grows beyond this many bytes.
+    pub max_output_bytes: usize,
+    /// Fail a render whose partials can nest deeper than this, or include
+    /// each other in a cycle.
+    pub max_partial_depth: usize,
+    /// Skip the startup template check while the templates are unchanged
+    /// since the last successful one, see `src/template_cache.rs`. Caches
+    /// the check's outcome only, not compiled templates.
+    pub template_cache: bool,
+}
+
+/// How requests for a page whose cached render expired are answered while
+/// the first of them renders it again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryPolicy {
+    /// Wait for the new render.
+    #[default]
+    Wait,
+    /// Serve the expired render right away (stale-while-revalidate).
+    Stale,
+}
+
+impl Default for RenderConfig {
+    fn default() -> RenderConfig {
+        RenderConfig {
+            strict: None,
+            error_details: None,
+            fail_on_empty: None,
+            reserved_prefix: None,
+            stream_threshold: None,
+            cache: false,
+            max_age: None,
+            on_expiry: ExpiryPolicy::Wait,
+            cache_capacity: 1000,
+            cache_query: Vec::new(),
+            slow_threshold_ms: None,
+            max_output_bytes: 16 * 1024 * 1024,
+            max_partial_depth: 32,
+            template_cache: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    /// Line format. When unset, actix's default format is logged at `info`
+    /// level and only shown with `RUST_LOG=info`.
+    pub format: Option<AccessLogFormat>,
+    /// Request paths never logged, e.g. `/health`.
+    pub exclude: Vec<String>,
+    /// Regular expressions for request paths never logged, e.g. `^/static/`.
+    pub exclude_regex: Vec<String>,
+}
+
+/// Prometheus metrics; see `src/metrics.rs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` and count requests for it.
+    pub enabled: bool,
+}
+
+/// Answering every request with a 503; see `src/maintenance.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Turn maintenance mode on regardless of `file`.
+    pub enabled: bool,
+    /// Maintenance mode is on while this file exists, relative to the
+    /// project root.
+    pub file: PathBuf,
+    /// Path prefixes still served, besides health, metrics and admin routes.
+    pub exempt: Vec<String>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> MaintenanceConfig {
+        MaintenanceConfig {
+            enabled: false,
+            file: PathBuf::from(".maintenance"),
+            exempt: vec!["/static/".to_string()],
+        }
+    }
+}
+
+/// Per-client token buckets; see `src/rate_limit.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Requests allowed per window. Limiting is off while unset.
+    pub requests: Option<u32>,
+    /// Window length in seconds.
+    pub window: u64,
+    /// Requests a client may make at once; defaults to `requests`.
+    pub burst: Option<u32>,
+    /// Header set by a trusted reverse proxy with the client address, e.g.
+    /// `X-Forwarded-For`. Only set this when every request passes the proxy.
+    pub client_ip_header: Option<String>,
+    /// Path prefixes never limited, e.g. `/health`.
+    pub exempt: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            requests: None,
+            window: 60,
+            burst: None,
+            client_ip_header: None,
+            exempt: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// Apache common log format.
+    Common,
+    /// Apache combined log format: common plus referer and user agent.
+    Combined,
+    /// One JSON object per request with method, path, status, duration and bytes.
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StaticConfig {
+    /// Serve a `.br` or `.gz` sibling of a requested asset when the client
+    /// accepts that encoding.
+    pub precompressed: bool,
+    /// Send `Last-Modified` from the file's modification time and answer a
+    /// matching `If-Modified-Since` with a 304.
+    pub last_modified: bool,
+    /// Content types by file extension, e.g. `avif = "image/avif"`, over
+    /// the ones guessed from the extension.
+    pub mime_types: BTreeMap<String, String>,
+    /// Path prefixes below `/static/`, e.g. `downloads/`, only served with
+    /// an unexpired signature from the `signedUrl` helper.
+    pub signed: Vec<String>,
+    /// Key the signatures are made with; see `signing_secret()`.
+    pub signing_secret: Option<String>,
+    /// Seconds a signed URL stays valid unless `ttl=` is given.
+    pub signed_ttl: u64,
+}
+
+impl Default for StaticConfig {
+    fn default() -> StaticConfig {
+        StaticConfig {
+            precompressed: false,
+            last_modified: true,
+            mime_types: BTreeMap::new(),
+            signed: Vec::new(),
+            signing_secret: None,
+            signed_ttl: 3600,
+        }
+    }
+}
+
+impl StaticConfig {
+    /// `HBS_COMPOSER_SIGNING_SECRET`, else `signing_secret`. Empty values
+    /// count as unset.
+    pub fn signing_secret(&self) -> Option<String> {
+        std::env::var(SIGNING_SECRET_VAR)
+            .ok()
+            .or_else(|| self.signing_secret.clone())
+            .filter(|secret| !secret.is_empty())
+    }
+}
+
+/// Static export settings, for `--build`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BuildConfig {
+    /// Write compressed variants beside each output file. Set by `--compress`.
+    pub compress: bool,
+    /// The variants to write.
+    pub compress_encodings: Vec<CompressEncoding>,
+    /// Skip a variant that saves less than this fraction of the original
+    /// size, as for images that are already compressed.
+    pub compress_min_saving: f64,
+}
+
+impl Default for BuildConfig {
+    fn default() -> BuildConfig {
+        BuildConfig {
+            compress: false,
+            compress_encodings: vec![CompressEncoding::Gzip, CompressEncoding::Br],
+            compress_min_saving: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressEncoding {
+    Gzip,
+    Br,
+}
+
+impl CompressEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressEncoding::Gzip => "gzip",
+            CompressEncoding::Br => "br",
+        }
+    }
+
+    /// Appended to the compressed file's name, e.g. `about.html.gz`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            CompressEncoding::Gzip => "gz",
+            CompressEncoding::Br => "br",
+        }
+    }
+}
+
+/// Where the `icon` helper finds icons. With neither set, it emits
+/// same-document `<use href="#icon-NAME">` references.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IconsConfig {
+    /// SVG sprite with a `<symbol id="icon-NAME">` per icon, e.g.
+    /// `static/icons.svg`. Its ids are used to detect unknown icons.
+    pub sprite: Option<PathBuf>,
+    /// Directory of `NAME.svg` files to inline instead; takes precedence
+    /// over `sprite`.
+    pub dir: Option<PathBuf>,
+    /// Prefix of the symbol ids. Defaults to `icon-`.
+    pub prefix: Option<String>,
+}
+
+/// Web app manifest members. Unset optional members are left out of the
+/// document.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ManifestConfig {
+    pub name: Option<String>,
+    pub short_name: Option<String>,
+    pub description: Option<String>,
+    pub start_url: String,
+    pub scope: Option<String>,
+    pub display: String,
+    pub background_color: Option<String>,
+    pub theme_color: Option<String>,
+    pub icons: Vec<ManifestIcon>,
+}
+
+impl Default for ManifestConfig {
+    fn default() -> ManifestConfig {
+        ManifestConfig {
+            name: None,
+            short_name: None,
+            description: None,
+            start_url: "/".to_string(),
+            scope: None,
+            display: "standalone".to_string(),
+            background_color: None,
+            theme_color: None,
+            icons: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestIcon {
+    /// Path relative to `static/`, e.g. `icons/192.png`, or an absolute URL.
+    pub src: String,
+    /// e.g. `192x192`.
+    pub sizes: Option<String>,
+    /// Defaults to the type matching the file extension.
+    #[serde(rename = "type")]
+    pub icon_type: Option<String>,
+    /// e.g. `any maskable`.
+    pub purpose: Option<String>,
+}
+
+/// A site served by `Host` header, declared with `[[sites]]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HostSiteConfig {
+    /// e.g. `["blog.example.com", "www.blog.example.com"]`.
+    pub hosts: Vec<String>,
+    /// Directory with the site's `templates/`, `data/` and `static/`.
+    pub root: PathBuf,
+    /// Overrides `base_url` under `[site]` for this site.
+    pub base_url: Option<String>,
+}
+
+/// A context value computed from others, e.g. key `person.name` with value
+/// `"{person.first} {person.last}"`. See `src/derived.rs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DerivedConfig {
+    /// Dotted context path to set.
+    pub key: String,
+    /// Text with `{path}` references to other context values.
+    pub value: String,
+}
+
+/// A helper declared with `[[helpers]]`, selected by `type`. See
+/// `src/helpers/declared.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeclaredHelperConfig {
+    /// Looks its argument up in `values`.
+    Map {
+        name: String,
+        /// Output by argument, e.g. `draft = "Draft"`.
+        values: BTreeMap<String, String>,
+        /// Output for arguments missing from `values`; the argument itself
+        /// when unset.
+        default: Option<String>,
+    },
+    /// Renders `template` with the arguments as its context.
+    Template {
+        name: String,
+        template: String,
+        /// Names of the positional arguments in `template`, in order.
+        #[serde(default)]
+        params: Vec<String>,
+    },
+}
+
+impl DeclaredHelperConfig {
+    pub fn name(&self) -> &str {
+        match self {
+            DeclaredHelperConfig::Map { name, .. }
+            | DeclaredHelperConfig::Template { name, .. } => name,
+        }
+    }
+}
+
+/// A `[[transforms]]` entry: the transform, and the pages it runs for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransformEntry {
+    /// Page paths such as `/blog` or `/blog/**`; every page when empty.
+    #[serde(default)]
+    pub routes: RoutePatterns,
+    #[serde(flatten)]
+    pub transform: TransformConfig,
+}
+
+/// A context transform, selected by `type`. See `src/transforms.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformConfig {
+    /// Sorts the array at `key` by each item's `field`.
+    SortByDate {
+        key: String,
+        #[serde(default = "default_date_field")]
+        field: String,
+        /// Newest first by default.
+        #[serde(default)]
+        order: SortOrder,
+    },
+    /// Adds `reading_time` in minutes to the items at `key`.
+    ReadingTime {
+        key: String,
+        #[serde(default = "default_body_field")]
+        field: String,
+        /// Defaults to `words_per_minute` under `[reading_time]`.
+        words_per_minute: Option<u32>,
+    },
+    /// Keeps the release notes at `key` that have a semantic `version`,
+    /// newest first.
+    Releases {
+        #[serde(default = "default_releases_key")]
+        key: String,
+    },
+    /// Sorts the array at `key` by each item's `field`, numbers numerically.
+    Sort {
+        key: String,
+        field: String,
+        #[serde(default = "default_sort_order")]
+        order: SortOrder,
+    },
+    /// Keeps `count` items of the array at `key`, after skipping `offset`.
+    Limit {
+        key: String,
+        count: usize,
+        #[serde(default)]
+        offset: usize,
+    },
+    /// Replaces the array at `key`, or sets `into`, with `{key, items}`
+    /// groups by each item's `field`.
+    Group {
+        key: String,
+        field: String,
+        into: Option<String>,
+        #[serde(default = "default_sort_order")]
+        order: SortOrder,
+    },
+    /// Keeps the items of the array at `key` whose `field` is `equals`, or
+    /// contains it when an array, or is truthy without `equals`. `exclude`
+    /// drops those items instead.
+    Filter {
+        key: String,
+        field: String,
+        equals: Option<Value>,
+        #[serde(default)]
+        exclude: bool,
+    },
+}
+
+fn default_date_field() -> String {
+    "date".to_string()
+}
+
+fn default_body_field() -> String {
+    "body".to_string()
+}
+
+fn default_releases_key() -> String {
+    "releases".to_string()
+}
+
+fn default_sort_order() -> SortOrder {
+    SortOrder::Asc
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Responsive variants for the `image` helper. For `photo.jpg` and width
+/// 480 the variant is `photo-480w.jpg` next to it, generated ahead of time.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ImagesConfig {
+    /// Variant widths in pixels, e.g. `[480, 960, 1440]`.
+    pub widths: Vec<u32>,
+    /// Default `sizes` when a call doesn't pass one.
+    pub sizes: Option<String>,
+    /// Attributes added to every image unless the call overrides them.
+    pub attributes: BTreeMap<String, String>,
+    /// Color `imagePlaceholder` uses for images that are missing or can't be
+    /// read.
+    pub placeholder_fallback: String,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> ImagesConfig {
+        ImagesConfig {
+            widths: Vec::new(),
+            sizes: None,
+            attributes: BTreeMap::from([
+                ("decoding".to_string(), "async".to_string()),
+                ("loading".to_string(), "lazy".to_string()),
+            ]),
+            placeholder_fallback: "#e5e5e5".to_string(),
+        }
+    }
+}
+
+/// Wording for the `timeAgo` helper; unset strings use English. `{}` in
+/// `past` and `future` is replaced by an amount such as `3 days`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TimeAgoConfig {
+    pub just_now: Option<String>,
+    pub past: Option<String>,
+    pub future: Option<String>,
+    /// Singular and plural, e.g. `["Minute", "Minuten"]`.
+    pub minute: Option<[String; 2]>,
+    pub hour: Option<[String; 2]>,
+    pub day: Option<[String; 2]>,
+    pub month: Option<[String; 2]>,
+    pub year: Option<[String; 2]>,
+}
+
+/// Reading speed and wording for the `readingTime` helpers and transform.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReadingTimeConfig {
+    /// Defaults to 200.
+    pub words_per_minute: Option<u32>,
+    /// `{}` is the number of minutes. Defaults to `{} min read`.
+    pub label: Option<String>,
+    /// Shown for content under half a minute. Defaults to
+    /// `less than a minute`.
+    pub under_a_minute: Option<String>,
+}
+
+impl ReadingTimeConfig {
+    pub fn words_per_minute(&self) -> u32 {
+        self.words_per_minute.unwrap_or(200).max(1)
+    }
+}
+
+/// How the `pageTitle` helper combines a page's title with the site name.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TitleConfig {
+    /// e.g. `My Site`. Without it, page titles are used alone.
+    pub site_name: Option<String>,
+    /// Between the page title and the site name. Defaults to ` — `.
+    pub separator: Option<String>,
+    pub position: TitlePosition,
+    /// The whole title of pages without one. Defaults to the site name.
+    pub default: Option<String>,
+}
+
+/// Where the site name goes relative to the page title.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitlePosition {
+    /// `My Site — About`
+    Prefix,
+    /// `About — My Site`
+    #[default]
+    Suffix,
+}
+
+/// The editor preview endpoint, see `src/preview.rs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// Bearer token required by `/_preview/<page>`. The endpoint only exists
+    /// when this is set, and never in `prod`.
+    pub token: Option<String>,
+}
+
+/// The operator endpoints, see `src/admin.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Bearer token required by `/_admin/*`. The endpoints only exist when
+    /// this is set.
+    pub token: Option<String>,
+    /// Seconds between two runs of `/_admin/selftest`, which renders every
+    /// page.
+    pub selftest_interval: u64,
+}
+
+impl Default for AdminConfig {
+    fn default() -> AdminConfig {
+        AdminConfig {
+            token: None,
+            selftest_interval: 60,
+        }
+    }
+}
+
+/// `/llms.txt` for AI crawlers, see `src/llms_txt.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LlmsTxtConfig {
+    pub enabled: bool,
+    /// Served as it is when it exists, relative to the project root.
+    pub file: PathBuf,
+    /// The summary quoted under the heading of a generated file.
+    pub description: Option<String>,
+}
+
+impl Default for LlmsTxtConfig {
+    fn default() -> LlmsTxtConfig {
+        LlmsTxtConfig {
+            enabled: false,
+            file: PathBuf::from("llms.txt"),
+            description: None,
+        }
+    }
+}
+
+/// Rendering any template over HTTP, see `src/render_api.rs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RenderApiConfig {
+    /// Bearer token required by `/_render/<template>`. The endpoint only
+    /// exists when this is set.
+    pub token: Option<String>,
+    /// Response content type by template name, e.g.
+    /// `"emails/welcome-text" = "text/plain; charset=utf-8"`.
+    pub content_types: BTreeMap<String, String>,
+}
+
+/// A partial fetched over HTTP, declared with `[[remote_partials]]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemotePartialConfig {
+    /// Name it is included by, e.g. `partials/header`.
+    pub name: String,
+    pub url: String,
+    /// Local copy used when the fetch fails.
+    pub fallback: Option<PathBuf>,
+    #[serde(default)]
+    pub on_failure: RemoteFailurePolicy,
+}
+
+/// What happens when neither the URL nor the fallback can be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteFailurePolicy {
+    /// Exit at startup.
+    #[default]
+    Abort,
+    /// Register an empty partial, with a warning.
+    Empty,
+}
+
+/// A form endpoint declared with `[[forms]]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FormConfig {
+    /// Route that accepts the POST, e.g. `/contact`.
+    pub path: String,
+    /// Page re-rendered with `errors` when validation fails, e.g. `contact`.
+    pub template: String,
+    /// Page rendered after a successful submission.
+    pub success_template: String,
+    /// Fields that must be present and non-blank.
+    #[serde(default)]
+    pub required: Vec<String>,
+    #[serde(default)]
+    pub sink: FormSinkConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct I18nConfig {
+    /// Locales the site is available in, e.g. `["en", "de"]`. Locale
+    /// resolution is off while this is empty.
+    pub locales: Vec<String>,
+    /// Used when the request names no available locale. Defaults to the
+    /// first of `locales`.
+    pub default_locale: Option<String>,
+}
+
+impl I18nConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.locales.is_empty()
+    }
+
+    pub fn default_locale(&self) -> &str {
+        self.default_locale
+            .as_deref()
+            .or(self.locales.first().map(String::as_str))
+            .unwrap_or_default()
+    }
+}
+
+/// Where accepted submissions are sent.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FormSinkConfig {
+    /// Print the submission to stdout.
+    #[default]
+    Log,
+    /// Append the submission as a JSON line to a file.
+    File { path: PathBuf },
+    /// POST the submission as JSON to a URL.
+    Webhook { url: String },
+}
+
+async fn read_table(path: &Path) -> Result<toml::Table, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path).await?;
+    Ok(toml::from_str(&content)?)
+}
+
+/// Merges `overlay` into `base` table by table; any other value, including
+/// arrays, replaces the base value.
+fn merge_table(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_table(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Loads `hbs-composer.toml`, then deep-merges `hbs-composer.<env>.toml`
+    /// over it if that exists. The environment comes from `ENVIRONMENT_VAR`,
+    /// else the base file's `environment`; without either there's no overlay.
+    pub async fn load() -> Result<Config, Box<dyn std::error::Error>> {
+        let path = Path::new(CONFIG_FILE);
+        let mut sources = Vec::new();
+
+        let mut table = match path.exists() {
+            true => {
+                sources.push(path.to_path_buf());
+                read_table(path).await?
+            }
+            false => toml::Table::new(),
+        };
+
+        let from_var = std::env::var(ENVIRONMENT_VAR)
+            .ok()
+            .filter(|environment| !environment.is_empty());
+        let environment = match &from_var {
+            Some(environment) => Some(environment.clone()),
+            None => match table.get("environment") {
+                Some(toml::Value::String(environment)) => Some(environment.clone()),
+                _ => None,
+            },
+        };
+
+        let overlay_path = environment
+            .map(|environment| PathBuf::from(format!("hbs-composer.{}.toml", environment)));
+        if let Some(overlay_path) = overlay_path
+            && overlay_path.exists()
+        {
+            let overlay = read_table(&overlay_path)
+                .await
+                .map_err(|e| format!("{}: {}", overlay_path.display(), e))?;
+            merge_table(&mut table, overlay);
+            sources.push(overlay_path);
+        }
+
+        // The variable wins over an `environment` set in either file
+        if let Some(environment) = from_var {
+            table.insert("environment".to_string(), toml::Value::String(environment));
+        }
+
+        let mut config: Config = toml::Value::Table(table).try_into()?;
+        config.sources = sources;
+        Ok(config)
+    }
+
+    pub fn is_dev(&self) -> bool {
+        self.environment == Some(Environment::Dev)
+    }
+
+    pub fn is_prod(&self) -> bool {
+        self.environment == Some(Environment::Prod)
+    }
+
+    /// The environment's name, or `unset`.
+    pub fn environment_name(&self) -> &'static str {
+        self.environment.map_or("unset", Environment::name)
+    }
+
+    pub fn minify(&self) -> bool {
+        self.html.minify.unwrap_or(self.is_prod())
+    }
+
+    pub fn live_reload(&self) -> bool {
+        self.html.live_reload.unwrap_or(self.is_dev())
+    }
+
+    pub fn strict(&self) -> bool {
+        self.render.strict.unwrap_or(self.is_dev())
+    }
+
+    pub fn root_status(&self) -> RedirectStatus {
+        self.routing.root_status.unwrap_or(match self.is_dev() {
+            true => RedirectStatus::FOUND,
+            false => RedirectStatus::PERMANENT,
+        })
+    }
+
+    pub fn error_details(&self) -> bool {
+        self.render.error_details.unwrap_or(self.is_dev())
+    }
+
+    pub fn fail_on_empty(&self) -> bool {
+        self.render.fail_on_empty.unwrap_or(self.strict())
+    }
+
+    pub fn templates_dir(&self) -> PathBuf {
+        self.root.join(TEMPLATES_DIR)
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.root.join(DATA_DIR)
+    }
+
+    pub fn locales_dir(&self) -> PathBuf {
+        self.root.join(LOCALES_DIR)
+    }
+
+    pub fn maintenance_file(&self) -> PathBuf {
+        self.root.join(&self.maintenance.file)
+    }
+
+    pub fn static_dir(&self) -> PathBuf {
+        self.root.join(STATIC_DIR)
+    }
+
+    /// This config for one of `sites`: the same settings, with the site's
+    /// directory as the root that relative paths are resolved against.
+    pub fn for_site(&self, site: &HostSiteConfig) -> Config {
+        let mut config = self.clone();
+        config.root = site.root.clone();
+        config.sites = Vec::new();
+        if site.base_url.is_some() {
+            config.site.base_url = site.base_url.clone();
+        }
+        let resolve = |path: &mut PathBuf| {
+            if path.is_relative() {
+                *path = site.root.join(&*path);
+            }
+        };
+        config.data.globals.iter_mut().for_each(resolve);
+        config.icons.sprite.iter_mut().for_each(resolve);
+        config.icons.dir.iter_mut().for_each(resolve);
+        config
+    }
+
+    pub fn reserved_prefix(&self) -> &str {
+        self.render
+            .reserved_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_RESERVED_PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_prefixes_end_at_a_segment() {
+        assert!(is_path_under("/static", "/static"));
+        assert!(is_path_under("/static/app.css", "/static"));
+        assert!(!is_path_under("/staticky-expensive-page", "/static"));
+        assert!(is_path_under("/static/app.css", "/static/"));
+        assert!(!is_path_under("/static", "/static/"));
+        assert!(!is_path_under("/other", "/static"));
+    }
+}
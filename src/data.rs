@@ -0,0 +1,833 @@
+#[cfg(feature = "csv")]
+mod csv;
+mod format;
+mod include;
+#[cfg(feature = "ini")]
+mod ini;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod oversize;
+#[cfg(feature = "xml")]
+mod xml;
+
+use crate::build::collect_files;
+use crate::config::{CollisionPolicy, Config, DataConfig, DottedKeyPolicy, Environment, KeyStyle};
+use crate::headings::slugify;
+use format::Format;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::fs;
+
+/// Loaded into the context, below the site root.
+pub const DATA_DIR: &str = "data";
+
+/// Holds a data tree per locale, e.g. `locales/de/`, merged over `data/`
+/// for requests in that locale.
+pub const LOCALES_DIR: &str = "locales";
+
+/// Per-directory settings file. It is never loaded into the context.
+const DIRECTORY_CONFIG_FILE: &str = "_config.json";
+
+type LoadDirectoryRecursiveResult = Result<LoadedDirectory, Box<dyn std::error::Error>>;
+
+/// Files that contributed to each key, in load order.
+pub type Sources = BTreeMap<String, Vec<PathBuf>>;
+
+#[derive(Default)]
+struct LoadedDirectory {
+    data: Map<String, Value>,
+    sources: Sources,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DirectoryConfig {
+    /// Overrides `DataConfig::flatten` for this directory.
+    flatten: Option<bool>,
+    /// Only load this directory in the given environment.
+    environment: Option<Environment>,
+}
+
+/// Splits an environment marker off a file stem or directory name:
+/// `stats.prod` is `stats`, loaded only in `prod`.
+fn split_environment(name: &str) -> (&str, Option<Environment>) {
+    match name.rsplit_once('.') {
+        Some((base, marker)) if !base.is_empty() => match Environment::from_name(marker) {
+            Some(environment) => (base, Some(environment)),
+            None => (name, None),
+        },
+        _ => (name, None),
+    }
+}
+
+/// The key for a file whose stem, without an environment marker, is
+/// `name`, or for a directory named `name`, under `keys`.
+fn derive_key(options: &DataConfig, name: &str, is_file: bool) -> String {
+    match options.keys {
+        KeyStyle::AllExtensions if is_file => match name.split_once('.') {
+            Some((base, _)) if !base.is_empty() => base.to_string(),
+            _ => name.to_string(),
+        },
+        KeyStyle::Slug => match slugify(name) {
+            slug if slug.is_empty() => name.to_string(),
+            slug => slug,
+        },
+        _ => name.to_string(),
+    }
+}
+
+/// Entries whose key contains a dot, so each is warned about once.
+static DOTTED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// Whether the entry at `path` is loaded under `key`, which `dotted_keys`
+/// decides when the key contains a dot.
+fn accepts_key(options: &DataConfig, key: &str, path: &Path) -> bool {
+    if !key.contains('.') || options.dotted_keys == DottedKeyPolicy::Allow {
+        return true;
+    }
+    let skip = options.dotted_keys == DottedKeyPolicy::Skip;
+    let mut warned = DOTTED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned
+        .get_or_insert_with(HashSet::new)
+        .insert(path.to_path_buf())
+    {
+        eprintln!(
+            "Warning: key '{}' from {} contains a dot, so dotted paths can't reach it; {}",
+            key,
+            path.display(),
+            match skip {
+                true => "skipping it",
+                false => "rename it or set `keys` under [data]",
+            }
+        );
+    }
+    !skip
+}
+
+async fn load_directory_config(dir_path: &Path) -> DirectoryConfig {
+    let path = dir_path.join(DIRECTORY_CONFIG_FILE);
+
+    if !path.exists() {
+        return DirectoryConfig::default();
+    }
+
+    match fs::read_to_string(&path).await {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Failed to parse {}: {}", path.display(), e);
+                DirectoryConfig::default()
+            }
+        },
+        Err(e) => {
+            eprintln!("Failed to read file {}: {}", path.display(), e);
+            DirectoryConfig::default()
+        }
+    }
+}
+
+/// Include/exclude rules from `DataConfig`, matched against paths relative
+/// to the data root.
+struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    fn new(options: &DataConfig) -> Result<FileFilter, globset::Error> {
+        let include = match options.include.is_empty() {
+            true => None,
+            false => Some(build_glob_set(&options.include)?),
+        };
+
+        Ok(FileFilter {
+            include,
+            exclude: build_glob_set(&options.exclude)?,
+        })
+    }
+
+    fn is_selected(&self, relative_path: &Path) -> bool {
+        if self.exclude.is_match(relative_path) {
+            return false;
+        }
+
+        match &self.include {
+            Some(include) => include.is_match(relative_path),
+            None => true,
+        }
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+/// Settings shared by every level of a single data load.
+struct LoadContext<'a> {
+    options: &'a DataConfig,
+    root: &'a Path,
+    filter: FileFilter,
+    /// The active environment; marked files and directories are skipped
+    /// while it is unset.
+    environment: Option<Environment>,
+    /// The requested route as a relative path, when data is scoped by route.
+    route: Option<PathBuf>,
+    /// Files and directories that failed to load, for the health status.
+    problems: Mutex<Vec<String>>,
+    /// When each loaded file was last modified, for `lastModified`.
+    modified: Mutex<HashMap<PathBuf, SystemTime>>,
+    /// Keys loaded twice, which fail the load with `collisions = "error"`.
+    collisions: Mutex<Vec<String>>,
+}
+
+impl LoadContext<'_> {
+    /// Prints a load failure and records it.
+    fn problem(&self, message: String) {
+        eprintln!("{}", message);
+        self.problems
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(message);
+    }
+
+    /// Whether a subdirectory, relative to the data root, should be loaded.
+    fn is_in_scope(&self, relative_dir: &Path) -> bool {
+        match &self.route {
+            Some(route) => {
+                route.starts_with(relative_dir)
+                    || self.options.shared.iter().any(|shared| {
+                        relative_dir.starts_with(shared) || shared.starts_with(relative_dir)
+                    })
+            }
+            None => true,
+        }
+    }
+}
+
+/// Inserts `value` under `key`. Replacing an existing value is warned
+/// about, or recorded as a collision with `collisions = "error"`.
+fn insert_value(
+    context: &LoadContext,
+    data: &mut Map<String, Value>,
+    key: String,
+    value: Value,
+    source: &Path,
+) {
+    if data.contains_key(&key) {
+        match context.options.collisions {
+            CollisionPolicy::Error => context
+                .collisions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(format!(
+                    "key '{}' from {} collides with an existing value",
+                    key,
+                    source.display()
+                )),
+            CollisionPolicy::LastWins | CollisionPolicy::Namespace => eprintln!(
+                "Warning: key '{}' from {} overwrites an existing value",
+                key,
+                source.display()
+            ),
+        }
+    }
+    data.insert(key, value);
+}
+
+/// Reads and parses a data file of `size` bytes, recording failures. Files
+/// over `max_file_bytes` are skipped or truncated instead, see
+/// `data/oversize.rs`. `$include` directives are resolved, see
+/// `data/include.rs`.
+async fn read_file(
+    context: &LoadContext<'_>,
+    path: &Path,
+    format: Format,
+    size: u64,
+) -> Option<Value> {
+    if let Some(max_bytes) = context.options.max_file_bytes
+        && size > max_bytes
+    {
+        return oversize::load(path, format, size, max_bytes, context.options.oversize).await;
+    }
+
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) => {
+            context.problem(format!("Failed to read file {}: {}", path.display(), e));
+            return None;
+        }
+    };
+    match format.parse(content, path) {
+        Ok(value) if include::has_includes(&value) => {
+            match include::expand(value, path, context.root).await {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    context.problem(format!(
+                        "Failed to include data in {}: {}",
+                        path.display(),
+                        e
+                    ));
+                    None
+                }
+            }
+        }
+        Ok(value) => Some(value),
+        Err(e) => {
+            context.problem(format!(
+                "Failed to parse {} file {}: {}",
+                format.name(),
+                path.display(),
+                e
+            ));
+            None
+        }
+    }
+}
+
+fn load_directory_recursive<'a>(
+    dir_path: &'a Path,
+    context: &'a LoadContext<'a>,
+) -> Pin<Box<dyn Future<Output = LoadDirectoryRecursiveResult> + 'a>> {
+    Box::pin(async move {
+        let mut data = Map::new();
+        let mut sources = Sources::new();
+        // Values marked for the active environment, which replace unmarked ones
+        let mut specific = Map::new();
+        let mut specific_sources = Sources::new();
+
+        if !dir_path.exists() {
+            return Ok(LoadedDirectory::default());
+        }
+
+        let mut entries = fs::read_dir(dir_path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+
+            if metadata.is_dir() {
+                let relative_dir = path.strip_prefix(context.root).unwrap_or(&path);
+                if !context.is_in_scope(relative_dir) || context.options.is_fragment(relative_dir) {
+                    continue;
+                }
+
+                // Recursively load subdirectory
+                if let Some(dir_name) = path.file_name() {
+                    let dir_name = dir_name.to_string_lossy();
+                    let (key, marker) = split_environment(&dir_name);
+                    let key = derive_key(context.options, key, false);
+                    let dir_config = load_directory_config(&path).await;
+                    let environment = marker.or(dir_config.environment);
+                    if environment
+                        .is_some_and(|environment| Some(environment) != context.environment)
+                    {
+                        continue;
+                    }
+                    let (target, target_sources) = match marker {
+                        Some(_) => (&mut specific, &mut specific_sources),
+                        None => (&mut data, &mut sources),
+                    };
+
+                    match load_directory_recursive(&path, context).await {
+                        Ok(mut subdir) => {
+                            if dir_config.flatten.unwrap_or(context.options.flatten) {
+                                // Merge the subdirectory contents into this level
+                                for (sub_key, value) in subdir.data {
+                                    let files = subdir.sources.remove(&sub_key);
+                                    target_sources
+                                        .entry(sub_key.clone())
+                                        .or_default()
+                                        .extend(files.into_iter().flatten());
+                                    insert_value(context, target, sub_key, value, &path);
+                                }
+                                log::debug!("Loaded directory (flattened): {}", path.display());
+                            } else if accepts_key(context.options, &key, &path) {
+                                target_sources
+                                    .entry(key.clone())
+                                    .or_default()
+                                    .extend(subdir.sources.into_values().flatten());
+                                insert_value(
+                                    context,
+                                    target,
+                                    key,
+                                    Value::Object(subdir.data),
+                                    &path,
+                                );
+                                log::debug!("Loaded directory: {}", path.display());
+                            }
+                        }
+                        Err(e) => {
+                            context.problem(format!(
+                                "Failed to load directory {}: {}",
+                                path.display(),
+                                e
+                            ));
+                        }
+                    }
+                }
+            } else if metadata.is_file() {
+                if path
+                    .file_name()
+                    .is_some_and(|name| name == DIRECTORY_CONFIG_FILE)
+                {
+                    continue;
+                }
+
+                // Skip files outside the include/exclude selection before reading them
+                let relative_path = path.strip_prefix(context.root).unwrap_or(&path);
+                if !context.filter.is_selected(relative_path) {
+                    continue;
+                }
+
+                // Process files
+                if let Some(extension) = path.extension()
+                    && let Some(file_stem) = path.file_stem()
+                {
+                    let file_stem = file_stem.to_string_lossy();
+                    let (key, marker) = split_environment(&file_stem);
+                    if marker.is_some_and(|environment| Some(environment) != context.environment) {
+                        continue;
+                    }
+                    let (target, target_sources) = match marker {
+                        Some(_) => (&mut specific, &mut specific_sources),
+                        None => (&mut data, &mut sources),
+                    };
+
+                    let format = Format::from_extension(&extension.to_string_lossy());
+                    let key = derive_key(context.options, key, true);
+                    let key = match context.options.collisions {
+                        CollisionPolicy::Namespace if format == Some(Format::Text) => {
+                            format!("{}{}", key, context.options.text_suffix())
+                        }
+                        _ => key,
+                    };
+                    match format {
+                        Some(format) if format.is_enabled() => {
+                            if accepts_key(context.options, &key, &path)
+                                && let Some(value) =
+                                    read_file(context, &path, format, metadata.len()).await
+                            {
+                                if let Ok(modified) = metadata.modified() {
+                                    context
+                                        .modified
+                                        .lock()
+                                        .unwrap_or_else(|e| e.into_inner())
+                                        .insert(path.clone(), modified);
+                                }
+                                target_sources
+                                    .entry(key.clone())
+                                    .or_default()
+                                    .push(path.clone());
+                                insert_value(context, target, key, value, &path);
+                                log::debug!("Loaded {} file: {}", format.name(), path.display());
+                            }
+                        }
+                        Some(format) => format.hint_disabled(&path),
+                        None => {
+                            // Ignore files with other extensions
+                        }
+                    }
+                }
+            }
+        }
+
+        data.extend(specific);
+        for (key, files) in specific_sources {
+            sources.entry(key).or_default().extend(files);
+        }
+        Ok(LoadedDirectory { data, sources })
+    })
+}
+
+/// The data version each site's routes were last measured at, by locale,
+/// so a context is only serialized again once its data changed.
+type Measured = HashMap<(PathBuf, String, String), Option<SystemTime>>;
+static MEASURED: Mutex<Option<Measured>> = Mutex::new(None);
+
+/// Routes remembered in `MEASURED` before it starts over, since any path
+/// can be requested.
+const MAX_MEASURED: usize = 10_000;
+
+/// Counts the bytes written, to measure a context without holding its JSON.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Warns when the context for `route` in `locale` serializes to more than
+/// `context_budget_bytes`. Measured once per `version` of its data, the
+/// latest change to any file behind it, so each route is warned about once
+/// until its data changes.
+pub fn check_context_budget(
+    config: &Config,
+    route: &str,
+    locale: &str,
+    version: Option<SystemTime>,
+    context: &Map<String, Value>,
+) {
+    let Some(budget) = config.data.context_budget_bytes else {
+        return;
+    };
+    {
+        let mut measured = MEASURED.lock().unwrap_or_else(|e| e.into_inner());
+        let measured = measured.get_or_insert_with(HashMap::new);
+        if measured.len() >= MAX_MEASURED {
+            measured.clear();
+        }
+        let key = (config.root.clone(), route.to_string(), locale.to_string());
+        if measured.insert(key, version) == Some(version) {
+            return;
+        }
+    }
+    let mut counter = ByteCounter(0);
+    if serde_json::to_writer(&mut counter, context).is_err() || counter.0 <= budget {
+        return;
+    }
+
+    let mut sizes: Vec<(&String, usize)> = context
+        .iter()
+        .map(|(key, value)| {
+            let mut counter = ByteCounter(0);
+            let _ = serde_json::to_writer(&mut counter, value);
+            (key, counter.0)
+        })
+        .collect();
+    sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    let largest: Vec<String> = sizes
+        .iter()
+        .take(3)
+        .map(|(key, size)| format!("{} ({} bytes)", key, size))
+        .collect();
+    eprintln!(
+        "Warning: the context for '{}' is {} bytes, over context_budget_bytes = {}; largest keys: {}",
+        route,
+        counter.0,
+        budget,
+        largest.join(", ")
+    );
+}
+
+/// Site-wide values from the `globals` file, shared by every request.
+#[derive(Debug, Clone, Default)]
+pub struct Globals(pub Map<String, Value>);
+
+impl Globals {
+    pub async fn load(options: &DataConfig) -> Result<Globals, Box<dyn std::error::Error>> {
+        let Some(path) = &options.globals else {
+            return Ok(Globals::default());
+        };
+
+        let format = path
+            .extension()
+            .and_then(|extension| Format::from_extension(&extension.to_string_lossy()))
+            .filter(|format| matches!(format, Format::Json | Format::Yaml))
+            .ok_or("globals must be a .json, .yaml or .yml file")?;
+
+        let content = fs::read_to_string(path).await?;
+        match format.parse(content, path)? {
+            Value::Object(globals) => {
+                log::debug!("Loaded globals file: {}", path.display());
+                Ok(Globals(globals))
+            }
+            _ => Err("globals file must contain an object".into()),
+        }
+    }
+}
+
+/// Reads a `--data-file` value, parsed according to its extension.
+pub async fn load_value_file(path: &Path) -> Result<Value, Box<dyn std::error::Error>> {
+    let format = path
+        .extension()
+        .and_then(|extension| Format::from_extension(&extension.to_string_lossy()))
+        .ok_or("unsupported file extension")?;
+    let content = fs::read_to_string(path).await?;
+    format.parse(content, path)
+}
+
+/// Reads the JSON object piped in with `--stdin`.
+pub fn load_stdin() -> Result<Map<String, Value>, Box<dyn std::error::Error>> {
+    let content = std::io::read_to_string(std::io::stdin())?;
+    match serde_json::from_str(&content)? {
+        Value::Object(map) => Ok(map),
+        _ => Err("expected a JSON object".into()),
+    }
+}
+
+/// Recursively merges `overlay` into `base`. Objects are merged key by key;
+/// any other overlay value replaces the base value.
+pub fn merge_value(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => merge_map(base, overlay),
+        (base, overlay) => *base = overlay,
+    }
+}
+
+pub fn merge_map(base: &mut Map<String, Value>, overlay: Map<String, Value>) {
+    for (key, value) in overlay {
+        match base.get_mut(&key) {
+            Some(existing) => merge_value(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Whether the file or a directory above it is marked for another environment.
+fn has_other_environment(relative_path: &Path, environment: Option<Environment>) -> bool {
+    let file_stem = relative_path.with_extension("");
+    file_stem.components().any(|component| {
+        let (_, marker) = split_environment(&component.as_os_str().to_string_lossy());
+        marker.is_some_and(|marker| Some(marker) != environment)
+    })
+}
+
+/// Per-format counts of the files a load of the data directory would read,
+/// for the startup summary.
+#[derive(Debug, Default)]
+pub struct DataFileCounts {
+    pub by_format: BTreeMap<&'static str, usize>,
+    /// Files skipped because their format is compiled out.
+    pub disabled: usize,
+}
+
+/// Counts the data files selected by `include`/`exclude` and environment
+/// markers in their names, on any route.
+pub async fn count_data_files(
+    config: &Config,
+) -> Result<DataFileCounts, Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir();
+    let environment = config.environment;
+    let mut counts = DataFileCounts::default();
+    if !data_dir.exists() {
+        return Ok(counts);
+    }
+
+    let filter = FileFilter::new(&config.data)?;
+    for file in collect_files(&data_dir, PathBuf::new()).await? {
+        if file
+            .file_name()
+            .is_some_and(|name| name == DIRECTORY_CONFIG_FILE)
+            || !filter.is_selected(&file)
+            || has_other_environment(&file, environment)
+            || config.data.is_fragment(&file)
+        {
+            continue;
+        }
+        let format = file
+            .extension()
+            .and_then(|extension| Format::from_extension(&extension.to_string_lossy()));
+        match format {
+            Some(format) if format.is_enabled() => {
+                *counts.by_format.entry(format.name()).or_default() += 1;
+            }
+            Some(_) => counts.disabled += 1,
+            None => {}
+        }
+    }
+
+    Ok(counts)
+}
+
+/// The values loaded from the data directory, along with the files that
+/// failed to load and were left out.
+#[derive(Debug, Default)]
+pub struct DataFiles {
+    pub values: HashMap<String, Value>,
+    pub problems: Vec<String>,
+    /// The files behind each top-level key, for `/_debug/data`.
+    pub sources: Sources,
+    /// When the most recently changed file behind each top-level key was
+    /// modified.
+    pub modified: BTreeMap<String, SystemTime>,
+}
+
+/// Why the data for a page could not be loaded.
+#[derive(Debug)]
+pub enum DataError {
+    /// A source the page needs can't be reached right now, such as a data
+    /// directory on a network mount that timed out. Pages answer with a
+    /// 503, as retrying later may succeed.
+    Unavailable(Box<dyn std::error::Error>),
+    /// The data or its configuration is wrong, such as colliding keys or
+    /// an unreadable directory, and won't load until it is fixed.
+    Invalid(Box<dyn std::error::Error>),
+}
+
+impl DataError {
+    /// Only failures a retry may get past, such as a timed out or stale
+    /// network mount, are `Unavailable`; anything else, such as a missing
+    /// permission, is `Invalid`.
+    fn from_load(error: Box<dyn std::error::Error>) -> DataError {
+        match error.downcast_ref::<io::Error>().map(io::Error::kind) {
+            Some(
+                io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ResourceBusy
+                | io::ErrorKind::StaleNetworkFileHandle
+                | io::ErrorKind::NetworkDown
+                | io::ErrorKind::NetworkUnreachable
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected,
+            ) => DataError::Unavailable(error),
+            _ => DataError::Invalid(error),
+        }
+    }
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataError::Unavailable(e) => write!(f, "data unavailable: {}", e),
+            DataError::Invalid(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// Loads the site's data directory for a request to `route`, e.g.
+/// `blog/post`, in the configured environment.
+pub async fn load_data_files(config: &Config, route: &str) -> Result<DataFiles, DataError> {
+    load_tree(config, &config.data_dir(), route).await
+}
+
+/// Loads the overlay for `locale` below `locales/`, read like the data
+/// directory. Empty without a locale or an overlay for it.
+pub async fn load_locale_files(
+    config: &Config,
+    locale: &str,
+    route: &str,
+) -> Result<DataFiles, DataError> {
+    if locale.is_empty() {
+        return Ok(DataFiles::default());
+    }
+    load_tree(config, &config.locales_dir().join(locale), route).await
+}
+
+async fn load_tree(config: &Config, data_dir: &Path, route: &str) -> Result<DataFiles, DataError> {
+    let options = &config.data;
+    if !data_dir.exists() {
+        log::debug!(
+            "Data directory '{}' does not exist, creating empty context",
+            data_dir.display()
+        );
+        return Ok(DataFiles::default());
+    }
+
+    let context = LoadContext {
+        options,
+        root: data_dir,
+        filter: FileFilter::new(options).map_err(|e| DataError::Invalid(e.into()))?,
+        environment: config.environment,
+        route: options.scope_by_route.then(|| PathBuf::from(route)),
+        problems: Mutex::new(Vec::new()),
+        modified: Mutex::new(HashMap::new()),
+        collisions: Mutex::new(Vec::new()),
+    };
+    let loaded = load_directory_recursive(data_dir, &context)
+        .await
+        .map_err(DataError::from_load)?;
+    let collisions = context
+        .collisions
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner());
+    if !collisions.is_empty() {
+        return Err(DataError::Invalid(collisions.join("; ").into()));
+    }
+
+    // Convert Map<String, Value> to HashMap<String, Value>
+    let mut data = HashMap::new();
+    for (key, value) in loaded.data {
+        data.insert(key, value);
+    }
+
+    let file_modified = context
+        .modified
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner());
+    let modified = loaded
+        .sources
+        .iter()
+        .filter_map(|(key, files)| {
+            let latest = files
+                .iter()
+                .filter_map(|file| file_modified.get(file))
+                .max()?;
+            Some((key.clone(), *latest))
+        })
+        .collect();
+
+    Ok(DataFiles {
+        values: data,
+        sources: loaded.sources,
+        modified,
+        problems: context
+            .problems
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(kind: io::ErrorKind) -> DataError {
+        DataError::from_load(io::Error::from(kind).into())
+    }
+
+    #[test]
+    fn only_transient_read_errors_are_unavailable() {
+        for kind in [
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::Interrupted,
+            io::ErrorKind::WouldBlock,
+            io::ErrorKind::StaleNetworkFileHandle,
+            io::ErrorKind::NetworkUnreachable,
+        ] {
+            assert!(
+                matches!(load_error(kind), DataError::Unavailable(_)),
+                "{:?}",
+                kind
+            );
+        }
+        for kind in [
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound,
+            io::ErrorKind::InvalidData,
+        ] {
+            assert!(
+                matches!(load_error(kind), DataError::Invalid(_)),
+                "{:?}",
+                kind
+            );
+        }
+        let other = DataError::from_load("unsupported layout".into());
+        assert!(matches!(other, DataError::Invalid(_)));
+    }
+}
@@ -0,0 +1,27 @@
+//! Converts CSV files into JSON values for the template context.
+//!
+//! The first row is the header. Every following row becomes an object keyed
+//! by the header names, and the file's value is the array of rows in order,
+//! so `data/people.csv` can be iterated with `{{#each people}}{{name}}{{/each}}`.
+//! Values are kept as strings, and rows with a different number of fields
+//! than the header are a parse error.
+
+use serde_json::{Map, Value};
+
+pub fn parse(content: &str) -> Result<Value, csv::Error> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut rows = Vec::new();
+
+    for record in reader.records() {
+        let record = record?;
+        let row: Map<String, Value> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, field)| (header.to_string(), Value::String(field.to_string())))
+            .collect();
+        rows.push(Value::Object(row));
+    }
+
+    Ok(Value::Array(rows))
+}
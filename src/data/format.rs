@@ -0,0 +1,137 @@
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Disabled formats that have already been reported, so each hint is printed once.
+static DISABLED_FORMAT_HINTS: Mutex<Vec<Format>> = Mutex::new(Vec::new());
+
+/// A data file format, selected by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Text,
+    Ini,
+    Yaml,
+    Toml,
+    Csv,
+    Markdown,
+    Xml,
+}
+
+impl Format {
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension {
+            "json" => Some(Format::Json),
+            "txt" => Some(Format::Text),
+            "ini" => Some(Format::Ini),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "csv" => Some(Format::Csv),
+            "md" | "markdown" => Some(Format::Markdown),
+            "xml" => Some(Format::Xml),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name used in log messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Json => "JSON",
+            Format::Text => "text",
+            Format::Ini => "INI",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+            Format::Csv => "CSV",
+            Format::Markdown => "Markdown",
+            Format::Xml => "XML",
+        }
+    }
+
+    /// The cargo feature that enables this format, if it is optional.
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            Format::Json | Format::Text => None,
+            Format::Ini => Some("ini"),
+            Format::Yaml => Some("yaml"),
+            Format::Toml => Some("toml"),
+            Format::Csv => Some("csv"),
+            Format::Markdown => Some("markdown"),
+            Format::Xml => Some("xml"),
+        }
+    }
+
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Format::Json | Format::Text => true,
+            Format::Ini => cfg!(feature = "ini"),
+            Format::Yaml => cfg!(feature = "yaml"),
+            Format::Toml => cfg!(feature = "toml"),
+            Format::Csv => cfg!(feature = "csv"),
+            Format::Markdown => cfg!(feature = "markdown"),
+            Format::Xml => cfg!(feature = "xml"),
+        }
+    }
+
+    /// Prints, once per format, that a file was skipped because its feature is off.
+    pub fn hint_disabled(self, path: &Path) {
+        let mut hinted = DISABLED_FORMAT_HINTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if hinted.contains(&self) {
+            return;
+        }
+        hinted.push(self);
+
+        eprintln!(
+            "Skipping {} files such as {}: rebuild with `--features {}` to load them",
+            self.name(),
+            path.display(),
+            self.feature().unwrap_or_default()
+        );
+    }
+
+    /// Parses file content into a context value.
+    pub fn parse(
+        self,
+        content: String,
+        #[allow(unused_variables)] path: &Path,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        match self {
+            Format::Json => Ok(serde_json::from_str(&content)?),
+            Format::Text => Ok(Value::String(content)),
+            #[cfg(feature = "ini")]
+            Format::Ini => Ok(super::ini::parse(&content, path)?),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => Ok(serde_yaml_ng::from_str(&content)?),
+            #[cfg(feature = "toml")]
+            Format::Toml => Ok(toml_to_json(toml::from_str(&content)?)),
+            #[cfg(feature = "csv")]
+            Format::Csv => Ok(super::csv::parse(&content)?),
+            #[cfg(feature = "markdown")]
+            Format::Markdown => Ok(super::markdown::parse(&content, path)?),
+            #[cfg(feature = "xml")]
+            Format::Xml => Ok(super::xml::parse(&content)?),
+            #[allow(unreachable_patterns)]
+            disabled => Err(format!("{} support is not enabled", disabled.name()).into()),
+        }
+    }
+}
+
+/// TOML datetimes have no JSON equivalent, so they become strings.
+#[cfg(feature = "toml")]
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
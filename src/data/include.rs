@@ -0,0 +1,147 @@
+//! `{"$include": "social-links.json"}` anywhere in a data file is replaced by
+//! the value of that file. Paths are relative to the including file, or to
+//! the data directory with a leading `/`, and must stay inside it. Included
+//! files may include others; a cycle fails the file that started it.
+
+use super::format::Format;
+use serde_json::{Map, Value};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::fs;
+
+/// The key of an include directive, the only key of its object.
+const KEY: &str = "$include";
+
+type ExpandResult<'a> = Pin<Box<dyn Future<Output = Result<Value, String>> + 'a>>;
+
+/// The path of an include directive, if `value` is one.
+fn directive(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Object(map) if map.len() == 1 => map.get(KEY),
+        _ => None,
+    }
+}
+
+/// Whether `value` contains an include directive, so files without any are
+/// passed through as they are.
+pub fn has_includes(value: &Value) -> bool {
+    match value {
+        _ if directive(value).is_some() => true,
+        Value::Object(map) => map.values().any(has_includes),
+        Value::Array(items) => items.iter().any(has_includes),
+        _ => false,
+    }
+}
+
+/// `value`, read from `file`, with every include directive replaced by the
+/// value of the file it names. `root` is the data directory.
+pub async fn expand(value: Value, file: &Path, root: &Path) -> Result<Value, String> {
+    let root = fs::canonicalize(root)
+        .await
+        .map_err(|e| format!("{}: {}", root.display(), e))?;
+    let file = fs::canonicalize(file)
+        .await
+        .map_err(|e| format!("{}: {}", file.display(), e))?;
+    let mut stack = vec![file.clone()];
+    expand_value(value, &file, &root, &mut stack).await
+}
+
+/// `stack` holds the files being expanded, outermost first, to find cycles.
+fn expand_value<'a>(
+    value: Value,
+    file: &'a Path,
+    root: &'a Path,
+    stack: &'a mut Vec<PathBuf>,
+) -> ExpandResult<'a> {
+    Box::pin(async move {
+        if let Some(target) = directive(&value) {
+            let target = target.as_str().ok_or_else(|| {
+                format!("{} in {} must be a path string", KEY, display(file, root))
+            })?;
+            return include(target, file, root, stack).await;
+        }
+        match value {
+            Value::Object(map) => {
+                let mut expanded = Map::new();
+                for (key, value) in map {
+                    let value = expand_value(value, file, root, stack).await?;
+                    expanded.insert(key, value);
+                }
+                Ok(Value::Object(expanded))
+            }
+            Value::Array(items) => {
+                let mut expanded = Vec::with_capacity(items.len());
+                for item in items {
+                    expanded.push(expand_value(item, file, root, stack).await?);
+                }
+                Ok(Value::Array(expanded))
+            }
+            value => Ok(value),
+        }
+    })
+}
+
+/// `path` relative to the data directory, for messages.
+fn display(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Loads the file `target` names, from a directive in `file`.
+async fn include(
+    target: &str,
+    file: &Path,
+    root: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Value, String> {
+    let path = match target.strip_prefix('/') {
+        Some(from_root) => root.join(from_root),
+        None => file.parent().unwrap_or(root).join(target),
+    };
+    let path = fs::canonicalize(&path)
+        .await
+        .map_err(|e| format!("{} included from {}: {}", target, display(file, root), e))?;
+    if !path.starts_with(root) {
+        return Err(format!(
+            "{} included from {} is outside the data directory",
+            target,
+            display(file, root)
+        ));
+    }
+    if let Some(start) = stack.iter().position(|including| *including == path) {
+        let cycle: Vec<String> = stack[start..]
+            .iter()
+            .chain([&path])
+            .map(|path| display(path, root))
+            .collect();
+        return Err(format!("include cycle: {}", cycle.join(" → ")));
+    }
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let format = Format::from_extension(&extension)
+        .filter(|format| format.is_enabled())
+        .ok_or_else(|| {
+            format!(
+                "{} included from {} is not a supported data file",
+                target,
+                display(file, root)
+            )
+        })?;
+    let content = fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("{}: {}", display(&path, root), e))?;
+    let value = format
+        .parse(content, &path)
+        .map_err(|e| format!("{}: {}", display(&path, root), e))?;
+
+    stack.push(path.clone());
+    let expanded = expand_value(value, &path, root, stack).await;
+    stack.pop();
+    expanded
+}
@@ -0,0 +1,49 @@
+//! Converts INI files into JSON values for the template context.
+//!
+//! Each `[section]` becomes an object of string values, so `db.host` in
+//! `[db]` of `legacy.ini` is `{{legacy.db.host}}`. Keys that appear before the
+//! first section are collected under `GENERAL_SECTION`. When a key repeats
+//! within a section (including a section declared twice), the last value wins
+//! and a warning is printed.
+
+use ini::Ini;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+pub const GENERAL_SECTION: &str = "general";
+
+pub fn parse(content: &str, source: &Path) -> Result<Value, ini::ParseError> {
+    let ini = Ini::load_from_str(content)?;
+    let mut document = Map::new();
+
+    for (section, properties) in ini.iter() {
+        // The general section always exists; only expose it when it has keys
+        if section.is_none() && properties.is_empty() {
+            continue;
+        }
+        let section = section.unwrap_or(GENERAL_SECTION);
+
+        let entry = document
+            .entry(section.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let Value::Object(fields) = entry else {
+            continue;
+        };
+
+        for (key, value) in properties.iter() {
+            if fields
+                .insert(key.to_string(), Value::String(value.to_string()))
+                .is_some()
+            {
+                eprintln!(
+                    "Warning: duplicate key '{}' in section [{}] of {}, using the last value",
+                    key,
+                    section,
+                    source.display()
+                );
+            }
+        }
+    }
+
+    Ok(Value::Object(document))
+}
@@ -0,0 +1,42 @@
+//! Renders Markdown files to HTML strings for the template context. Use a
+//! triple-stash (`{{{post}}}`) to insert the result without escaping. Files
+//! with front matter become objects holding the front matter keys and the
+//! rendered HTML under `body`.
+
+use crate::front_matter;
+use pulldown_cmark::{Options, Parser, html};
+use serde_json::Value;
+use std::path::Path;
+
+/// Key holding the rendered HTML of a file with front matter.
+const BODY_KEY: &str = "body";
+
+pub fn render(content: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+
+    let mut output = String::new();
+    html::push_html(&mut output, Parser::new_ext(content, options));
+    output
+}
+
+/// The rendered HTML, or `{ ...front matter, body }` when the file has front
+/// matter. A `body` key in the front matter is replaced, with a warning.
+pub fn parse(content: &str, path: &Path) -> Result<Value, serde_yaml_ng::Error> {
+    let Some((source, body)) = front_matter::split(content) else {
+        return Ok(Value::String(render(content)));
+    };
+
+    let mut object = front_matter::parse(source)?;
+    if object.contains_key(BODY_KEY) {
+        eprintln!(
+            "Warning: front matter key '{}' in {} is replaced by the rendered Markdown",
+            BODY_KEY,
+            path.display()
+        );
+    }
+    object.insert(BODY_KEY.to_string(), Value::String(render(body)));
+    Ok(Value::Object(object))
+}
@@ -0,0 +1,159 @@
+//! Data files over `max_file_bytes`, which are skipped or, for JSON arrays,
+//! streamed so that only the items within the limit are ever held in memory.
+
+use super::format::Format;
+use crate::config::OversizePolicy;
+use serde::de::{Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde_json::Value;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Mutex;
+
+/// Files already warned about, with the size they had, so each is reported
+/// once rather than on every request, and again if it changes.
+static WARNED: Mutex<Option<HashSet<(PathBuf, u64)>>> = Mutex::new(None);
+
+fn warn_once(path: &Path, size: u64, message: String) {
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned
+        .get_or_insert_with(HashSet::new)
+        .insert((path.to_path_buf(), size))
+    {
+        eprintln!("Warning: {}", message);
+    }
+}
+
+/// Loads a file of `size` bytes over `max_bytes` according to `policy`,
+/// or returns `None` when it is left out.
+pub async fn load(
+    path: &Path,
+    format: Format,
+    size: u64,
+    max_bytes: u64,
+    policy: OversizePolicy,
+) -> Option<Value> {
+    if policy == OversizePolicy::Skip || format != Format::Json {
+        warn_once(
+            path,
+            size,
+            format!(
+                "skipping data file {} ({} bytes, over max_file_bytes = {})",
+                path.display(),
+                size,
+                max_bytes
+            ),
+        );
+        return None;
+    }
+
+    let file = path.to_path_buf();
+    let read = tokio::task::spawn_blocking(move || read_array_prefix(&file, max_bytes)).await;
+    match read {
+        Ok(Ok(prefix)) => {
+            warn_once(
+                path,
+                size,
+                format!(
+                    "truncated data file {} to {} of {} items ({} bytes, over max_file_bytes = {})",
+                    path.display(),
+                    prefix.items.len(),
+                    prefix.total,
+                    size,
+                    max_bytes
+                ),
+            );
+            Some(Value::Array(prefix.items))
+        }
+        Ok(Err(e)) => {
+            warn_once(
+                path,
+                size,
+                format!(
+                    "skipping data file {} ({} bytes, over max_file_bytes = {}): {}",
+                    path.display(),
+                    size,
+                    max_bytes,
+                    e
+                ),
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!("Failed to read data file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Counts the bytes the parser has consumed so far.
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+struct ArrayPrefix {
+    items: Vec<Value>,
+    /// Items in the whole array, including those left out.
+    total: usize,
+}
+
+struct ArrayPrefixVisitor {
+    count: Rc<Cell<u64>>,
+    max_bytes: u64,
+}
+
+impl<'de> Visitor<'de> for ArrayPrefixVisitor {
+    type Value = ArrayPrefix;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array, as only arrays can be truncated")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ArrayPrefix, A::Error> {
+        let mut items = Vec::new();
+        let mut total = 0;
+        // Keep items while they end within the limit, then only count the rest
+        while self.count.get() <= self.max_bytes {
+            match seq.next_element::<Value>()? {
+                Some(item) => {
+                    total += 1;
+                    if self.count.get() <= self.max_bytes {
+                        items.push(item);
+                    }
+                }
+                None => return Ok(ArrayPrefix { items, total }),
+            }
+        }
+        while seq.next_element::<IgnoredAny>()?.is_some() {
+            total += 1;
+        }
+        Ok(ArrayPrefix { items, total })
+    }
+}
+
+/// The leading items of the JSON array in `path` that end within the first
+/// `max_bytes`. The rest of the file is parsed without being kept.
+fn read_array_prefix(path: &Path, max_bytes: u64) -> Result<ArrayPrefix, serde_json::Error> {
+    let count = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: BufReader::new(File::open(path).map_err(serde_json::Error::io)?),
+        count: Rc::clone(&count),
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let prefix = deserializer.deserialize_seq(ArrayPrefixVisitor { count, max_bytes })?;
+    deserializer.end()?;
+    Ok(prefix)
+}
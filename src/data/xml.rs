@@ -0,0 +1,151 @@
+//! Converts XML documents into JSON values for the template context.
+//!
+//! The mapping is:
+//!
+//! - The file's value is an object holding the root element under its name,
+//!   so `<site><title>Hi</title></site>` in `meta.xml` is `{{meta.site.title}}`.
+//! - Attributes become keys prefixed with `@` (`<a href="/">` → `@href`).
+//! - An element with neither attributes nor child elements becomes its text
+//!   as a string (empty elements become `""`).
+//! - Otherwise the element becomes an object, and any non-whitespace text is
+//!   stored under `#text`.
+//! - Child elements are keyed by name; an element name that appears more than
+//!   once in the same parent becomes an array in document order.
+//! - Text is trimmed, entities and CDATA are decoded, and no type inference is
+//!   done: every leaf is a string. Comments, processing instructions and the
+//!   doctype are ignored.
+
+use quick_xml::errors::IllFormedError;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use serde_json::{Map, Value};
+
+pub const ATTRIBUTE_PREFIX: &str = "@";
+pub const TEXT_KEY: &str = "#text";
+
+struct Element {
+    name: String,
+    fields: Map<String, Value>,
+    text: String,
+}
+
+impl Element {
+    fn new(start: &BytesStart) -> Result<Element, quick_xml::Error> {
+        let mut fields = Map::new();
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            let key = format!("{}{}", ATTRIBUTE_PREFIX, attribute.key.as_ref());
+            let value = attribute.normalized_value(XmlVersion::Implicit1_0)?;
+            fields.insert(key, Value::String(value.into_owned()));
+        }
+
+        Ok(Element {
+            name: start.name().as_ref().to_string(),
+            fields,
+            text: String::new(),
+        })
+    }
+
+    fn into_value(mut self) -> Value {
+        let text = self.text.trim();
+        if self.fields.is_empty() {
+            return Value::String(text.to_string());
+        }
+        if !text.is_empty() {
+            self.fields
+                .insert(TEXT_KEY.to_string(), Value::String(text.to_string()));
+        }
+        Value::Object(self.fields)
+    }
+}
+
+/// Adds a child element, turning repeated names into arrays.
+fn append_child(fields: &mut Map<String, Value>, name: String, value: Value) {
+    match fields.get_mut(&name) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            fields.insert(name, value);
+        }
+    }
+}
+
+pub fn parse(content: &str) -> Result<Value, quick_xml::Error> {
+    let mut reader = Reader::from_str(content);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut document = Map::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(Element::new(&start)?),
+            Event::Empty(start) => {
+                let element = Element::new(&start)?;
+                let name = element.name.clone();
+                let value = element.into_value();
+                match stack.last_mut() {
+                    Some(parent) => append_child(&mut parent.fields, name, value),
+                    None => append_child(&mut document, name, value),
+                }
+            }
+            Event::End(_) => {
+                // Mismatched end tags are rejected by the reader itself
+                if let Some(element) = stack.pop() {
+                    let name = element.name.clone();
+                    let value = element.into_value();
+                    match stack.last_mut() {
+                        Some(parent) => append_child(&mut parent.fields, name, value),
+                        None => append_child(&mut document, name, value),
+                    }
+                }
+            }
+            Event::Text(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element
+                        .text
+                        .push_str(&text.xml_content(XmlVersion::Implicit1_0));
+                }
+            }
+            Event::CData(data) => {
+                if let Some(element) = stack.last_mut() {
+                    element
+                        .text
+                        .push_str(&data.xml_content(XmlVersion::Implicit1_0));
+                }
+            }
+            Event::GeneralRef(reference) => {
+                if let Some(element) = stack.last_mut() {
+                    if let Some(c) = reference.resolve_char_ref()? {
+                        element.text.push(c);
+                    } else {
+                        let name = reference.xml_content(XmlVersion::Implicit1_0);
+                        match resolve_predefined_entity(&name) {
+                            Some(resolved) => element.text.push_str(resolved),
+                            None => {
+                                element.text.push('&');
+                                element.text.push_str(&name);
+                                element.text.push(';');
+                            }
+                        }
+                    }
+                }
+            }
+            Event::Eof => {
+                if let Some(element) = stack.pop() {
+                    return Err(quick_xml::Error::IllFormed(IllFormedError::MissingEndTag(
+                        element.name,
+                    )));
+                }
+                break;
+            }
+            _ => {
+                // Declarations, comments, processing instructions and doctypes carry no data
+            }
+        }
+    }
+
+    Ok(Value::Object(document))
+}
@@ -0,0 +1,100 @@
+//! `/_debug/data` reports which files each top-level context key was loaded
+//! from. The listing is kept beside the context, never merged into it.
+
+use crate::build::manifest_path;
+use crate::config::Config;
+use crate::data::{Globals, load_data_files, load_locale_files};
+use crate::render;
+use actix_web::http::header;
+use actix_web::{HttpResponse, Result, web};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+pub const DATA_ENDPOINT: &str = "/_debug/data";
+
+/// Listed for keys set with `--data` or `--data-file`.
+const COMMAND_LINE_SOURCE: &str = "command line";
+
+/// Listed for keys set under `[data.defaults]`.
+const DEFAULTS_SOURCE: &str = "config defaults";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DataQuery {
+    /// The route whose data to report, e.g. `blog/post`. Only matters with
+    /// `scope_by_route`.
+    route: String,
+    /// The locale whose overlay to report, e.g. `de`.
+    locale: String,
+}
+
+/// Whether `/_debug/data` should be routed: it is opt-in and never served
+/// in `prod`.
+pub fn is_enabled(config: &Config) -> bool {
+    config.data.provenance && !config.is_prod()
+}
+
+/// Lists, for each top-level key, every source that set part of it, in the
+/// order they were applied: the globals file, the files below `data/`, those
+/// of the `?locale=` overlay below `locales/`, then the command line. Later
+/// sources take precedence.
+pub async fn data(
+    query: web::Query<DataQuery>,
+    config: web::Data<Config>,
+    globals: web::Data<Globals>,
+) -> Result<HttpResponse> {
+    let route = match query.route.trim_matches('/') {
+        "" => config.routing.default_page(),
+        route => route,
+    };
+    let loaded = match load_data_files(&config, route).await {
+        Ok(data) => load_locale_files(&config, &query.locale, route)
+            .await
+            .map(|overlay| (data, overlay)),
+        Err(e) => Err(e),
+    };
+    let (data, overlay) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Failed to load data files: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load data files",
+                &e,
+            )));
+        }
+    };
+
+    let mut sources: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for key in config.data.defaults.keys() {
+        sources
+            .entry(key.clone())
+            .or_default()
+            .push(DEFAULTS_SOURCE.to_string());
+    }
+    if let Some(path) = &config.data.globals {
+        for key in globals.0.keys() {
+            sources
+                .entry(key.clone())
+                .or_default()
+                .push(manifest_path(path));
+        }
+    }
+    for (key, files) in data.sources.into_iter().chain(overlay.sources) {
+        sources
+            .entry(key)
+            .or_default()
+            .extend(files.iter().map(|file| manifest_path(file)));
+    }
+    for key in config.data.overrides.keys() {
+        sources
+            .entry(key.clone())
+            .or_default()
+            .push(COMMAND_LINE_SOURCE.to_string());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(json!({ "route": route, "sources": sources })))
+}
@@ -0,0 +1,159 @@
+//! Context values computed from other values, declared as `[[derived]]` in
+//! the config. Templates are plain strings with `{path}` references; there
+//! is deliberately nothing else to evaluate.
+
+use crate::config::DerivedConfig;
+use crate::helpers::lookup;
+use handlebars::JsonRender;
+use serde_json::{Map, Value};
+
+/// Expands the `{path}` references in `template`. `{{` and `}}` are literal
+/// braces. A template that is exactly one reference keeps the value's type.
+fn evaluate(template: &str, root: &Value, key: &str) -> Value {
+    let resolve = |path: &str| {
+        let value = lookup(root, path.trim());
+        if value.is_none() {
+            eprintln!(
+                "Warning: derived key '{}' references missing value '{}'",
+                key, path
+            );
+        }
+        value.cloned()
+    };
+
+    if let Some(path) = template
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .filter(|path| !path.contains(['{', '}']))
+    {
+        return resolve(path).unwrap_or(Value::String(String::new()));
+    }
+
+    let mut output = String::new();
+    let mut rest = template;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("{{") {
+            output.push('{');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("}}") {
+            output.push('}');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{')
+            && let Some(end) = after.find('}')
+        {
+            if let Some(value) = resolve(&after[..end]) {
+                output.push_str(&value.render());
+            }
+            rest = &after[end + 1..];
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let next = rest[first..]
+                .find(['{', '}'])
+                .map_or(rest.len(), |index| index + first);
+            output.push_str(&rest[..next]);
+            rest = &rest[next..];
+        }
+    }
+    Value::String(output)
+}
+
+/// Sets `value` at a dotted `key`, creating objects along the way. Fails
+/// with the segment in the way when one is not an object.
+pub fn insert_path<'k>(
+    context: &mut Map<String, Value>,
+    key: &'k str,
+    value: Value,
+) -> Result<(), &'k str> {
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let last = segments.pop().unwrap_or_default();
+
+    let mut target = context;
+    for segment in segments {
+        let entry = target
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let Value::Object(object) = entry else {
+            return Err(segment);
+        };
+        target = object;
+    }
+    target.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Computes every derived key in declaration order, so a key can use the
+/// ones declared before it.
+pub fn apply(derived: &[DerivedConfig], context: &mut Map<String, Value>) {
+    if derived.is_empty() {
+        return;
+    }
+    // Paths are looked up in a `Value`, so the context is moved into one
+    // for the duration rather than copied for every key
+    let mut root = Value::Object(std::mem::take(context));
+    for entry in derived {
+        let value = evaluate(&entry.value, &root, &entry.key);
+        if let Value::Object(context) = &mut root
+            && let Err(segment) = insert_path(context, &entry.key, value)
+        {
+            eprintln!(
+                "Warning: derived key '{}' skipped: '{}' is not an object",
+                entry.key, segment
+            );
+        }
+    }
+    if let Value::Object(root) = root {
+        *context = root;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn derive(value: &str, context: Value) -> Value {
+        let Value::Object(mut context) = context else {
+            unreachable!()
+        };
+        let derived = [DerivedConfig {
+            key: "out".to_string(),
+            value: value.to_string(),
+        }];
+        apply(&derived, &mut context);
+        context["out"].clone()
+    }
+
+    #[test]
+    fn expands_references_around_multi_byte_text() {
+        let context = json!({"title": "Café"});
+        assert_eq!(
+            derive("é {title} – ü", context.clone()),
+            json!("é Café – ü")
+        );
+        assert_eq!(derive("{{é}}", context), json!("{é}"));
+    }
+
+    #[test]
+    fn a_single_reference_keeps_its_type() {
+        assert_eq!(derive("{a.b}", json!({"a": {"b": [1, 2]}})), json!([1, 2]));
+    }
+
+    #[test]
+    fn later_keys_see_earlier_ones() {
+        let Value::Object(mut context) = json!({"name": "x"}) else {
+            unreachable!()
+        };
+        let derived = [
+            DerivedConfig {
+                key: "a.b".to_string(),
+                value: "{name}!".to_string(),
+            },
+            DerivedConfig {
+                key: "c".to_string(),
+                value: "{a.b}?".to_string(),
+            },
+        ];
+        apply(&derived, &mut context);
+        assert_eq!(context["c"], json!("x!?"));
+    }
+}
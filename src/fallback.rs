@@ -0,0 +1,157 @@
+//! The layers a key of the page context is looked up in, most specific
+//! first: the page's own data, the locale overlay, the site's data, then
+//! `[data.defaults]`. `Site::context_with_problems` merges them in the
+//! reverse order, so a key missing from one layer falls back to the next.
+
+use crate::config::Config;
+use crate::data::{load_locale_files, merge_value};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// The value of `data` at the page's route, e.g. `blog.post` for
+/// `blog/post`, from `data/blog/post/` or `data/blog/post.json`.
+pub fn page_data(data: &Map<String, Value>, route: &str) -> Option<(Vec<String>, Value)> {
+    let path: Vec<String> = route
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    let (first, rest) = path.split_first()?;
+    let mut value = data.get(first)?;
+    for segment in rest {
+        value = value.get(segment)?;
+    }
+    Some((path, value.clone()))
+}
+
+/// Merges `value` over the value at `path` in `context`, creating the
+/// objects on the way.
+pub fn merge_at(context: &mut Map<String, Value>, path: &[String], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut target = context;
+    for segment in parents {
+        let entry = target
+            .entry(segment.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        target = entry.as_object_mut().expect("just made an object");
+    }
+    match target.get_mut(last) {
+        Some(existing) => merge_value(existing, value),
+        None => {
+            target.insert(last.clone(), value);
+        }
+    }
+}
+
+/// The path of every value in `map` that isn't an object.
+fn leaf_paths(map: &Map<String, Value>, prefix: &[String], paths: &mut Vec<Vec<String>>) {
+    for (key, value) in map {
+        let mut path = prefix.to_vec();
+        path.push(key.clone());
+        match value {
+            Value::Object(map) => leaf_paths(map, &path, paths),
+            _ => paths.push(path),
+        }
+    }
+}
+
+/// Whether `map` has a value at `path`.
+fn has_path(map: &Map<String, Value>, path: &[String]) -> bool {
+    let Some((first, rest)) = path.split_first() else {
+        return false;
+    };
+    let mut value = map.get(first);
+    for segment in rest {
+        value = value.and_then(|value| value.get(segment));
+    }
+    value.is_some()
+}
+
+/// Keys with a value in `default_overlay` but none in `overlay`, dotted,
+/// e.g. `site.tagline`.
+fn missing_keys(default_overlay: &Map<String, Value>, overlay: &Map<String, Value>) -> Vec<String> {
+    let mut translated = Vec::new();
+    leaf_paths(default_overlay, &[], &mut translated);
+    translated
+        .into_iter()
+        .filter(|path| !has_path(overlay, path))
+        .map(|path| path.join("."))
+        .collect()
+}
+
+/// Locales and keys already warned about, so each is reported once.
+static WARNED: Mutex<Option<HashSet<(String, String)>>> = Mutex::new(None);
+
+/// Compares, in `dev`, each locale's overlay under `locales/` with the
+/// default locale's and warns about keys only the default one has, since
+/// those fall back to `data/` in the other locale. This is a check of the
+/// overlays' keys, not of what templates look up. It runs at startup, and
+/// for `[[sites]]` sites again whenever their registry is rebuilt; each
+/// overlay is read whole, whatever `scope_by_route` says.
+pub async fn check_overlays(config: &Config) {
+    let default_locale = config.i18n.default_locale();
+    if !config.is_dev()
+        || !config.i18n.is_enabled()
+        || !config.locales_dir().join(default_locale).exists()
+    {
+        return;
+    }
+    let mut whole = config.clone();
+    whole.data.scope_by_route = false;
+    let Ok(default_overlay) = load_locale_files(&whole, default_locale, "").await else {
+        return;
+    };
+    let default_overlay: Map<String, Value> = default_overlay.values.into_iter().collect();
+
+    for locale in config.i18n.locales.iter().filter(|l| *l != default_locale) {
+        let Ok(overlay) = load_locale_files(&whole, locale, "").await else {
+            continue;
+        };
+        let overlay: Map<String, Value> = overlay.values.into_iter().collect();
+
+        let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+        let warned = warned.get_or_insert_with(HashSet::new);
+        for key in missing_keys(&default_overlay, &overlay) {
+            if warned.insert((locale.clone(), key.clone())) {
+                eprintln!(
+                    "Warning: '{}' is in locales/{}/ but not in locales/{}/, so '{}' pages fall back to data/",
+                    key, default_locale, locale, locale
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn keys_only_the_default_overlay_has_are_missing() {
+        let default_overlay = object(json!({
+            "site": {"tagline": "Hello", "title": "Site", "menu": ["Home"]},
+            "footer": "Bye"
+        }));
+        let overlay = object(json!({
+            "site": {"title": "Seite", "extra": "Nur hier"},
+            "footer": {"nested": "Tschüss"}
+        }));
+        let mut missing = missing_keys(&default_overlay, &overlay);
+        missing.sort();
+        assert_eq!(missing, ["site.menu", "site.tagline"]);
+        assert!(missing_keys(&overlay, &overlay).is_empty());
+        assert!(missing_keys(&Map::new(), &overlay).is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+//! Feature flags, exposed to templates as the reserved `flags` object and
+//! checked with `{{#feature "new-nav"}}`. Flags come from `[flags]` in the
+//! config, then `data/flags.json` (or any other format), and outside of
+//! `prod` from the `flags` query parameter.
+
+use crate::config::Config;
+use actix_web::{HttpRequest, web};
+use serde_json::{Map, Value};
+
+/// Data key, and query parameter, that override the configured flags.
+const FLAGS_KEY: &str = "flags";
+
+/// The configured flags with those from the `flags` data file over them.
+/// Values that aren't booleans are skipped with a warning.
+pub fn context(config: &Config, data: &Map<String, Value>) -> Map<String, Value> {
+    let mut flags: Map<String, Value> = config
+        .flags
+        .iter()
+        .map(|(name, enabled)| (name.clone(), Value::Bool(*enabled)))
+        .collect();
+
+    match data.get(FLAGS_KEY) {
+        Some(Value::Object(overrides)) => {
+            for (name, value) in overrides {
+                match value {
+                    Value::Bool(_) => {
+                        flags.insert(name.clone(), value.clone());
+                    }
+                    _ => eprintln!(
+                        "Warning: feature flag '{}' in the flags data is not true or false",
+                        name
+                    ),
+                }
+            }
+        }
+        Some(_) => eprintln!("Warning: the flags data must be an object of true/false values"),
+        None => {}
+    }
+
+    flags
+}
+
+/// Applies `?flags=new-nav,-old-footer` to `flags`: a name turns its flag on,
+/// a leading `-` turns it off. Only applied in `dev`.
+pub fn apply_query(config: &Config, req: &HttpRequest, flags: &mut Value) {
+    if !config.is_dev() {
+        return;
+    }
+    let Value::Object(flags) = flags else {
+        return;
+    };
+
+    let parameters = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    for (_, list) in parameters.iter().filter(|(name, _)| name == FLAGS_KEY) {
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match name.strip_prefix('-') {
+                Some(name) => flags.insert(name.to_string(), Value::Bool(false)),
+                None => flags.insert(name.to_string(), Value::Bool(true)),
+            };
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::config::{Config, FormConfig, FormSinkConfig};
+use crate::data::Globals;
+use crate::limits;
+use crate::pages::PAGES_DIR;
+use crate::post_process::RenderedPage;
+use crate::render;
+use crate::sites::{self, Registry};
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+const REQUIRED_MESSAGE: &str = "This field is required.";
+
+type SinkResult<'a> = Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error>>> + 'a>>;
+
+/// An accepted form submission, as handed to a sink.
+#[derive(Debug, Serialize)]
+pub struct Submission {
+    /// The form's route, identifying which form was submitted.
+    pub form: String,
+    pub values: Map<String, Value>,
+}
+
+/// Destination for accepted submissions. Implement this to add new sinks.
+pub trait FormSink {
+    fn submit<'a>(&'a self, submission: &'a Submission) -> SinkResult<'a>;
+}
+
+struct LogSink;
+
+impl FormSink for LogSink {
+    fn submit<'a>(&'a self, submission: &'a Submission) -> SinkResult<'a> {
+        Box::pin(async move {
+            println!(
+                "Form submission to {}: {}",
+                submission.form,
+                serde_json::to_string(&submission.values)?
+            );
+            Ok(())
+        })
+    }
+}
+
+struct FileSink {
+    path: PathBuf,
+}
+
+impl FormSink for FileSink {
+    fn submit<'a>(&'a self, submission: &'a Submission) -> SinkResult<'a> {
+        Box::pin(async move {
+            let mut line = serde_json::to_string(submission)?;
+            line.push('\n');
+
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(line.as_bytes()).await?;
+            Ok(())
+        })
+    }
+}
+
+struct WebhookSink {
+    url: String,
+}
+
+impl FormSink for WebhookSink {
+    fn submit<'a>(&'a self, submission: &'a Submission) -> SinkResult<'a> {
+        Box::pin(async move {
+            reqwest::Client::new()
+                .post(&self.url)
+                .json(submission)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+fn build_sink(config: &FormSinkConfig) -> Box<dyn FormSink> {
+    match config {
+        FormSinkConfig::Log => Box::new(LogSink),
+        FormSinkConfig::File { path } => Box::new(FileSink { path: path.clone() }),
+        FormSinkConfig::Webhook { url } => Box::new(WebhookSink { url: url.clone() }),
+    }
+}
+
+/// Checks required fields, returning a map of field name to error message.
+fn validate(form: &FormConfig, values: &Map<String, Value>) -> Map<String, Value> {
+    let mut errors = Map::new();
+
+    for field in &form.required {
+        let is_blank = values
+            .get(field)
+            .and_then(Value::as_str)
+            .is_none_or(|value| value.trim().is_empty());
+        if is_blank {
+            errors.insert(field.clone(), Value::String(REQUIRED_MESSAGE.to_string()));
+        }
+    }
+
+    errors
+}
+
+/// Handles a POST to a configured form route: validates the fields, passes
+/// accepted submissions to the sink and renders the success or form page.
+pub async fn handle_form(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    globals: web::Data<Globals>,
+    registry: Option<web::Data<Registry>>,
+    body: web::Form<Vec<(String, String)>>,
+) -> Result<HttpResponse> {
+    let Some(form) = config.forms.iter().find(|form| form.path == req.path()) else {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
+    };
+
+    // Repeated fields keep their last value
+    let mut values = Map::new();
+    for (name, value) in body.into_inner() {
+        values.insert(name, Value::String(value));
+    }
+
+    let errors = validate(form, &values);
+
+    let site = match sites::site(&config, registry.as_ref()).await {
+        Ok(site) => site,
+        Err(e) => {
+            eprintln!("Failed to register templates: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load templates",
+                &e,
+            )));
+        }
+    };
+    let mut context = match site
+        .context(&config, &globals, req.path().trim_start_matches('/'))
+        .await
+    {
+        Ok(context) => context,
+        Err(e) => {
+            eprintln!("Failed to load data files: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load data files",
+                &e,
+            )));
+        }
+    };
+
+    let (page, mut response) = if errors.is_empty() {
+        let submission = Submission {
+            form: form.path.clone(),
+            values: values.clone(),
+        };
+        if let Err(e) = build_sink(&form.sink).submit(&submission).await {
+            eprintln!("Failed to process form submission to {}: {}", form.path, e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to process form",
+                &e,
+            )));
+        }
+        (&form.success_template, HttpResponse::Ok())
+    } else {
+        (&form.template, HttpResponse::UnprocessableEntity())
+    };
+
+    render::insert_reserved(&mut context, &config, "form", Value::Object(values));
+    render::insert_reserved(&mut context, &config, "errors", Value::Object(errors));
+
+    let template_name = format!("{}/{}", PAGES_DIR, page);
+    let scoped = site.scoped_registry(&template_name);
+    let handlebars = scoped.as_ref().unwrap_or(&*site.handlebars);
+    match limits::render(handlebars, &config, &template_name, &context) {
+        Ok(rendered) => {
+            Ok(response
+                .content_type("text/html; charset=utf-8")
+                .body(render::post_process(
+                    &config,
+                    &RenderedPage::html(&template_name),
+                    rendered,
+                )))
+        }
+        Err(e) => Ok(render::render_failure(&config, &template_name, &e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::App;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body};
+
+    #[actix_web::test]
+    async fn submitted_values_count_toward_the_output_limit() {
+        let root = std::env::temp_dir().join(format!("hbs-composer-form-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("templates/pages")).unwrap();
+        std::fs::write(
+            root.join("templates/pages/contact.hbs"),
+            "{{_form.message}}",
+        )
+        .unwrap();
+        let mut config = Config {
+            root: root.clone(),
+            forms: vec![FormConfig {
+                path: "/contact".to_string(),
+                template: "contact".to_string(),
+                success_template: "contact".to_string(),
+                required: vec!["email".to_string()],
+                sink: FormSinkConfig::Log,
+            }],
+            ..Config::default()
+        };
+        config.render.max_output_bytes = 100;
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(Globals::default()))
+                .route("/contact", web::post().to(handle_form)),
+        )
+        .await;
+
+        let post = |message: String| {
+            TestRequest::post()
+                .uri("/contact")
+                .set_form([("message", message)])
+                .to_request()
+        };
+        let response = call_service(&app, post("short".to_string())).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(read_body(response).await, "short");
+        let response = call_service(&app, post("x".repeat(500))).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
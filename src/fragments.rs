@@ -0,0 +1,83 @@
+//! HTML fragments: files in the `fragments` directories under `[data]`,
+//! such as `data/fragments/legal/terms.html`, are registered as partials
+//! named by their path without the extension, `{{> legal/terms}}`, instead
+//! of being loaded into the context. Their content is output as written,
+//! never parsed as a template, so a stray `{{` in hand-written HTML is safe.
+
+use crate::build::{collect_files, manifest_path};
+use crate::config::Config;
+use handlebars::Handlebars;
+use handlebars::template::{Template, TemplateElement};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::fs;
+
+/// File extensions registered as fragments; other files are ignored.
+const EXTENSIONS: [&str; 3] = ["html", "htm", "txt"];
+
+/// Fragments shadowed by a template, so each is warned about once.
+static SHADOWED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// The fragment files as partial names and paths, in the order the
+/// directories are listed. Missing directories have none.
+pub async fn files(config: &Config) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    for dir in &config.data.fragments {
+        let root = config.data_dir().join(dir);
+        if !root.is_dir() {
+            continue;
+        }
+        let found = match collect_files(&root, PathBuf::new()).await {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!(
+                    "Warning: failed to list fragments in {}: {}",
+                    root.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        for file in found {
+            let is_fragment = file.extension().is_some_and(|extension| {
+                EXTENSIONS.contains(&extension.to_string_lossy().as_ref())
+            });
+            if is_fragment {
+                files.push((manifest_path(&file.with_extension("")), root.join(file)));
+            }
+        }
+    }
+    files
+}
+
+/// Registers the fragments as partials. A template of the same name, such
+/// as `templates/legal/terms.hbs`, is kept and the fragment skipped with a
+/// warning; of two fragments with the same name, the first listed wins.
+pub async fn register(handlebars: &mut Handlebars<'_>, config: &Config) {
+    for (name, path) in files(config).await {
+        if handlebars.has_template(&name) {
+            let mut shadowed = SHADOWED.lock().unwrap_or_else(|e| e.into_inner());
+            if shadowed
+                .get_or_insert_with(HashSet::new)
+                .insert(path.clone())
+            {
+                eprintln!(
+                    "Warning: fragment {} is not registered, as the partial '{}' already exists",
+                    path.display(),
+                    name
+                );
+            }
+            continue;
+        }
+        match fs::read_to_string(&path).await {
+            Ok(content) => {
+                let mut template = Template::new();
+                template.name = Some(name.clone());
+                template.elements.push(TemplateElement::RawString(content));
+                handlebars.register_template(&name, template);
+            }
+            Err(e) => eprintln!("Warning: failed to read fragment {}: {}", path.display(), e),
+        }
+    }
+}
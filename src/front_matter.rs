@@ -0,0 +1,28 @@
+use serde_json::{Map, Value};
+
+/// Splits a leading YAML front matter block, delimited by `---` lines, from
+/// the rest of the file. Returns `None` when the file has no front matter.
+pub fn split(content: &str) -> Option<(&str, &str)> {
+    let rest = content.strip_prefix("---").and_then(|rest| {
+        rest.strip_prefix('\n')
+            .or_else(|| rest.strip_prefix("\r\n"))
+    })?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Parses front matter into an object. An empty block yields an empty object.
+pub fn parse(source: &str) -> Result<Map<String, Value>, serde_yaml_ng::Error> {
+    if source.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    serde_yaml_ng::from_str(source)
+}
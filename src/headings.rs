@@ -0,0 +1,171 @@
+use lol_html::html_content::ContentType;
+use lol_html::{
+    ElementContentHandlers, EndTagHandler, RewriteStrSettings, Selector, element,
+    errors::RewritingError, rewrite_str, text,
+};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
+
+const HEADINGS: &str = "h2, h3, h4";
+
+/// Elements whose content is shown as written, so headings in them are code
+/// samples rather than sections.
+const VERBATIM: &str = "pre, code";
+
+/// Id used for headings whose text produces an empty slug.
+const FALLBACK_SLUG: &str = "section";
+
+struct Heading {
+    id: Option<String>,
+    text: String,
+}
+
+/// Lowercases `text` and joins its alphanumeric runs with `-`. Character
+/// references such as `&amp;` act as separators.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    let mut in_reference = false;
+
+    for c in text.chars() {
+        if in_reference {
+            in_reference = c != ';' && !c.is_whitespace();
+            continue;
+        }
+        if c == '&' {
+            in_reference = true;
+        } else if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+            continue;
+        }
+        if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Returns a slug for `text` that isn't in `used`, suffixing `-1`, `-2`, ...
+fn unique_slug(text: &str, used: &mut HashSet<String>) -> String {
+    let mut base = slugify(text);
+    if base.is_empty() {
+        base = FALLBACK_SLUG.to_string();
+    }
+
+    let mut slug = base.clone();
+    let mut suffix = 1;
+    while used.contains(&slug) {
+        slug = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+
+    used.insert(slug.clone());
+    slug
+}
+
+fn escape_attribute(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+/// Counts the open `VERBATIM` elements; a heading is skipped while any is.
+fn track_verbatim<'h>(depth: &Rc<Cell<usize>>) -> (Cow<'h, Selector>, ElementContentHandlers<'h>) {
+    let depth = Rc::clone(depth);
+    element!(VERBATIM, move |el| {
+        let closed = Rc::clone(&depth);
+        let on_close: EndTagHandler<'static> = Box::new(move |_| {
+            closed.set(closed.get().saturating_sub(1));
+            Ok(())
+        });
+        if let Some(handlers) = el.end_tag_handlers() {
+            depth.set(depth.get() + 1);
+            handlers.push(on_close);
+        }
+        Ok(())
+    })
+}
+
+/// Gives every `<h2>`–`<h4>` without an `id` one derived from its text,
+/// de-duplicated against all ids in the document. Existing ids are kept, and
+/// headings inside `<pre>` or `<code>` are left alone. With `anchors`, a `#`
+/// link to the heading is appended inside each heading.
+pub fn add_heading_ids(html: &str, anchors: bool) -> Result<String, RewritingError> {
+    // First pass: collect heading text and the ids already in use
+    let headings: RefCell<Vec<Heading>> = RefCell::new(Vec::new());
+    let used_ids: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    let verbatim = Rc::new(Cell::new(0));
+
+    rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(track_verbatim(&verbatim))
+            .append_element_content_handler(element!("[id]", |el| {
+                if let Some(id) = el.get_attribute("id") {
+                    used_ids.borrow_mut().insert(id);
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!(HEADINGS, |el| {
+                if verbatim.get() > 0 {
+                    return Ok(());
+                }
+                headings.borrow_mut().push(Heading {
+                    id: el.get_attribute("id").filter(|id| !id.is_empty()),
+                    text: String::new(),
+                });
+                Ok(())
+            }))
+            .append_element_content_handler(text!(HEADINGS, |chunk| {
+                if verbatim.get() > 0 {
+                    return Ok(());
+                }
+                if let Some(heading) = headings.borrow_mut().last_mut() {
+                    heading.text.push_str(chunk.as_str());
+                }
+                Ok(())
+            })),
+    )?;
+
+    let mut used_ids = used_ids.into_inner();
+    let ids: Vec<(String, bool)> = headings
+        .into_inner()
+        .into_iter()
+        .map(|heading| match heading.id {
+            Some(id) => (id, false),
+            None => (unique_slug(&heading.text, &mut used_ids), true),
+        })
+        .collect();
+
+    // Second pass: apply the ids in document order
+    let index = Cell::new(0);
+    let verbatim = Rc::new(Cell::new(0));
+    rewrite_str(
+        html,
+        RewriteStrSettings::new()
+            .append_element_content_handler(track_verbatim(&verbatim))
+            .append_element_content_handler(element!(HEADINGS, |el| {
+                if verbatim.get() > 0 {
+                    return Ok(());
+                }
+                let Some((id, generated)) = ids.get(index.get()) else {
+                    return Ok(());
+                };
+                index.set(index.get() + 1);
+
+                if *generated {
+                    el.set_attribute("id", id)?;
+                }
+                if anchors {
+                    el.append(
+                        &format!(
+                            r##"<a class="heading-anchor" href="#{}" aria-hidden="true">#</a>"##,
+                            escape_attribute(id)
+                        ),
+                        ContentType::Html,
+                    );
+                }
+                Ok(())
+            })),
+    )
+}
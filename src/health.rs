@@ -0,0 +1,184 @@
+use crate::config::Config;
+use crate::data::load_data_files;
+use crate::render_cache::RenderCache;
+use crate::sites::{self, Registry};
+use actix_web::http::header;
+use actix_web::{HttpResponse, Result, web};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Reports readiness as JSON: 200 unless a subsystem failed, 503 otherwise.
+pub const ENDPOINT: &str = "/ready";
+
+/// How long a template result stands before `/ready` loads the templates
+/// again, so a probe sees a fix without waiting for a page request.
+const TEMPLATE_RECHECK: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Working, but something was left out, such as a data file that
+    /// failed to parse.
+    Degraded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Subsystem {
+    status: Status,
+    problems: Vec<String>,
+    /// When this was last updated by a load, in RFC 3339.
+    checked_at: String,
+}
+
+impl Subsystem {
+    fn new(status: Status, problems: Vec<String>) -> Subsystem {
+        Subsystem {
+            status,
+            problems,
+            checked_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    templates: Option<Subsystem>,
+    /// When templates were last loaded or claimed for a recheck.
+    templates_at: Option<Instant>,
+    data: Option<Subsystem>,
+}
+
+/// The outcome of the latest template and data loads of one site, shared by
+/// all workers. Page requests update it, and `/ready` reads it.
+#[derive(Debug, Default)]
+pub struct Health {
+    state: Mutex<State>,
+}
+
+impl Health {
+    fn update(&self, update: impl FnOnce(&mut State)) {
+        update(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    fn set_templates(&self, subsystem: Subsystem) {
+        self.update(|state| {
+            state.templates = Some(subsystem);
+            state.templates_at = Some(Instant::now());
+        });
+    }
+
+    pub fn templates_loaded(&self) {
+        self.set_templates(Subsystem::new(Status::Ok, Vec::new()));
+    }
+
+    pub fn templates_failed(&self, error: &dyn std::fmt::Display) {
+        self.set_templates(Subsystem::new(Status::Failed, vec![error.to_string()]));
+    }
+
+    /// Loads the templates, or builds the site's kept registry, and records
+    /// the outcome.
+    pub async fn check_templates(&self, config: &Config, registry: Option<&web::Data<Registry>>) {
+        match sites::site(config, registry).await {
+            Ok(_) => self.templates_loaded(),
+            Err(e) => self.templates_failed(&e),
+        }
+    }
+
+    /// Rechecks the templates when the last result is older than
+    /// `TEMPLATE_RECHECK`. The first caller claims the recheck, so
+    /// concurrent probes don't load the templates more than once.
+    async fn refresh_templates(&self, config: &Config, registry: Option<&web::Data<Registry>>) {
+        let due = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let due = state
+                .templates_at
+                .is_none_or(|at| at.elapsed() >= TEMPLATE_RECHECK);
+            if due {
+                state.templates_at = Some(Instant::now());
+            }
+            due
+        };
+        if due {
+            self.check_templates(config, registry).await;
+        }
+    }
+
+    /// Data loaded; any `problems` mark it degraded.
+    pub fn data_loaded(&self, problems: Vec<String>) {
+        let status = match problems.is_empty() {
+            true => Status::Ok,
+            false => Status::Degraded,
+        };
+        self.update(|state| state.data = Some(Subsystem::new(status, problems)));
+    }
+
+    pub fn data_failed(&self, error: &dyn std::fmt::Display) {
+        let subsystem = Subsystem::new(Status::Failed, vec![error.to_string()]);
+        self.update(|state| state.data = Some(subsystem));
+    }
+
+    /// Records the startup template check and loads the default page's data
+    /// once, so `/ready` has a status before the first page request.
+    pub async fn check(&self, config: &Config, templates: &Result<Vec<String>, String>) {
+        match templates {
+            Ok(_) => self.templates_loaded(),
+            Err(e) => self.templates_failed(e),
+        }
+        self.check_data(config).await;
+    }
+
+    /// Loads the default page's data and records the outcome.
+    pub async fn check_data(&self, config: &Config) {
+        match load_data_files(config, config.routing.default_page()).await {
+            Ok(data) => self.data_loaded(data.problems),
+            Err(e) => self.data_failed(&e),
+        }
+    }
+}
+
+/// Serves `/ready` with the overall status and one entry per subsystem of
+/// the requested site, loading its templates again when the last result is
+/// stale.
+pub async fn ready(
+    config: web::Data<Config>,
+    health: web::Data<Health>,
+    cache: web::Data<RenderCache>,
+    registry: Option<web::Data<Registry>>,
+) -> Result<HttpResponse> {
+    health.refresh_templates(&config, registry.as_ref()).await;
+    let (templates, data) = {
+        let state = health.state.lock().unwrap_or_else(|e| e.into_inner());
+        (state.templates.clone(), state.data.clone())
+    };
+    let status = [&templates, &data]
+        .into_iter()
+        .flatten()
+        .map(|subsystem| subsystem.status)
+        .max()
+        .unwrap_or(Status::Ok);
+    let render_cache = match config.render.cache {
+        true => json!({ "status": Status::Ok, "entries": cache.len() }),
+        false => json!({ "status": "off" }),
+    };
+
+    let body = json!({
+        "status": status,
+        "checks": {
+            "templates": templates,
+            "data": data,
+            "render_cache": render_cache,
+        },
+    });
+    let mut response = match status {
+        Status::Failed => HttpResponse::ServiceUnavailable(),
+        _ => HttpResponse::Ok(),
+    };
+    Ok(response
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(body))
+}
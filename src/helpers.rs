@@ -0,0 +1,341 @@
+mod aggregate;
+mod amp;
+mod breadcrumbs;
+mod classes;
+mod collections;
+mod contains;
+mod count;
+mod declared;
+mod environment;
+mod feature;
+mod field_error;
+mod get;
+mod icons;
+mod if_partial;
+mod image_placeholder;
+mod images;
+mod inline;
+mod json_ld;
+mod last_modified;
+mod locale_format;
+mod navigation;
+mod page_title;
+mod pagination;
+mod query;
+mod reading_time;
+mod render_page;
+mod signed_url;
+mod tables;
+mod time_ago;
+mod translate;
+mod units;
+mod version;
+
+pub use collections::{group_by, sort_by};
+pub use declared::TEMPLATE_PREFIX as DECLARED_TEMPLATE_PREFIX;
+pub use get::lookup;
+pub use reading_time::minutes as reading_minutes;
+pub use time_ago::parse as parse_date;
+pub use version::parse as parse_version;
+
+use crate::cache_stats::CacheStats;
+use crate::config::Config;
+use crate::pages::Page;
+use chrono::Utc;
+use chrono_tz::Tz;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use locale_format::LocaleSource;
+
+/// Front matter key mapping page-scoped helper names to Rhai script files.
+const PAGE_HELPERS_KEY: &str = "helpers";
+
+/// Used by `{{now}}` when no format is given: the 4-digit year.
+const DEFAULT_NOW_FORMAT: &str = "%Y";
+
+/// `{{now "%B %-d, %Y"}}` renders the current time in the configured
+/// timezone, formatted with a strftime pattern in the locale's month names.
+struct NowHelper {
+    timezone: Tz,
+    locales: LocaleSource,
+}
+
+impl HelperDef for NowHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let format = match h.param(0) {
+            Some(param) => param
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType(
+                    "strftime format string",
+                ))?,
+            None => DEFAULT_NOW_FORMAT,
+        };
+
+        let formatted = locale_format::format_date(
+            &Utc::now().with_timezone(&self.timezone),
+            format,
+            self.locales.conventions(h, ctx),
+        )?;
+
+        out.write(&formatted)?;
+        Ok(())
+    }
+}
+
+/// The caches helpers keep across requests, for `/_debug/cache`.
+pub fn cache_stats() -> Vec<CacheStats> {
+    vec![inline::cache_stats(), image_placeholder::cache_stats()]
+}
+
+/// Registers the built-in helpers and those declared with `[[helpers]]`.
+pub fn register(handlebars: &mut Handlebars, config: &Config) {
+    register_builtins(handlebars, config);
+    declared::register(handlebars, &config.helpers);
+}
+
+/// Checks the helpers declared with `[[helpers]]`, whose names mustn't
+/// shadow a built-in helper, page helpers included.
+pub fn validate_declared(config: &Config) -> Result<(), String> {
+    let mut builtins = Handlebars::new();
+    register_builtins(&mut builtins, config);
+    register_page_helpers(&mut builtins, config, &[]);
+    declared::validate(builtins, &config.helpers)
+}
+
+fn register_builtins(handlebars: &mut Handlebars, config: &Config) {
+    let prefix = config.reserved_prefix();
+    let locales = || LocaleSource::new(prefix, config.i18n.default_locale());
+    handlebars.register_helper(
+        "activeClass",
+        Box::new(navigation::ActiveClassHelper::new(prefix)),
+    );
+    for aggregate in [
+        aggregate::Aggregate::Avg,
+        aggregate::Aggregate::Max,
+        aggregate::Aggregate::Min,
+        aggregate::Aggregate::Sum,
+    ] {
+        handlebars.register_helper(
+            aggregate.helper_name(),
+            Box::new(aggregate::AggregateHelper(aggregate)),
+        );
+    }
+    handlebars.register_helper(
+        "autolink",
+        Box::new(navigation::AutolinkHelper::new(
+            config.site.base_url.as_deref(),
+        )),
+    );
+    handlebars.register_helper("chunk", Box::new(collections::ChunkHelper));
+    handlebars.register_helper("classes", Box::new(classes::ClassesHelper));
+    handlebars.register_helper("contains", Box::new(contains::ContainsHelper));
+    handlebars.register_helper("count", Box::new(count::CountHelper::new(locales())));
+    handlebars.register_helper(
+        "currency",
+        Box::new(locale_format::CurrencyHelper::new(locales())),
+    );
+    handlebars.register_helper("dl", Box::new(tables::DlHelper));
+    handlebars.register_helper("duration", Box::new(units::DurationHelper));
+    handlebars.register_helper(
+        "exceptIn",
+        Box::new(environment::EnvironmentHelper::except_in(
+            config.environment_name(),
+        )),
+    );
+    handlebars.register_helper(
+        "externalLink",
+        Box::new(navigation::ExternalLinkHelper::new(
+            config.site.base_url.as_deref(),
+        )),
+    );
+    handlebars.register_helper("feature", Box::new(feature::FeatureHelper::new(prefix)));
+    handlebars.register_helper("fieldError", Box::new(field_error::FieldErrorHelper));
+    handlebars.register_helper("filesize", Box::new(units::FilesizeHelper::new(locales())));
+    handlebars.register_helper("get", Box::new(get::GetHelper));
+    handlebars.register_helper("groupBy", Box::new(collections::GroupByHelper));
+    handlebars.register_helper(
+        "icon",
+        Box::new(icons::IconHelper::new(
+            &config.icons,
+            &config.static_dir(),
+            config.is_dev(),
+        )),
+    );
+    handlebars.register_helper("ifFieldError", Box::new(field_error::IfFieldErrorHelper));
+    handlebars.register_helper("ifPartial", Box::new(if_partial::IfPartialHelper));
+    handlebars.register_helper("ifVersionGte", Box::new(version::IfVersionGteHelper));
+    handlebars.register_helper(
+        "image",
+        Box::new(images::ImageHelper::new(
+            &config.images,
+            &config.static_dir(),
+        )),
+    );
+    handlebars.register_helper(
+        "imagePlaceholder",
+        Box::new(image_placeholder::ImagePlaceholderHelper::new(
+            &config.static_dir(),
+            &config.images.placeholder_fallback,
+        )),
+    );
+    handlebars.register_helper(
+        "inlineCss",
+        Box::new(inline::InlineHelper::css(&config.static_dir())),
+    );
+    handlebars.register_helper(
+        "inlineJs",
+        Box::new(inline::InlineHelper::js(&config.static_dir())),
+    );
+    handlebars.register_helper("join", Box::new(collections::JoinHelper));
+    handlebars.register_helper(
+        "lastModified",
+        Box::new(last_modified::LastModifiedHelper::new(
+            prefix,
+            config.site.timezone.unwrap_or(Tz::UTC),
+            locales(),
+        )),
+    );
+    handlebars.register_helper(
+        "linkAttrs",
+        Box::new(navigation::LinkAttrsHelper::new(
+            config.site.base_url.as_deref(),
+        )),
+    );
+    handlebars.register_helper("mergeQuery", Box::new(query::MergeQueryHelper::new(prefix)));
+    for kind in [
+        query::QueryType::String,
+        query::QueryType::Int,
+        query::QueryType::Bool,
+    ] {
+        handlebars.register_helper(
+            kind.helper_name(),
+            Box::new(query::QueryHelper::new(kind, prefix)),
+        );
+    }
+    handlebars.register_helper(
+        "number",
+        Box::new(locale_format::NumberHelper::new(locales())),
+    );
+    handlebars.register_helper(
+        "onlyIn",
+        Box::new(environment::EnvironmentHelper::only_in(
+            config.environment_name(),
+        )),
+    );
+    handlebars.register_helper("pageRange", Box::new(pagination::PageRangeHelper));
+    handlebars.register_helper("queryString", Box::new(query::QueryStringHelper));
+    handlebars.register_helper(
+        "readingTime",
+        Box::new(reading_time::ReadingTimeHelper::new(
+            &config.reading_time,
+            false,
+        )),
+    );
+    handlebars.register_helper(
+        "readingTimeLabel",
+        Box::new(reading_time::ReadingTimeHelper::new(
+            &config.reading_time,
+            true,
+        )),
+    );
+    handlebars.register_helper("renderPage", Box::new(render_page::RenderPageHelper));
+    handlebars.register_helper(
+        "signedUrl",
+        Box::new(signed_url::SignedUrlHelper::new(&config.static_files)),
+    );
+    handlebars.register_helper("sortBy", Box::new(collections::SortByHelper));
+    handlebars.register_helper("table", Box::new(tables::TableHelper));
+    handlebars.register_helper(
+        "t",
+        Box::new(translate::TranslateHelper::new(
+            prefix,
+            config.i18n.default_locale(),
+        )),
+    );
+    handlebars.register_helper(
+        "timeAgo",
+        Box::new(time_ago::TimeAgoHelper::new(&config.time_ago, locales())),
+    );
+    handlebars.register_helper("unique", Box::new(collections::UniqueHelper));
+    handlebars.register_helper(
+        "now",
+        Box::new(NowHelper {
+            timezone: config.site.timezone.unwrap_or(Tz::UTC),
+            locales: locales(),
+        }),
+    );
+}
+
+/// Registers the helpers that need the site's pages, once they are read.
+pub fn register_page_helpers(handlebars: &mut Handlebars, config: &Config, pages: &[Page]) {
+    handlebars.register_helper("ampLink", Box::new(amp::AmpLinkHelper::new(config, pages)));
+    handlebars.register_helper(
+        "breadcrumbs",
+        Box::new(breadcrumbs::BreadcrumbsHelper::new(config, pages)),
+    );
+    handlebars.register_helper(
+        "jsonLd",
+        Box::new(json_ld::JsonLdHelper::new(config, pages)),
+    );
+    handlebars.register_helper(
+        "pageTitle",
+        Box::new(page_title::PageTitleHelper::new(&config.title, pages)),
+    );
+}
+
+/// A copy of `handlebars` with the helpers declared in the page's front
+/// matter registered on top, or `None` when it declares none. Cloning the
+/// registry and compiling the scripts happens on every render of the page.
+pub fn scoped_registry(
+    handlebars: &Handlebars<'static>,
+    page: &Page,
+) -> Option<Handlebars<'static>> {
+    let helpers = page.front_matter.get(PAGE_HELPERS_KEY)?;
+    let Some(helpers) = helpers.as_object() else {
+        eprintln!(
+            "Ignoring `{}` in page '{}': expected a map of helper names to script files",
+            PAGE_HELPERS_KEY, page.name
+        );
+        return None;
+    };
+
+    #[cfg(feature = "scripts")]
+    {
+        let mut scoped = handlebars.clone();
+        for (name, path) in helpers {
+            let Some(path) = path.as_str() else {
+                eprintln!(
+                    "Helper '{}' in page '{}' must name a script file",
+                    name, page.name
+                );
+                continue;
+            };
+            if let Err(e) = scoped.register_script_helper_file(name, path) {
+                eprintln!(
+                    "Failed to register helper '{}' for page '{}' from {}: {}",
+                    name, page.name, path, e
+                );
+            }
+        }
+        Some(scoped)
+    }
+
+    #[cfg(not(feature = "scripts"))]
+    {
+        let _ = (handlebars, helpers);
+        eprintln!(
+            "Page '{}' declares helpers, but script helpers are disabled; rebuild with `--features scripts`",
+            page.name
+        );
+        None
+    }
+}
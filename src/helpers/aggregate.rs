@@ -0,0 +1,113 @@
+//! `sum`, `avg`, `min` and `max` over an array of numbers, or over a numeric
+//! field of an array of objects: `{{sum orders "total"}}`. They return
+//! numbers, so they also work as subexpressions, as in
+//! `{{#if (gt (sum cart "price") 100)}}`.
+
+use super::get::lookup;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::{Number, Value};
+
+#[derive(Clone, Copy)]
+pub enum Aggregate {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn helper_name(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+
+    /// `None` for no values, except for a sum, which is 0.
+    fn apply(self, values: &[f64]) -> Option<f64> {
+        match self {
+            Aggregate::Sum => Some(values.iter().sum()),
+            _ if values.is_empty() => None,
+            Aggregate::Avg => Some(values.iter().sum::<f64>() / values.len() as f64),
+            Aggregate::Min => values.iter().copied().reduce(f64::min),
+            Aggregate::Max => values.iter().copied().reduce(f64::max),
+        }
+    }
+}
+
+/// Whole results are returned as integers, so a sum renders as `12`, not
+/// `12.0`.
+fn to_number(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        return Value::from(value as i64);
+    }
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+/// The numeric values of `items`, or of their `field`. Numbers and strings
+/// holding numbers count; missing and null values are skipped silently, and
+/// anything else with a warning.
+fn numbers(name: &str, items: &[Value], field: Option<&str>) -> Vec<f64> {
+    let mut numbers = Vec::new();
+    for item in items {
+        let value = match field {
+            Some(field) => lookup(item, field),
+            None => Some(item),
+        };
+        let number = match value {
+            None | Some(Value::Null) => continue,
+            Some(Value::Number(number)) => number.as_f64(),
+            Some(Value::String(s)) => s.trim().parse().ok(),
+            Some(_) => None,
+        };
+        match number {
+            Some(number) => numbers.push(number),
+            None => eprintln!(
+                "Warning: {} skipped a non-numeric value{}",
+                name,
+                field
+                    .map(|field| format!(" of '{}'", field))
+                    .unwrap_or_default()
+            ),
+        }
+    }
+    numbers
+}
+
+/// `{{sum items "field"}}`, and likewise `avg`, `min` and `max`.
+pub struct AggregateHelper(pub Aggregate);
+
+impl HelperDef for AggregateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let name = self.0.helper_name();
+        let items = match h.param(0).map(|param| param.value()) {
+            Some(Value::Array(items)) => items.as_slice(),
+            Some(Value::Null) => &[],
+            Some(_) => return Err(RenderErrorReason::InvalidParamType("array").into()),
+            None => return Err(RenderErrorReason::ParamNotFoundForIndex(name, 0).into()),
+        };
+        let field = match h.param(1) {
+            Some(field) => Some(
+                field
+                    .value()
+                    .as_str()
+                    .ok_or(RenderErrorReason::InvalidParamType("field string"))?,
+            ),
+            None => None,
+        };
+
+        let result = self.0.apply(&numbers(name, items, field));
+        Ok(result.map_or(Value::Null, to_number).into())
+    }
+}
@@ -0,0 +1,69 @@
+//! `{{ampLink}}` pairs a page with its AMP variant: on a page that has one
+//! it emits `<link rel="amphtml">` pointing at the variant, and on the
+//! variant `<link rel="canonical">` pointing back, so a shared layout can
+//! carry it. Elsewhere it emits nothing.
+
+use crate::config::Config;
+use crate::pages::{self, Page};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, html_escape,
+};
+use std::collections::HashMap;
+
+pub struct AmpLinkHelper {
+    /// The `<link>` tag by template name, for pages with an AMP variant and
+    /// for the variants.
+    links: HashMap<String, String>,
+}
+
+impl AmpLinkHelper {
+    pub fn new(config: &Config, pages: &[Page]) -> AmpLinkHelper {
+        let base_url = config
+            .site
+            .base_url
+            .as_deref()
+            .map(|base_url| base_url.trim_end_matches('/'))
+            .unwrap_or_default();
+        let trailing_slash = config.routing.trailing_slash;
+        let link = |rel: &str, url: &str| {
+            format!(
+                "<link rel=\"{}\" href=\"{}\">",
+                rel,
+                html_escape(&format!("{}{}", base_url, url))
+            )
+        };
+
+        let mut links = HashMap::new();
+        for variant in pages {
+            let Some(name) = variant.name.strip_suffix(pages::AMP_SUFFIX) else {
+                continue;
+            };
+            let Some(page) = pages.iter().find(|page| page.name == name) else {
+                continue;
+            };
+            let url = page.url(trailing_slash);
+            links.insert(page.template_name(), link("amphtml", &pages::amp_url(&url)));
+            links.insert(variant.template_name(), link("canonical", &url));
+        }
+        AmpLinkHelper { links }
+    }
+}
+
+impl HelperDef for AmpLinkHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        _: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        if let Some(link) = rc
+            .get_root_template_name()
+            .and_then(|template_name| self.links.get(template_name))
+        {
+            out.write(link)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! `{{#breadcrumbs}}<a href="{{url}}">{{name}}</a>{{/breadcrumbs}}` iterates
+//! the trail from the home page to the current one: an entry `{name, url,
+//! current}` for each page at an ancestor path of `_path`, so `/blog/post`
+//! gives `/`, `/blog` and `/blog/post`. `jsonLd=true` emits the same trail
+//! as a `BreadcrumbList` block too, and as a subexpression it returns the
+//! entries.
+
+use super::collections::render_each;
+use super::json_ld::JsonLdHelper;
+use crate::config::Config;
+use crate::pages::Page;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson,
+};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+/// The home page's name unless `home=` is given or it sets `breadcrumb`
+/// or `nav_title`; its `title` is usually the site's name.
+const DEFAULT_HOME: &str = "Home";
+
+struct Crumb {
+    url: String,
+    /// From front matter, if set there.
+    name: Option<Value>,
+}
+
+pub struct BreadcrumbsHelper {
+    /// Context key holding the current path, e.g. `_path`.
+    path_key: String,
+    /// Public pages by route: `""` for `index`, `blog` for `blog/index`.
+    crumbs: HashMap<String, Crumb>,
+    json_ld: JsonLdHelper,
+}
+
+impl BreadcrumbsHelper {
+    pub fn new(config: &Config, pages: &[Page]) -> BreadcrumbsHelper {
+        let trailing_slash = config.routing.trailing_slash;
+        let mut crumbs = HashMap::new();
+        for page in pages.iter().filter(|page| page.is_public()) {
+            let route = match page.name.as_str() {
+                "index" => "",
+                name => name.strip_suffix("/index").unwrap_or(name),
+            };
+            let keys: &[&str] = match route {
+                "" => &["breadcrumb", "nav_title"],
+                _ => &["breadcrumb", "nav_title", "title"],
+            };
+            let name = keys
+                .iter()
+                .find_map(|key| page.front_matter.get(*key))
+                .cloned();
+            crumbs.entry(route.to_string()).or_insert(Crumb {
+                url: page.url(trailing_slash),
+                name,
+            });
+        }
+        BreadcrumbsHelper {
+            path_key: format!("{}path", config.reserved_prefix()),
+            crumbs,
+            json_ld: JsonLdHelper::new(config, pages),
+        }
+    }
+
+    /// The entries for the page at `path`. Ancestors without a page of
+    /// their own are left out, so every entry links somewhere.
+    fn trail(&self, path: &str, home: &str) -> Vec<Value> {
+        let path = path.trim_matches('/');
+        let mut routes = vec![String::new()];
+        let mut route = String::new();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if !route.is_empty() {
+                route.push('/');
+            }
+            route.push_str(segment);
+            routes.push(route.clone());
+        }
+
+        routes
+            .into_iter()
+            .filter_map(|route| {
+                let crumb = self.crumbs.get(&route)?;
+                let name = match (&crumb.name, route.as_str()) {
+                    (Some(name), _) => name.clone(),
+                    (None, "") => Value::String(home.to_string()),
+                    (None, route) => Value::String(humanize(route.rsplit('/').next()?)),
+                };
+                Some(json!({ "name": name, "url": crumb.url, "current": route == path }))
+            })
+            .collect()
+    }
+
+    fn entries(&self, h: &Helper, ctx: &Context) -> Result<Vec<Value>, RenderError> {
+        let home = match h.hash_get("home") {
+            Some(home) => home
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("home name string"))?,
+            None => DEFAULT_HOME,
+        };
+        let path = ctx
+            .data()
+            .get(&self.path_key)
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        Ok(self.trail(path, home))
+    }
+}
+
+/// `release-notes` as `Release notes`, for pages without a title.
+fn humanize(segment: &str) -> String {
+    let words = segment.replace(['-', '_'], " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+impl HelperDef for BreadcrumbsHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        Ok(Value::Array(self.entries(h, ctx)?).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let entries = self.entries(h, ctx)?;
+        let json_ld = h
+            .hash_get("jsonLd")
+            .and_then(|json_ld| json_ld.value().as_bool())
+            .unwrap_or(false);
+        if !h.is_block() && !json_ld {
+            return Err(RenderErrorReason::Other(
+                "breadcrumbs: use it as a block, as a subexpression or with jsonLd=true"
+                    .to_string(),
+            )
+            .into());
+        }
+
+        let data = Value::Array(entries.clone());
+        if h.is_block() {
+            render_each(entries, h, r, ctx, rc, out)?;
+        }
+        if json_ld {
+            let template_name = rc.get_root_template_name().cloned().unwrap_or_default();
+            out.write(&self.json_ld.script(
+                "BreadcrumbList",
+                Some(&data),
+                &template_name,
+                None,
+            )?)?;
+        }
+        Ok(())
+    }
+}
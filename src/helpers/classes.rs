@@ -0,0 +1,62 @@
+//! `{{classes "card" featured="card--featured" large=(gt size 3)}}` builds a
+//! class attribute value from base classes and conditional ones, without the
+//! stray spaces `{{#if}}` blocks leave behind.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonRender, JsonTruthy, RenderContext, RenderError,
+    ScopedJson,
+};
+use serde_json::Value;
+
+pub struct ClassesHelper;
+
+/// Adds each whitespace-separated class in `classes` once.
+fn push_classes(list: &mut Vec<String>, classes: &str) {
+    for class in classes.split_whitespace() {
+        if !list.iter().any(|existing| existing == class) {
+            list.push(class.to_string());
+        }
+    }
+}
+
+impl HelperDef for ClassesHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let mut list = Vec::new();
+
+        // Base classes; null and false leave nothing, as from `(if x "a")`
+        for param in h.params() {
+            match param.value() {
+                Value::Null | Value::Bool(false) => {}
+                value => push_classes(&mut list, &value.render()),
+            }
+        }
+
+        // `key="class"` adds the class when the context value `key` is
+        // truthy; any other value is the condition for the class `key` itself
+        for (key, param) in h.hash() {
+            match param.value() {
+                Value::String(class) => {
+                    let condition = rc
+                        .evaluate(ctx, key)
+                        .is_ok_and(|value| value.as_json().is_truthy(false));
+                    if condition {
+                        push_classes(&mut list, class);
+                    }
+                }
+                condition => {
+                    if condition.is_truthy(false) {
+                        push_classes(&mut list, key);
+                    }
+                }
+            }
+        }
+
+        Ok(Value::String(list.join(" ")).into())
+    }
+}
@@ -0,0 +1,394 @@
+//! `groupBy`, `sortBy`, `chunk` and `unique`. Each works as a block, iterating like
+//! `each`, or as a subexpression returning the new array, so they compose:
+//! `{{#groupBy (sortBy posts "date" dir="desc") "year"}}`. `join` renders a
+//! collection inline.
+
+use super::get::lookup;
+use handlebars::{
+    BlockContext, Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output,
+    RenderContext, RenderError, RenderErrorReason, Renderable, ScopedJson, to_json,
+};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+
+/// Key of the group collecting items that lack the grouping key.
+const OTHER_GROUP: &str = "other";
+
+/// Numbers, and strings that parse as numbers, compare numerically and come
+/// before other strings, which compare lexically.
+fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    fn as_number(value: &Value) -> Option<f64> {
+        match value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    match (as_number(a), as_number(b)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.render().cmp(&b.render()),
+    }
+}
+
+/// Reads `items`, `key` and `dir=` for either helper.
+fn arguments<'h>(
+    h: &'h Helper,
+    name: &'static str,
+) -> Result<(Vec<Value>, &'h str, bool), RenderError> {
+    let items = match h.param(0).map(|param| param.value()) {
+        Some(Value::Array(items)) => items.clone(),
+        Some(Value::Null) => Vec::new(),
+        Some(_) => return Err(RenderErrorReason::InvalidParamType("array").into()),
+        None => return Err(RenderErrorReason::ParamNotFoundForIndex(name, 0).into()),
+    };
+    let key = h
+        .param(1)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 1))?
+        .value()
+        .as_str()
+        .ok_or(RenderErrorReason::InvalidParamType("key string"))?;
+    let descending = match h.hash_get("dir").and_then(|dir| dir.value().as_str()) {
+        None | Some("asc") => false,
+        Some("desc") => true,
+        Some(_) => {
+            return Err(RenderErrorReason::InvalidParamType("dir=\"asc\" or \"desc\"").into());
+        }
+    };
+
+    Ok((items, key, descending))
+}
+
+/// Sorts by `key`; items without it go last in either direction. The sort is
+/// stable, so ties keep their input order.
+pub fn sort_by(mut items: Vec<Value>, key: &str, descending: bool) -> Vec<Value> {
+    items.sort_by(|a, b| match (lookup(a, key), lookup(b, key)) {
+        (Some(a), Some(b)) if descending => compare_keys(b, a),
+        (Some(a), Some(b)) => compare_keys(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    items
+}
+
+/// Groups by `key` into `{key, items}` objects ordered by key, with items
+/// lacking the key collected last under `"other"`. Items keep their order.
+pub fn group_by(items: Vec<Value>, key: &str, descending: bool) -> Vec<Value> {
+    let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+    let mut other = Vec::new();
+
+    for item in items {
+        let Some(group_key) = lookup(&item, key).cloned() else {
+            other.push(item);
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(existing, _)| *existing == group_key)
+        {
+            Some((_, group)) => group.push(item),
+            None => groups.push((group_key, vec![item])),
+        }
+    }
+
+    groups.sort_by(|(a, _), (b, _)| match descending {
+        true => compare_keys(b, a),
+        false => compare_keys(a, b),
+    });
+    if !other.is_empty() {
+        groups.push((Value::String(OTHER_GROUP.to_string()), other));
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, items)| json!({ "key": key, "items": items }))
+        .collect()
+}
+
+/// Renders the block once per value with `@index`, `@first` and `@last`, or
+/// the `{{else}}` block when there are none.
+pub(super) fn render_each<'reg: 'rc, 'rc>(
+    values: Vec<Value>,
+    h: &Helper<'rc>,
+    r: &'reg Handlebars<'reg>,
+    ctx: &'rc Context,
+    rc: &mut RenderContext<'reg, 'rc>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(template) = h.template() else {
+        out.write(&Value::Array(values).render())?;
+        return Ok(());
+    };
+
+    if values.is_empty() {
+        if let Some(inverse) = h.inverse() {
+            inverse.render(r, ctx, rc, out)?;
+        }
+        return Ok(());
+    }
+
+    let len = values.len();
+    for (index, value) in values.into_iter().enumerate() {
+        let mut block = BlockContext::new();
+        block.set_local_var("index", to_json(index));
+        block.set_local_var("first", to_json(index == 0));
+        block.set_local_var("last", to_json(index == len - 1));
+        block.set_base_value(value);
+
+        rc.push_block(block);
+        let result = template.render(r, ctx, rc, out);
+        rc.pop_block();
+        result?;
+    }
+
+    Ok(())
+}
+
+/// `{{#sortBy items "key" dir="desc"}}...{{/sortBy}}`
+pub struct SortByHelper;
+
+impl HelperDef for SortByHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let (items, key, descending) = arguments(h, "sortBy")?;
+        Ok(Value::Array(sort_by(items, key, descending)).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let (items, key, descending) = arguments(h, "sortBy")?;
+        render_each(sort_by(items, key, descending), h, r, ctx, rc, out)
+    }
+}
+
+/// `{{#groupBy items "key"}}{{key}}: {{#each items}}...{{/each}}{{/groupBy}}`
+pub struct GroupByHelper;
+
+impl HelperDef for GroupByHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let (items, key, descending) = arguments(h, "groupBy")?;
+        Ok(Value::Array(group_by(items, key, descending)).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let (items, key, descending) = arguments(h, "groupBy")?;
+        render_each(group_by(items, key, descending), h, r, ctx, rc, out)
+    }
+}
+
+/// Reads `items` and the chunk size. Input other than an array yields no
+/// chunks at all.
+fn chunk_arguments(h: &Helper) -> Result<(Option<Vec<Value>>, usize), RenderError> {
+    let size = h
+        .param(1)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("chunk", 1))?
+        .value()
+        .as_u64()
+        .filter(|size| *size > 0)
+        .ok_or(RenderErrorReason::InvalidParamType("positive chunk size"))?;
+    let items = match h.param(0).map(|param| param.value()) {
+        Some(Value::Array(items)) => Some(items.clone()),
+        _ => None,
+    };
+    Ok((items, size as usize))
+}
+
+/// Splits `items` into arrays of `size`; the last one may be shorter.
+fn chunk(items: Vec<Value>, size: usize) -> Vec<Value> {
+    items
+        .chunks(size)
+        .map(|chunk| Value::Array(chunk.to_vec()))
+        .collect()
+}
+
+/// `{{#chunk cards 3}}<div class="row">{{#each this}}...{{/each}}</div>{{/chunk}}`
+pub struct ChunkHelper;
+
+impl HelperDef for ChunkHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let (items, size) = chunk_arguments(h)?;
+        Ok(Value::Array(chunk(items.unwrap_or_default(), size)).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        match chunk_arguments(h)? {
+            (Some(items), size) => render_each(chunk(items, size), h, r, ctx, rc, out),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// Reads `items` and `field=`. Input other than an array yields nothing.
+fn unique_arguments<'h>(
+    h: &'h Helper,
+) -> Result<(Option<Vec<Value>>, Option<&'h str>), RenderError> {
+    if h.params().is_empty() {
+        return Err(RenderErrorReason::ParamNotFoundForIndex("unique", 0).into());
+    }
+    let field = match h.hash_get("field") {
+        Some(field) => Some(
+            field
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("field string"))?,
+        ),
+        None => None,
+    };
+    let items = match h.param(0).map(|param| param.value()) {
+        Some(Value::Array(items)) => Some(items.clone()),
+        _ => None,
+    };
+    Ok((items, field))
+}
+
+/// The first item with each distinct value, or value of `field`, in input
+/// order. Items lacking `field` are all kept, as there is nothing to compare.
+fn unique(items: Vec<Value>, field: Option<&str>) -> Vec<Value> {
+    let mut seen: Vec<Value> = Vec::new();
+    items
+        .into_iter()
+        .filter(|item| {
+            let value = match field {
+                Some(field) => match lookup(item, field) {
+                    Some(value) => value,
+                    None => return true,
+                },
+                None => item,
+            };
+            match seen.contains(value) {
+                true => false,
+                false => {
+                    seen.push(value.clone());
+                    true
+                }
+            }
+        })
+        .collect()
+}
+
+/// `{{#unique posts field="slug"}}...{{/unique}}`, or `{{join (unique tags)}}`
+pub struct UniqueHelper;
+
+impl HelperDef for UniqueHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let (items, field) = unique_arguments(h)?;
+        Ok(Value::Array(unique(items.unwrap_or_default(), field)).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        match unique_arguments(h)? {
+            (Some(items), field) => render_each(unique(items, field), h, r, ctx, rc, out),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// `{{join tags ", " field="name"}}`. Elements are escaped; the separator is
+/// written as given, so it may contain markup such as `&rsaquo;`. Elements
+/// lacking `field` are skipped.
+pub struct JoinHelper;
+
+impl HelperDef for JoinHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("join", 0))?
+            .value();
+        let separator = match h.param(1) {
+            Some(param) => param
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("separator string"))?,
+            None => ", ",
+        };
+        let field = match h.hash_get("field") {
+            Some(field) => Some(
+                field
+                    .value()
+                    .as_str()
+                    .ok_or(RenderErrorReason::InvalidParamType("field string"))?,
+            ),
+            None => None,
+        };
+
+        let elements: Vec<&Value> = match value {
+            Value::Array(items) => items
+                .iter()
+                .filter_map(|item| match field {
+                    Some(field) => lookup(item, field),
+                    None => Some(item),
+                })
+                .collect(),
+            Value::Null => Vec::new(),
+            other => vec![other],
+        };
+
+        let joined = elements
+            .iter()
+            .map(|element| r.get_escape_fn()(&element.render()))
+            .collect::<Vec<_>>()
+            .join(separator);
+        out.write(&joined)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+//! `{{#contains page.tags tag}}...{{else}}...{{/contains}}` renders its block
+//! when the array holds the value; inline, `{{contains page.tags tag}}` is a
+//! boolean for subexpressions such as `{{#if (or (contains a x) …)}}`.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderError, RenderErrorReason, Renderable, ScopedJson,
+};
+use serde_json::Value;
+
+/// Equal values match, and so do a number and a string holding the same
+/// number, e.g. an id from the query string against numeric ids.
+fn matches(element: &Value, value: &Value) -> bool {
+    fn as_number(value: &Value) -> Option<f64> {
+        match value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    element == value
+        || match (element, value) {
+            (Value::Number(_), Value::Number(_) | Value::String(_))
+            | (Value::String(_), Value::Number(_)) => {
+                as_number(element).is_some_and(|element| Some(element) == as_number(value))
+            }
+            _ => false,
+        }
+}
+
+/// Whether the first parameter is an array holding the second; input other
+/// than an array holds nothing.
+fn contains(h: &Helper) -> Result<bool, RenderError> {
+    let array = h
+        .param(0)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("contains", 0))?
+        .value();
+    let value = h
+        .param(1)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("contains", 1))?
+        .value();
+    Ok(match array {
+        Value::Array(items) => items.iter().any(|element| matches(element, value)),
+        _ => false,
+    })
+}
+
+pub struct ContainsHelper;
+
+impl HelperDef for ContainsHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        Ok(Value::Bool(contains(h)?).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let found = contains(h)?;
+        if !h.is_block() {
+            out.write(&Value::Bool(found).render())?;
+            return Ok(());
+        }
+        let block = match found {
+            true => h.template(),
+            false => h.inverse(),
+        };
+        match block {
+            Some(block) => block.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! `{{count items "No items" "{n} item" "{n} items"}}` picks the phrase for
+//! a count and fills in the count with the locale's thousands separators.
+
+use super::locale_format::{Conventions, LocaleSource};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use serde_json::Value;
+
+/// The count a value stands for: numbers and numeric strings as they are,
+/// arrays and objects by their length, and null as zero.
+fn count_of(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Array(items) => Some(items.len() as f64),
+        Value::Object(entries) => Some(entries.len() as f64),
+        Value::Null => Some(0.0),
+        Value::Bool(_) => None,
+    }
+}
+
+/// `1234567` as `1,234,567`; a fractional part is kept, after the locale's
+/// decimal mark.
+fn group_thousands(count: f64, separator: &str, conventions: &Conventions) -> String {
+    let formatted = count.abs().to_string();
+    let (whole, fraction) = match formatted.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (formatted.as_str(), None),
+    };
+
+    let mut grouped = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    if count < 0.0 {
+        grouped.insert(0, '-');
+    }
+    if let Some(fraction) = fraction {
+        grouped.push_str(conventions.decimal);
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+pub struct CountHelper {
+    locales: LocaleSource,
+}
+
+impl CountHelper {
+    pub fn new(locales: LocaleSource) -> CountHelper {
+        CountHelper { locales }
+    }
+}
+
+impl HelperDef for CountHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let count = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("count", 0))?
+            .value();
+        let count = count_of(count).ok_or(RenderErrorReason::InvalidParamType(
+            "number, numeric string or collection",
+        ))?;
+
+        let mut forms = Vec::new();
+        for index in 1..h.params().len() {
+            let form = h.params()[index]
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("phrase string"))?;
+            forms.push(form);
+        }
+        // The zero form is optional; without it zero takes the locale's
+        // plural rule like any other count
+        let (zero, one, many) = match forms[..] {
+            [zero, one, many] => (Some(zero), one, many),
+            [one, many] => (None, one, many),
+            _ => {
+                return Err(RenderErrorReason::Other(
+                    "count takes one and many phrases, optionally preceded by a zero phrase"
+                        .to_string(),
+                )
+                .into());
+            }
+        };
+        let conventions = self.locales.conventions(h, ctx);
+        let separator = match h.hash_get("separator") {
+            Some(separator) => separator
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("separator string"))?,
+            None => conventions.group,
+        };
+
+        let phrase = match zero {
+            Some(zero) if count == 0.0 => zero,
+            _ if conventions.is_one(count) => one,
+            _ => many,
+        };
+        let rendered = phrase.replace("{n}", &group_thousands(count, separator, conventions));
+        out.write(&r.get_escape_fn()(&rendered))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, count: Value, locale: &str) -> String {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper(
+            "count",
+            Box::new(CountHelper::new(LocaleSource::new("_", "en"))),
+        );
+        let data = json!({"n": count, "_locale": locale});
+        handlebars.render_template(template, &data).unwrap()
+    }
+
+    #[test]
+    fn phrases_follow_the_locale_plural_rule() {
+        let template = r#"{{count n "{n} one" "{n} other"}}"#;
+        let cases = [
+            (
+                "en",
+                ["0 other", "1 one", "2 other", "5 other", "1.5 other"],
+            ),
+            (
+                "de",
+                ["0 other", "1 one", "2 other", "5 other", "1,5 other"],
+            ),
+            ("fr", ["0 one", "1 one", "2 other", "5 other", "1,5 one"]),
+            ("pt", ["0 one", "1 one", "2 other", "5 other", "1,5 one"]),
+        ];
+        for (locale, expected) in cases {
+            let rendered =
+                [0.0, 1.0, 2.0, 5.0, 1.5].map(|count| render(template, json!(count), locale));
+            assert_eq!(rendered, expected, "{}", locale);
+        }
+    }
+
+    #[test]
+    fn an_explicit_zero_phrase_wins() {
+        let template = r#"{{count n "none" "{n} one" "{n} other"}}"#;
+        assert_eq!(render(template, json!(0), "fr"), "none");
+        assert_eq!(render(template, json!(-1234), "en"), "-1,234 other");
+    }
+}
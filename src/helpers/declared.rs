@@ -0,0 +1,256 @@
+//! Helpers declared with `[[helpers]]` in the config, for formatting that
+//! doesn't warrant Rust code. A `map` helper looks its argument up in a
+//! table; a `template` helper renders a template string with its arguments
+//! as the context. Neither runs code beyond the registered helpers.
+
+use crate::config::DeclaredHelperConfig;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderError, RenderErrorReason, ScopedJson, Template,
+};
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+/// How deeply template helpers may call each other, or themselves.
+const MAX_DEPTH: usize = 8;
+
+/// A template helper's template is compiled once and registered under this
+/// prefix and the helper's name, apart from the files under `templates/`.
+pub const TEMPLATE_PREFIX: &str = "[[helpers]]/";
+
+thread_local! {
+    /// Template helpers being rendered on this thread. A render never moves
+    /// between threads, so this tracks the current nesting.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// `{{statusLabel post.status}}`: the output for the argument, rendered
+/// with `.render()` as the key, or `default`, or the argument itself.
+struct MapHelper {
+    name: String,
+    values: BTreeMap<String, String>,
+    default: Option<String>,
+}
+
+impl HelperDef for MapHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let value = h
+            .param(0)
+            .ok_or_else(|| {
+                RenderErrorReason::Other(format!("{}: expected an argument", self.name))
+            })?
+            .value();
+        let output = match self.values.get(&value.render()) {
+            Some(output) => Value::String(output.clone()),
+            None => match &self.default {
+                Some(default) => Value::String(default.clone()),
+                None => value.clone(),
+            },
+        };
+        Ok(output.into())
+    }
+}
+
+/// `{{badge "New" "info"}}`: the template rendered with the positional
+/// arguments under the names in `params` and the hash arguments under their
+/// own names.
+struct TemplateHelper {
+    name: String,
+    /// Where `register` put the compiled template.
+    template_name: String,
+    params: Vec<String>,
+}
+
+impl TemplateHelper {
+    fn render(&self, h: &Helper, r: &Handlebars) -> Result<String, RenderError> {
+        if h.params().len() > self.params.len() {
+            return Err(RenderErrorReason::Other(format!(
+                "{}: expected at most {} arguments, got {}",
+                self.name,
+                self.params.len(),
+                h.params().len()
+            ))
+            .into());
+        }
+        let mut data = Map::new();
+        // Arguments left out are null, so strict mode doesn't reject them
+        for (index, name) in self.params.iter().enumerate() {
+            let value = h.param(index).map(|param| param.value().clone());
+            data.insert(name.clone(), value.unwrap_or(Value::Null));
+        }
+        for (name, value) in h.hash() {
+            data.insert(name.to_string(), value.value().clone());
+        }
+
+        let depth = DEPTH.get();
+        if depth >= MAX_DEPTH {
+            return Err(RenderErrorReason::Other(format!(
+                "{}: declared helpers nested more than {} levels",
+                self.name, MAX_DEPTH
+            ))
+            .into());
+        }
+        DEPTH.set(depth + 1);
+        let rendered = r.render_with_context(&self.template_name, &Context::wraps(data)?);
+        DEPTH.set(depth);
+        rendered
+    }
+}
+
+impl HelperDef for TemplateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        Ok(Value::String(self.render(h, r)?).into())
+    }
+
+    /// The output is markup, so it is written as rendered; the template
+    /// escapes the arguments itself.
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        out.write(&self.render(h, r)?)?;
+        Ok(())
+    }
+}
+
+/// Registers the declared helpers, and the compiled templates of template
+/// helpers; `validate` has made sure at startup that their names are free
+/// and their templates parse.
+pub fn register(handlebars: &mut Handlebars, helpers: &[DeclaredHelperConfig]) {
+    for helper in helpers {
+        let name = helper.name().to_string();
+        let definition: Box<dyn HelperDef + Send + Sync> = match helper {
+            DeclaredHelperConfig::Map {
+                values, default, ..
+            } => Box::new(MapHelper {
+                name,
+                values: values.clone(),
+                default: default.clone(),
+            }),
+            DeclaredHelperConfig::Template {
+                template, params, ..
+            } => {
+                let Ok(mut compiled) = Template::compile(template) else {
+                    continue;
+                };
+                let template_name = format!("{}{}", TEMPLATE_PREFIX, name);
+                compiled.name = Some(template_name.clone());
+                handlebars.register_template(&template_name, compiled);
+                Box::new(TemplateHelper {
+                    name,
+                    template_name,
+                    params: params.clone(),
+                })
+            }
+        };
+        handlebars.register_helper(helper.name(), definition);
+    }
+}
+
+/// Whether `name` is a helper in `handlebars`, which has no lookup for
+/// them: in strict mode, only a name that is neither a helper nor a value
+/// fails as a missing variable.
+fn is_helper(handlebars: &Handlebars, name: &str) -> bool {
+    match handlebars.render_template(&format!("{{{{{}}}}}", name), &Map::new()) {
+        Ok(_) => true,
+        Err(e) => !matches!(e.reason(), RenderErrorReason::MissingVariable(_)),
+    }
+}
+
+/// Checks that each declared helper has a free, unique name and that
+/// template helpers parse.
+pub fn validate(mut builtins: Handlebars, helpers: &[DeclaredHelperConfig]) -> Result<(), String> {
+    builtins.set_strict_mode(true);
+    let mut names = Vec::new();
+    for helper in helpers {
+        let name = helper.name();
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(format!("'{}' is not a valid helper name", name));
+        }
+        if is_helper(&builtins, name) {
+            return Err(format!("helper '{}' is already built in", name));
+        }
+        if names.contains(&name) {
+            return Err(format!("helper '{}' is declared twice", name));
+        }
+        names.push(name);
+        if let DeclaredHelperConfig::Template { template, .. } = helper {
+            Template::compile(template).map_err(|e| format!("helper '{}': {}", name, e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn template_helper(name: &str, template: &str, params: &[&str]) -> DeclaredHelperConfig {
+        DeclaredHelperConfig::Template {
+            name: name.to_string(),
+            template: template.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn template_helpers_render_their_compiled_template() {
+        let mut handlebars = Handlebars::new();
+        register(
+            &mut handlebars,
+            &[template_helper(
+                "badge",
+                "<span class=\"{{kind}}\">{{label}}</span>",
+                &["label", "kind"],
+            )],
+        );
+        assert!(handlebars.has_template("[[helpers]]/badge"));
+
+        let rendered = handlebars
+            .render_template(
+                "{{badge name \"info\"}} {{badge \"<b>\" kind=\"x\"}}",
+                &json!({"name": "New"}),
+            )
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "<span class=\"info\">New</span> <span class=\"x\">&lt;b&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn template_helpers_stop_recursing() {
+        let mut handlebars = Handlebars::new();
+        register(&mut handlebars, &[template_helper("loop", "{{loop}}", &[])]);
+        let error = handlebars
+            .render_template("{{loop}}", &json!({}))
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("nested more than 8 levels"),
+            "{}",
+            error
+        );
+    }
+}
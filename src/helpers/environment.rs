@@ -0,0 +1,69 @@
+//! `{{#onlyIn "dev"}}...{{/onlyIn}}` renders its block only in the listed
+//! environments, and `{{#exceptIn "prod"}}...{{/exceptIn}}` in all others,
+//! for staging banners and debug widgets. Both take `{{else}}`.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+    Renderable,
+};
+
+pub struct EnvironmentHelper {
+    name: &'static str,
+    /// The active environment, e.g. `dev`.
+    environment: &'static str,
+    /// Whether the block renders when the environment is listed, as for
+    /// `onlyIn`, rather than when it isn't.
+    when_listed: bool,
+}
+
+impl EnvironmentHelper {
+    pub fn only_in(environment: &'static str) -> EnvironmentHelper {
+        EnvironmentHelper {
+            name: "onlyIn",
+            environment,
+            when_listed: true,
+        }
+    }
+
+    pub fn except_in(environment: &'static str) -> EnvironmentHelper {
+        EnvironmentHelper {
+            name: "exceptIn",
+            environment,
+            when_listed: false,
+        }
+    }
+}
+
+impl HelperDef for EnvironmentHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        if h.params().is_empty() {
+            return Err(RenderErrorReason::ParamNotFoundForIndex(self.name, 0).into());
+        }
+        let mut listed = false;
+        for param in h.params() {
+            let name = param
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType(
+                    "environment name string",
+                ))?;
+            listed |= name == self.environment;
+        }
+
+        let block = match listed == self.when_listed {
+            true => h.template(),
+            false => h.inverse(),
+        };
+        match block {
+            Some(block) => block.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
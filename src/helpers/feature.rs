@@ -0,0 +1,54 @@
+//! `{{#feature "new-nav"}}...{{else}}...{{/feature}}` renders its block when
+//! the named flag in the reserved `flags` object is on. Unknown flags are off.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+    Renderable,
+};
+use serde_json::Value;
+
+pub struct FeatureHelper {
+    /// Context key holding the flags, e.g. `_flags`.
+    flags_key: String,
+}
+
+impl FeatureHelper {
+    pub fn new(reserved_prefix: &str) -> FeatureHelper {
+        FeatureHelper {
+            flags_key: format!("{}flags", reserved_prefix),
+        }
+    }
+}
+
+impl HelperDef for FeatureHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("feature", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("flag name string"))?;
+
+        let enabled = ctx
+            .data()
+            .get(&self.flags_key)
+            .and_then(|flags| flags.get(name))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let block = match enabled {
+            true => h.template(),
+            false => h.inverse(),
+        };
+        match block {
+            Some(block) => block.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! `{{fieldError _errors "email"}}` renders a field's validation message
+//! from an errors object, such as the one form pages get as `_errors`, and
+//! `{{#ifFieldError _errors "email"}}...{{else}}...{{/ifFieldError}}`
+//! renders its block when the field has one. A missing errors object, as on
+//! the first render of a form, means every field is valid.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason, Renderable,
+};
+use serde_json::Value;
+
+/// The message for the field named by the helper's second parameter, if it
+/// has a non-empty one.
+fn message<'a>(
+    h: &'a Helper,
+    helper_name: &'static str,
+) -> Result<Option<&'a Value>, RenderErrorReason> {
+    let errors = h
+        .param(0)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex(helper_name, 0))?
+        .value();
+    let field = h
+        .param(1)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex(helper_name, 1))?
+        .value()
+        .as_str()
+        .ok_or(RenderErrorReason::InvalidParamType("field name string"))?;
+
+    Ok(errors.get(field).filter(|message| match message {
+        Value::Null => false,
+        Value::String(message) => !message.is_empty(),
+        _ => true,
+    }))
+}
+
+pub struct FieldErrorHelper;
+
+impl HelperDef for FieldErrorHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        if let Some(message) = message(h, "fieldError")? {
+            out.write(&r.get_escape_fn()(&message.render()))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct IfFieldErrorHelper;
+
+impl HelperDef for IfFieldErrorHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let block = match message(h, "ifFieldError")? {
+            Some(_) => h.template(),
+            None => h.inverse(),
+        };
+        match block {
+            Some(block) => block.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
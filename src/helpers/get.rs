@@ -0,0 +1,51 @@
+//! `{{get object "a.b.0" default="-"}}` reads a nested value, falling back to
+//! `default` instead of failing, even in strict mode.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::Value;
+
+/// Looks up a dotted path such as `meta.year` or `tags.0`, where numeric
+/// segments index into arrays. Null counts as missing.
+pub fn lookup<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(item, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+        .filter(|value| !value.is_null())
+}
+
+pub struct GetHelper;
+
+impl HelperDef for GetHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let object = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("get", 0))?
+            .value();
+        let path = h
+            .param(1)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("get", 1))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("path string"))?;
+
+        let value = match lookup(object, path) {
+            Some(value) => value.clone(),
+            None => h
+                .hash_get("default")
+                .map(|default| default.value().clone())
+                .unwrap_or(Value::Null),
+        };
+        Ok(value.into())
+    }
+}
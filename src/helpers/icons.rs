@@ -0,0 +1,170 @@
+//! `{{icon "name" class="w-4"}}` renders an icon from an SVG sprite or a
+//! directory of SVG files, see `IconsConfig`.
+
+use crate::config::IconsConfig;
+use crate::static_files::STATIC_DIR;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason, html_escape,
+};
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+enum Source {
+    /// `<use href>` references into a sprite. `ids` is `None` when the sprite
+    /// couldn't be read, in which case every name is accepted.
+    Sprite {
+        url: String,
+        ids: Option<HashSet<String>>,
+    },
+    /// Inline `<name>.svg` files from this directory.
+    Directory(PathBuf),
+}
+
+pub struct IconHelper {
+    source: Source,
+    prefix: String,
+    /// Render a visible placeholder for unknown icons instead of nothing.
+    placeholder: bool,
+}
+
+/// The ids of the `<symbol>` elements in a sprite file.
+fn symbol_ids(path: &Path) -> Option<HashSet<String>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read icon sprite {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let ids = RefCell::new(HashSet::new());
+    rewrite_str(
+        &content,
+        RewriteStrSettings::new().append_element_content_handler(element!("symbol[id]", |el| {
+            if let Some(id) = el.get_attribute("id") {
+                ids.borrow_mut().insert(id);
+            }
+            Ok(())
+        })),
+    )
+    .ok()?;
+
+    Some(ids.into_inner())
+}
+
+/// Sprites under the static directory are referenced by their `/static` URL;
+/// anything else is assumed to be inlined in the page.
+fn sprite_url(path: &Path, static_dir: &Path) -> String {
+    match path.strip_prefix(static_dir) {
+        Ok(relative) => format!("/{}/{}", STATIC_DIR, relative.to_string_lossy()),
+        Err(_) => String::new(),
+    }
+}
+
+/// Icon names become file and id names, so only simple names are allowed.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl IconHelper {
+    pub fn new(config: &IconsConfig, static_dir: &Path, placeholder: bool) -> IconHelper {
+        let source = match (&config.dir, &config.sprite) {
+            (Some(dir), _) => Source::Directory(dir.clone()),
+            (None, Some(sprite)) => Source::Sprite {
+                url: sprite_url(sprite, static_dir),
+                ids: symbol_ids(sprite),
+            },
+            (None, None) => Source::Sprite {
+                url: String::new(),
+                ids: None,
+            },
+        };
+
+        IconHelper {
+            source,
+            prefix: config.prefix.clone().unwrap_or_else(|| "icon-".to_string()),
+            placeholder,
+        }
+    }
+
+    /// The icon's markup without attributes, or `None` if it doesn't exist.
+    fn markup(&self, name: &str) -> Option<(String, String)> {
+        if !is_valid_name(name) {
+            return None;
+        }
+
+        match &self.source {
+            Source::Sprite { url, ids } => {
+                let id = format!("{}{}", self.prefix, name);
+                if ids.as_ref().is_some_and(|ids| !ids.contains(&id)) {
+                    return None;
+                }
+                Some((
+                    "<svg".to_string(),
+                    format!(
+                        "><use href=\"{}#{}\"></use></svg>",
+                        html_escape(url),
+                        html_escape(&id)
+                    ),
+                ))
+            }
+            Source::Directory(dir) => {
+                let svg = std::fs::read_to_string(dir.join(format!("{}.svg", name))).ok()?;
+                // Anything before the root element, such as an XML declaration, is dropped
+                let start = svg.find("<svg")?;
+                Some(("<svg".to_string(), svg[start + 4..].trim_end().to_string()))
+            }
+        }
+    }
+}
+
+impl HelperDef for IconHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("icon", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("icon name string"))?;
+
+        let Some((open, rest)) = self.markup(name) else {
+            eprintln!("Warning: unknown icon '{}'", name);
+            if self.placeholder {
+                out.write(&format!(
+                    "<span class=\"icon-missing\" title=\"Unknown icon: {0}\">[{0}]</span>",
+                    html_escape(name)
+                ))?;
+            }
+            return Ok(());
+        };
+
+        // Hash arguments become attributes on the <svg> element
+        let mut attributes = String::new();
+        for (key, value) in h.hash() {
+            attributes.push_str(&format!(
+                " {}=\"{}\"",
+                html_escape(key),
+                html_escape(&value.value().render())
+            ));
+        }
+        if !h.hash().contains_key("aria-label") && !h.hash().contains_key("role") {
+            attributes.push_str(" aria-hidden=\"true\"");
+        }
+
+        out.write(&format!("{}{}{}", open, attributes, rest))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! `{{#ifPartial "sidebar"}}{{> sidebar}}{{else}}...{{/ifPartial}}` renders
+//! its block only when the partial exists, so shared templates can include
+//! optional partials.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+    Renderable,
+};
+
+pub struct IfPartialHelper;
+
+impl HelperDef for IfPartialHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("ifPartial", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("partial name string"))?;
+
+        // Inline partials count as well as template files
+        let exists = rc.get_partial(name).is_some() || r.has_template(name);
+        let block = match exists {
+            true => h.template(),
+            false => h.inverse(),
+        };
+        match block {
+            Some(block) => block.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
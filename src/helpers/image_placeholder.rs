@@ -0,0 +1,103 @@
+//! `{{imagePlaceholder "photos/team.jpg"}}` renders `background-color:#7d6b5a`,
+//! the image's average color, for an inline style shown while it loads.
+
+use crate::cache_stats::{CacheStats, Counters};
+use crate::image_color::{self, Rgb};
+use crate::static_files::{self, STATIC_DIR};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// An image's color, or why it couldn't be read, as of its mtime.
+type CachedColor = (PathBuf, SystemTime, Result<Rgb, String>);
+
+/// Colors by path, shared across requests and builds since every request
+/// builds a new registry. An entry is recomputed only when the file's mtime
+/// changes; failures are kept too, so each is warned about once.
+static CACHE: Mutex<Vec<CachedColor>> = Mutex::new(Vec::new());
+
+static COUNTERS: Counters = Counters::new();
+
+pub fn cache_stats() -> CacheStats {
+    let cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    COUNTERS.stats("image_placeholder", cache.len(), None)
+}
+
+fn color_cached(path: &Path) -> std::io::Result<Result<Rgb, String>> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((_, _, color)) = cache
+        .iter()
+        .find(|(cached, time, _)| cached == path && *time == modified)
+    {
+        COUNTERS.hit();
+        return Ok(color.clone());
+    }
+
+    COUNTERS.miss();
+    let color = image_color::average_color(path);
+    COUNTERS.reloaded();
+    if let Err(e) = &color {
+        eprintln!(
+            "Warning: imagePlaceholder can't read the color of {}: {}",
+            path.display(),
+            e
+        );
+    }
+    cache.retain(|(cached, _, _)| cached != path);
+    cache.push((path.to_path_buf(), modified, color.clone()));
+    Ok(color)
+}
+
+pub struct ImagePlaceholderHelper {
+    static_dir: PathBuf,
+    /// Used for images that are missing or can't be read, e.g. `#e5e5e5`.
+    fallback: String,
+}
+
+impl ImagePlaceholderHelper {
+    pub fn new(static_dir: &Path, fallback: &str) -> ImagePlaceholderHelper {
+        ImagePlaceholderHelper {
+            static_dir: static_dir.to_path_buf(),
+            fallback: fallback.to_string(),
+        }
+    }
+}
+
+impl HelperDef for ImagePlaceholderHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(
+                "imagePlaceholder",
+                0,
+            ))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("image path string"))?
+            .trim_start_matches('/');
+
+        let color =
+            match static_files::resolve(&self.static_dir, name).map(|path| color_cached(&path)) {
+                Some(Ok(Ok(color))) => image_color::hex(color),
+                Some(Ok(Err(_))) => self.fallback.clone(),
+                Some(Err(_)) | None => {
+                    eprintln!("Warning: image {}/{} not found", STATIC_DIR, name);
+                    self.fallback.clone()
+                }
+            };
+        Ok(Value::String(format!("background-color:{}", color)).into())
+    }
+}
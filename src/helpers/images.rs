@@ -0,0 +1,107 @@
+//! `{{image "photos/team.jpg" alt="The team"}}` renders an `<img>` with a
+//! `srcset` of the width variants configured in `ImagesConfig`.
+
+use crate::config::ImagesConfig;
+use crate::static_files::{self, STATIC_DIR};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason, html_escape,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub struct ImageHelper {
+    config: ImagesConfig,
+    static_dir: PathBuf,
+}
+
+impl ImageHelper {
+    pub fn new(config: &ImagesConfig, static_dir: &Path) -> ImageHelper {
+        ImageHelper {
+            config: config.clone(),
+            static_dir: static_dir.to_path_buf(),
+        }
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        static_files::resolve(&self.static_dir, name).is_some_and(|path| path.is_file())
+    }
+}
+
+/// `photos/team.jpg` at 480 pixels is `photos/team-480w.jpg`.
+fn variant_name(name: &str, width: u32) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.ends_with('/') => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    format!("{}-{}w{}", stem, width, extension)
+}
+
+fn url(name: &str) -> String {
+    format!("/{}/{}", STATIC_DIR, name)
+}
+
+impl HelperDef for ImageHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("image", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("image path string"))?
+            .trim_start_matches('/');
+
+        if !self.exists(name) {
+            eprintln!("Warning: image {}/{} not found", STATIC_DIR, name);
+        }
+
+        let mut srcset = Vec::new();
+        let mut missing = Vec::new();
+        for &width in &self.config.widths {
+            let variant = variant_name(name, width);
+            match self.exists(&variant) {
+                true => srcset.push(format!("{} {}w", url(&variant), width)),
+                false => missing.push(variant),
+            }
+        }
+        if !missing.is_empty() {
+            eprintln!(
+                "Warning: image variants missing for {}: {}",
+                name,
+                missing.join(", ")
+            );
+        }
+
+        // Configured attributes first, so the call can override them
+        let mut attributes: BTreeMap<String, String> = self.config.attributes.clone();
+        if let Some(sizes) = &self.config.sizes {
+            attributes.insert("sizes".to_string(), sizes.clone());
+        }
+        for (key, value) in h.hash() {
+            attributes.insert(key.to_string(), value.value().render());
+        }
+        // `sizes` only means something alongside a `srcset`
+        if srcset.is_empty() {
+            attributes.remove("sizes");
+        }
+
+        let mut tag = format!("<img src=\"{}\"", html_escape(&url(name)));
+        if !srcset.is_empty() {
+            tag.push_str(&format!(" srcset=\"{}\"", html_escape(&srcset.join(", "))));
+        }
+        for (key, value) in &attributes {
+            tag.push_str(&format!(" {}=\"{}\"", html_escape(key), html_escape(value)));
+        }
+        tag.push('>');
+
+        out.write(&tag)?;
+        Ok(())
+    }
+}
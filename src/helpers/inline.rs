@@ -0,0 +1,113 @@
+//! `{{inlineCss "css/critical.css"}}` and `{{inlineJs "js/boot.js"}}` embed a
+//! file from the static directory in a `<style>` or `<script>` tag.
+
+use crate::cache_stats::{CacheStats, Counters};
+use crate::static_files;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// File contents by path, shared across requests since every request builds
+/// a new registry. An entry is re-read only when the file's mtime changes.
+static CACHE: Mutex<Vec<(PathBuf, SystemTime, Arc<str>)>> = Mutex::new(Vec::new());
+
+static COUNTERS: Counters = Counters::new();
+
+pub fn cache_stats() -> CacheStats {
+    let cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let bytes = cache.iter().map(|(_, _, content)| content.len()).sum();
+    COUNTERS.stats("inline", cache.len(), Some(bytes))
+}
+
+fn read_cached(path: &Path) -> std::io::Result<Arc<str>> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some((_, _, content)) = cache
+        .iter()
+        .find(|(cached, time, _)| cached == path && *time == modified)
+    {
+        COUNTERS.hit();
+        return Ok(content.clone());
+    }
+
+    COUNTERS.miss();
+    let content: Arc<str> = std::fs::read_to_string(path)?.into();
+    COUNTERS.reloaded();
+    cache.retain(|(cached, _, _)| cached != path);
+    cache.push((path.to_path_buf(), modified, content.clone()));
+    Ok(content)
+}
+
+pub struct InlineHelper {
+    name: &'static str,
+    tag: &'static str,
+    static_dir: PathBuf,
+}
+
+impl InlineHelper {
+    pub fn css(static_dir: &Path) -> InlineHelper {
+        InlineHelper {
+            name: "inlineCss",
+            tag: "style",
+            static_dir: static_dir.to_path_buf(),
+        }
+    }
+
+    pub fn js(static_dir: &Path) -> InlineHelper {
+        InlineHelper {
+            name: "inlineJs",
+            tag: "script",
+            static_dir: static_dir.to_path_buf(),
+        }
+    }
+}
+
+impl HelperDef for InlineHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(self.name, 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("path string"))?;
+
+        // Only paths relative to the static directory, without `..`
+        let path = match name.starts_with('/') {
+            true => None,
+            false => static_files::resolve(&self.static_dir, name),
+        };
+        let Some(path) = path else {
+            eprintln!(
+                "Warning: {} path '{}' must be relative to {}/",
+                self.name,
+                name,
+                static_files::STATIC_DIR
+            );
+            return Ok(());
+        };
+
+        match read_cached(&path) {
+            Ok(content) => {
+                out.write(&format!("<{}>{}</{}>", self.tag, content, self.tag))?;
+            }
+            Err(e) => eprintln!(
+                "Warning: {} can't read {}: {}",
+                self.name,
+                path.display(),
+                e
+            ),
+        }
+        Ok(())
+    }
+}
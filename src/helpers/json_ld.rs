@@ -0,0 +1,270 @@
+//! `{{jsonLd "Article"}}` emits a schema.org JSON-LD block for the page
+//! being rendered, from its front matter and an optional data object:
+//! `{{jsonLd "Product" product}}`, `{{jsonLd "BreadcrumbList" crumbs}}`.
+//! URLs and images are made absolute with `base_url`. A block missing a
+//! required property is still emitted, with a warning.
+
+use crate::config::Config;
+use crate::pages::Page;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason,
+};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+/// Data keys read under another name, so they aren't copied as they are.
+const ALIASES: [&str; 5] = ["title", "date", "updated", "price", "currency"];
+
+pub struct JsonLdHelper {
+    /// Without a trailing slash.
+    base_url: Option<String>,
+    site_name: Option<String>,
+    /// Context key holding the current path, e.g. `_path`.
+    path_key: String,
+    /// Front matter by template name, e.g. `pages/about`.
+    front_matter: HashMap<String, Map<String, Value>>,
+}
+
+impl JsonLdHelper {
+    pub fn new(config: &Config, pages: &[Page]) -> JsonLdHelper {
+        JsonLdHelper {
+            base_url: config
+                .site
+                .base_url
+                .as_deref()
+                .map(|base_url| base_url.trim_end_matches('/').to_string()),
+            site_name: config.title.site_name.clone(),
+            path_key: format!("{}path", config.reserved_prefix()),
+            front_matter: pages
+                .iter()
+                .map(|page| (page.template_name(), page.front_matter.clone()))
+                .collect(),
+        }
+    }
+
+    /// `/img/a.png` or `img/a.png` as `https://example.com/img/a.png`. URLs
+    /// with a scheme are kept, as is everything without `base_url`.
+    fn absolute(&self, url: &str) -> String {
+        match &self.base_url {
+            Some(base_url) if !url.contains("://") && !url.starts_with("//") => {
+                format!("{}/{}", base_url, url.trim_start_matches('/'))
+            }
+            _ => url.to_string(),
+        }
+    }
+
+    fn absolute_value(&self, value: &Value) -> Value {
+        match value {
+            Value::String(url) => Value::String(self.absolute(url)),
+            Value::Array(urls) => urls.iter().map(|url| self.absolute_value(url)).collect(),
+            other => other.clone(),
+        }
+    }
+
+    fn article(&self, fields: &Fields, object: &mut Map<String, Value>) {
+        fields.set(object, "headline", &["headline", "title"]);
+        fields.set(object, "description", &["description"]);
+        fields.set(object, "image", &["image"]);
+        fields.set(object, "datePublished", &["datePublished", "date"]);
+        fields.set(object, "dateModified", &["dateModified", "updated"]);
+        if let Some(author) = fields.get(&["author"]) {
+            object.insert("author".to_string(), named(author, "Person"));
+        }
+        if let Some(site_name) = &self.site_name
+            && !object.contains_key("publisher")
+        {
+            object.insert(
+                "publisher".to_string(),
+                json!({ "@type": "Organization", "name": site_name }),
+            );
+        }
+    }
+
+    fn product(&self, fields: &Fields, object: &mut Map<String, Value>) {
+        fields.set(object, "name", &["name", "title"]);
+        fields.set(object, "description", &["description"]);
+        fields.set(object, "image", &["image"]);
+        fields.set(object, "sku", &["sku"]);
+        if let Some(brand) = fields.get(&["brand"]) {
+            object.insert("brand".to_string(), named(brand, "Brand"));
+        }
+        if !object.contains_key("offers")
+            && let Some(price) = fields.get(&["price"])
+        {
+            let mut offer = json!({ "@type": "Offer", "price": price });
+            if let Some(currency) = fields.get(&["priceCurrency", "currency"]) {
+                offer["priceCurrency"] = currency.clone();
+            }
+            object.insert("offers".to_string(), offer);
+        }
+    }
+
+    /// `[{name, url}, ...]` as numbered `ListItem`s.
+    fn breadcrumbs(&self, data: Option<&Value>, object: &mut Map<String, Value>) {
+        let items = match data {
+            Some(Value::Array(items)) => items.as_slice(),
+            Some(Value::Object(data)) => match data.get("items") {
+                Some(Value::Array(items)) => items.as_slice(),
+                _ => &[],
+            },
+            _ => &[],
+        };
+        let elements: Vec<Value> = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let mut element = json!({ "@type": "ListItem", "position": index + 1 });
+                if let Some(name) = item.get("name") {
+                    element["name"] = name.clone();
+                }
+                if let Some(url) = item.get("url").or_else(|| item.get("item")) {
+                    element["item"] = Value::String(self.absolute(&url.render()));
+                }
+                element
+            })
+            .collect();
+        if !elements.is_empty() {
+            object.insert("itemListElement".to_string(), Value::Array(elements));
+        }
+    }
+}
+
+/// Looks properties up in the data object, then in the front matter.
+struct Fields<'a> {
+    data: Option<&'a Map<String, Value>>,
+    front_matter: Option<&'a Map<String, Value>>,
+}
+
+impl Fields<'_> {
+    fn get(&self, keys: &[&str]) -> Option<&Value> {
+        [self.data, self.front_matter]
+            .into_iter()
+            .flatten()
+            .find_map(|source| keys.iter().find_map(|key| source.get(*key)))
+            .filter(|value| !value.is_null())
+    }
+
+    fn set(&self, object: &mut Map<String, Value>, property: &str, keys: &[&str]) {
+        if let Some(value) = self.get(keys) {
+            object.insert(property.to_string(), value.clone());
+        }
+    }
+}
+
+/// A plain name as `{"@type": kind, "name": name}`; objects are kept.
+fn named(value: &Value, kind: &str) -> Value {
+    match value {
+        Value::String(name) => json!({ "@type": kind, "name": name }),
+        Value::Array(values) => values.iter().map(|value| named(value, kind)).collect(),
+        other => other.clone(),
+    }
+}
+
+/// The properties a type needs to be eligible for rich results.
+fn missing_required(kind: &str, object: &Map<String, Value>) -> Vec<&'static str> {
+    let required: &[&'static str] = match kind {
+        "Article" | "BlogPosting" | "NewsArticle" => &["headline"],
+        "Product" => &["name"],
+        "BreadcrumbList" => &["itemListElement"],
+        _ => &[],
+    };
+    required
+        .iter()
+        .copied()
+        .filter(|property| !object.contains_key(*property))
+        .collect()
+}
+
+impl JsonLdHelper {
+    /// The `<script>` block of type `kind` for the page `template_name` at
+    /// `path`, from its front matter and the `data` object.
+    pub(super) fn script(
+        &self,
+        kind: &str,
+        data: Option<&Value>,
+        template_name: &str,
+        path: Option<&str>,
+    ) -> Result<String, RenderErrorReason> {
+        let mut object = Map::new();
+        object.insert("@context".to_string(), json!("https://schema.org"));
+        object.insert("@type".to_string(), json!(kind));
+        // Other properties of the data object are passed through
+        if let Some(Value::Object(data)) = data
+            && kind != "BreadcrumbList"
+        {
+            for (key, value) in data {
+                if !ALIASES.contains(&key.as_str()) && !value.is_null() {
+                    object.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        let fields = Fields {
+            data: data.and_then(Value::as_object),
+            front_matter: self.front_matter.get(template_name),
+        };
+        match kind {
+            "Article" | "BlogPosting" | "NewsArticle" => self.article(&fields, &mut object),
+            "Product" => self.product(&fields, &mut object),
+            "BreadcrumbList" => self.breadcrumbs(data, &mut object),
+            _ => {}
+        }
+
+        // The page itself, unless the data names another URL
+        if kind != "BreadcrumbList"
+            && !object.contains_key("url")
+            && let Some(path) = path
+        {
+            object.insert("url".to_string(), json!(path));
+        }
+        for property in ["url", "image", "logo"] {
+            if let Some(value) = object.get(property) {
+                let absolute = self.absolute_value(value);
+                object.insert(property.to_string(), absolute);
+            }
+        }
+
+        for property in missing_required(kind, &object) {
+            eprintln!(
+                "Warning: jsonLd {} in '{}' is missing the required '{}'",
+                kind, template_name, property
+            );
+        }
+
+        // `</script>` inside a string would end the block early
+        let json = serde_json::to_string(&Value::Object(object))
+            .map_err(|e| RenderErrorReason::Other(e.to_string()))?
+            .replace('<', "\\u003c");
+        Ok(format!(
+            "<script type=\"application/ld+json\">{}</script>",
+            json
+        ))
+    }
+}
+
+impl HelperDef for JsonLdHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let kind = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("jsonLd", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType(
+                "schema.org type string",
+            ))?;
+        let data = h.param(1).map(|param| param.value());
+        let template_name = rc.get_root_template_name().cloned().unwrap_or_default();
+        let path = ctx.data().get(&self.path_key).and_then(Value::as_str);
+
+        out.write(&self.script(kind, data, &template_name, path)?)?;
+        Ok(())
+    }
+}
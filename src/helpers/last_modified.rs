@@ -0,0 +1,79 @@
+//! `{{lastModified "posts" "%B %-d, %Y"}}` renders when the file behind a
+//! top-level context key last changed, from the reserved `modified` object,
+//! in the configured timezone and the locale's month names. A key loaded
+//! from several files reports the most recent. Keys without a file, such as
+//! `--data` values, render nothing.
+
+use super::locale_format::{LocaleSource, format_date};
+use crate::helpers::parse_date;
+use chrono_tz::Tz;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use serde_json::Value;
+
+/// Used when no format is given, e.g. `2024-03-05`.
+const DEFAULT_FORMAT: &str = "%Y-%m-%d";
+
+pub struct LastModifiedHelper {
+    /// Context key holding the timestamps, e.g. `_modified`.
+    modified_key: String,
+    timezone: Tz,
+    locales: LocaleSource,
+}
+
+impl LastModifiedHelper {
+    pub fn new(reserved_prefix: &str, timezone: Tz, locales: LocaleSource) -> LastModifiedHelper {
+        LastModifiedHelper {
+            modified_key: format!("{}modified", reserved_prefix),
+            timezone,
+            locales,
+        }
+    }
+}
+
+impl HelperDef for LastModifiedHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let key = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("lastModified", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("context key string"))?;
+        let format = match h.param(1) {
+            Some(param) => param
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType(
+                    "strftime format string",
+                ))?,
+            None => DEFAULT_FORMAT,
+        };
+
+        let Some(modified) = ctx
+            .data()
+            .get(&self.modified_key)
+            .and_then(|modified| modified.get(key))
+            .and_then(Value::as_str)
+            .and_then(parse_date)
+        else {
+            return Ok(());
+        };
+
+        let formatted = format_date(
+            &modified.with_timezone(&self.timezone),
+            format,
+            self.locales.conventions(h, ctx),
+        )?;
+
+        out.write(&r.get_escape_fn()(&formatted))?;
+        Ok(())
+    }
+}
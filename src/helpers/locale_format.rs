@@ -0,0 +1,716 @@
+//! Number, currency and date conventions by locale, used by the formatting
+//! helpers. Each helper follows the active `_locale`, or a `locale="de"`
+//! argument, falling back from `de-AT` to `de`, then to the default locale,
+//! then to English. `{{number value}}` and `{{currency value "EUR"}}` are
+//! defined here too.
+
+use super::units::as_number;
+use chrono::{DateTime, Datelike, TimeZone};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason,
+};
+use serde_json::Value;
+use std::fmt::Write;
+
+/// How a locale writes numbers, currency amounts, dates and relative times.
+pub struct Conventions {
+    /// Language subtag, e.g. `de`.
+    language: &'static str,
+    pub decimal: &'static str,
+    pub group: &'static str,
+    /// `{s}` is the currency symbol, `{n}` the amount.
+    currency: &'static str,
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+    /// Monday first.
+    weekdays: [&'static str; 7],
+    short_weekdays: [&'static str; 7],
+    pub just_now: &'static str,
+    /// `{}` is the amount and unit.
+    pub past: &'static str,
+    pub future: &'static str,
+    /// Singular and plural of minute, hour, day, month and year.
+    pub units: [[&'static str; 2]; 5],
+    /// Whether a count takes the singular, CLDR's `one` category.
+    one: fn(f64) -> bool,
+}
+
+impl Conventions {
+    /// Whether `count` takes the singular form.
+    pub fn is_one(&self, count: f64) -> bool {
+        (self.one)(count)
+    }
+}
+
+/// `one` for exactly 1, as in English and German.
+fn exactly_one(count: f64) -> bool {
+    count == 1.0
+}
+
+/// `one` for anything below 2, including 0 and 1.5, as in French and
+/// Portuguese.
+fn below_two(count: f64) -> bool {
+    (0.0..2.0).contains(&count.abs())
+}
+
+const ENGLISH: Conventions = Conventions {
+    language: "en",
+    decimal: ".",
+    group: ",",
+    currency: "{s}{n}",
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    short_months: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    weekdays: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    short_weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    just_now: "just now",
+    past: "{} ago",
+    future: "in {}",
+    units: [
+        ["minute", "minutes"],
+        ["hour", "hours"],
+        ["day", "days"],
+        ["month", "months"],
+        ["year", "years"],
+    ],
+    one: exactly_one,
+};
+
+/// The built-in locales, by language. Spaces inside amounts are
+/// non-breaking.
+const LOCALES: [Conventions; 7] = [
+    ENGLISH,
+    Conventions {
+        language: "de",
+        decimal: ",",
+        group: ".",
+        currency: "{n}\u{a0}{s}",
+        months: [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        short_months: [
+            "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+        ],
+        weekdays: [
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+            "Sonntag",
+        ],
+        short_weekdays: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+        just_now: "gerade eben",
+        past: "vor {}",
+        future: "in {}",
+        units: [
+            ["Minute", "Minuten"],
+            ["Stunde", "Stunden"],
+            ["Tag", "Tagen"],
+            ["Monat", "Monaten"],
+            ["Jahr", "Jahren"],
+        ],
+        one: exactly_one,
+    },
+    Conventions {
+        language: "fr",
+        decimal: ",",
+        group: "\u{202f}",
+        currency: "{n}\u{a0}{s}",
+        months: [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        short_months: [
+            "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+            "nov.", "déc.",
+        ],
+        weekdays: [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+        short_weekdays: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+        just_now: "à l'instant",
+        past: "il y a {}",
+        future: "dans {}",
+        units: [
+            ["minute", "minutes"],
+            ["heure", "heures"],
+            ["jour", "jours"],
+            ["mois", "mois"],
+            ["an", "ans"],
+        ],
+        one: below_two,
+    },
+    Conventions {
+        language: "es",
+        decimal: ",",
+        group: ".",
+        currency: "{n}\u{a0}{s}",
+        months: [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        short_months: [
+            "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+        ],
+        weekdays: [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+        short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+        just_now: "ahora mismo",
+        past: "hace {}",
+        future: "dentro de {}",
+        units: [
+            ["minuto", "minutos"],
+            ["hora", "horas"],
+            ["día", "días"],
+            ["mes", "meses"],
+            ["año", "años"],
+        ],
+        one: exactly_one,
+    },
+    Conventions {
+        language: "it",
+        decimal: ",",
+        group: ".",
+        currency: "{n}\u{a0}{s}",
+        months: [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        short_months: [
+            "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+        ],
+        weekdays: [
+            "lunedì",
+            "martedì",
+            "mercoledì",
+            "giovedì",
+            "venerdì",
+            "sabato",
+            "domenica",
+        ],
+        short_weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+        just_now: "proprio ora",
+        past: "{} fa",
+        future: "tra {}",
+        units: [
+            ["minuto", "minuti"],
+            ["ora", "ore"],
+            ["giorno", "giorni"],
+            ["mese", "mesi"],
+            ["anno", "anni"],
+        ],
+        one: exactly_one,
+    },
+    Conventions {
+        language: "nl",
+        decimal: ",",
+        group: ".",
+        currency: "{s}\u{a0}{n}",
+        months: [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        short_months: [
+            "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+        ],
+        weekdays: [
+            "maandag",
+            "dinsdag",
+            "woensdag",
+            "donderdag",
+            "vrijdag",
+            "zaterdag",
+            "zondag",
+        ],
+        short_weekdays: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+        just_now: "zojuist",
+        past: "{} geleden",
+        future: "over {}",
+        units: [
+            ["minuut", "minuten"],
+            ["uur", "uur"],
+            ["dag", "dagen"],
+            ["maand", "maanden"],
+            ["jaar", "jaar"],
+        ],
+        one: exactly_one,
+    },
+    Conventions {
+        language: "pt",
+        decimal: ",",
+        group: ".",
+        currency: "{s}\u{a0}{n}",
+        months: [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+        short_months: [
+            "jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez",
+        ],
+        weekdays: [
+            "segunda-feira",
+            "terça-feira",
+            "quarta-feira",
+            "quinta-feira",
+            "sexta-feira",
+            "sábado",
+            "domingo",
+        ],
+        short_weekdays: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+        just_now: "agora mesmo",
+        past: "há {}",
+        future: "em {}",
+        units: [
+            ["minuto", "minutos"],
+            ["hora", "horas"],
+            ["dia", "dias"],
+            ["mês", "meses"],
+            ["ano", "anos"],
+        ],
+        one: below_two,
+    },
+];
+
+/// Symbols for common ISO 4217 codes; other codes are shown as they are.
+const CURRENCY_SYMBOLS: [(&str, &str); 7] = [
+    ("EUR", "€"),
+    ("USD", "$"),
+    ("GBP", "£"),
+    ("JPY", "¥"),
+    ("INR", "₹"),
+    ("BRL", "R$"),
+    ("CHF", "CHF"),
+];
+
+/// The built-in conventions for a tag such as `de-AT`, by its language.
+fn lookup(tag: &str) -> Option<&'static Conventions> {
+    let language = tag.split(['-', '_']).next().unwrap_or(tag);
+    LOCALES
+        .iter()
+        .find(|locale| locale.language.eq_ignore_ascii_case(language))
+}
+
+/// Finds the conventions for a helper call: its `locale=` argument, then
+/// the active locale in the context, then the default locale, then English.
+pub struct LocaleSource {
+    /// Context key holding the active locale, e.g. `_locale`.
+    locale_key: String,
+    default_locale: String,
+}
+
+impl LocaleSource {
+    pub fn new(reserved_prefix: &str, default_locale: &str) -> LocaleSource {
+        LocaleSource {
+            locale_key: format!("{}locale", reserved_prefix),
+            default_locale: default_locale.to_string(),
+        }
+    }
+
+    pub fn conventions(&self, h: &Helper, ctx: &Context) -> &'static Conventions {
+        let explicit = h
+            .hash_get("locale")
+            .and_then(|locale| locale.value().as_str());
+        let active = ctx.data().get(&self.locale_key).and_then(Value::as_str);
+        [explicit, active, Some(self.default_locale.as_str())]
+            .into_iter()
+            .flatten()
+            .find_map(lookup)
+            .unwrap_or(&LOCALES[0])
+    }
+}
+
+/// Groups the whole part of a formatted non-negative number, e.g.
+/// `1234567.5` → `1,234,567.5` in English.
+fn group_digits(formatted: &str, conventions: &Conventions) -> String {
+    let (whole, fraction) = match formatted.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (formatted, None),
+    };
+
+    let mut grouped = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            grouped.push_str(conventions.group);
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push_str(conventions.decimal);
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+/// `number` with grouped thousands and the locale's decimal mark, rounded
+/// to `decimals` places when given and otherwise as precise as it is.
+pub fn format_number(number: f64, decimals: Option<usize>, conventions: &Conventions) -> String {
+    let formatted = match decimals {
+        Some(decimals) => format!("{:.*}", decimals, number.abs()),
+        None => number.abs().to_string(),
+    };
+    let grouped = group_digits(&formatted, conventions);
+    // Rounding can leave nothing but zeros, which takes no sign
+    match number < 0.0 && formatted.bytes().any(|byte| matches!(byte, b'1'..=b'9')) {
+        true => format!("-{}", grouped),
+        false => grouped,
+    }
+}
+
+/// Replaces the `.` in an already formatted number with the decimal mark.
+pub fn localize_decimal(formatted: &str, conventions: &Conventions) -> String {
+    formatted.replacen('.', conventions.decimal, 1)
+}
+
+/// Formats `date` with a strftime pattern, using the locale's month and
+/// weekday names for `%B`, `%b`, `%h`, `%A` and `%a`. Fails on an invalid
+/// pattern.
+pub fn format_date<Tz: TimeZone>(
+    date: &DateTime<Tz>,
+    pattern: &str,
+    conventions: &Conventions,
+) -> Result<String, RenderErrorReason>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let month = date.month0() as usize;
+    let weekday = date.weekday().num_days_from_monday() as usize;
+
+    // Names are substituted before chrono sees the pattern, so they must
+    // not introduce specifiers of their own
+    let mut localized = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            localized.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('B') => localized.push_str(conventions.months[month]),
+            Some('b' | 'h') => localized.push_str(conventions.short_months[month]),
+            Some('A') => localized.push_str(conventions.weekdays[weekday]),
+            Some('a') => localized.push_str(conventions.short_weekdays[weekday]),
+            Some(next) => {
+                localized.push('%');
+                localized.push(next);
+            }
+            None => localized.push('%'),
+        }
+    }
+
+    // Formatting fails, rather than panics, on an invalid pattern
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(&localized))
+        .map_err(|_| RenderErrorReason::Other(format!("invalid strftime format '{}'", pattern)))?;
+    Ok(formatted)
+}
+
+fn decimals_arg(h: &Helper) -> Result<Option<usize>, RenderErrorReason> {
+    match h.hash_get("decimals") {
+        Some(decimals) => decimals
+            .value()
+            .as_u64()
+            .map(|decimals| Some(decimals as usize))
+            .ok_or(RenderErrorReason::InvalidParamType("decimals number")),
+        None => Ok(None),
+    }
+}
+
+/// `{{number 1234.5}}` → `1,234.5`, or `1.234,5` in German;
+/// `decimals=2` rounds to a fixed number of places. Anything that isn't a
+/// number is shown as is.
+pub struct NumberHelper {
+    locales: LocaleSource,
+}
+
+impl NumberHelper {
+    pub fn new(locales: LocaleSource) -> NumberHelper {
+        NumberHelper { locales }
+    }
+}
+
+impl HelperDef for NumberHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("number", 0))?
+            .value();
+        let decimals = decimals_arg(h)?;
+
+        let rendered = match as_number(value) {
+            Some(number) => format_number(number, decimals, self.locales.conventions(h, ctx)),
+            None => value.render(),
+        };
+        out.write(&r.get_escape_fn()(&rendered))?;
+        Ok(())
+    }
+}
+
+/// `{{currency 1234.5 "EUR"}}` → `€1,234.50`, or `1.234,50 €` in German.
+/// Amounts have two decimals unless `decimals=` says otherwise. Anything
+/// that isn't a number is shown as is.
+pub struct CurrencyHelper {
+    locales: LocaleSource,
+}
+
+impl CurrencyHelper {
+    pub fn new(locales: LocaleSource) -> CurrencyHelper {
+        CurrencyHelper { locales }
+    }
+}
+
+impl HelperDef for CurrencyHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("currency", 0))?
+            .value();
+        let code = h
+            .param(1)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("currency", 1))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("currency code string"))?;
+        let decimals = decimals_arg(h)?.unwrap_or(2);
+
+        let Some(amount) = as_number(value) else {
+            out.write(&r.get_escape_fn()(&value.render()))?;
+            return Ok(());
+        };
+        let conventions = self.locales.conventions(h, ctx);
+        let symbol = CURRENCY_SYMBOLS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(code))
+            .map_or(code, |(_, symbol)| symbol);
+        let number = format_number(amount.abs(), Some(decimals), conventions);
+        let sign = match amount < 0.0 && number.bytes().any(|byte| matches!(byte, b'1'..=b'9')) {
+            true => "-",
+            false => "",
+        };
+
+        let rendered = format!(
+            "{}{}",
+            sign,
+            conventions
+                .currency
+                .replace("{s}", symbol)
+                .replace("{n}", &number)
+        );
+        out.write(&r.get_escape_fn()(&rendered))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NBSP: &str = "\u{a0}";
+    const NNBSP: &str = "\u{202f}";
+
+    fn conventions(language: &str) -> &'static Conventions {
+        lookup(language).unwrap()
+    }
+
+    fn render(template: &str, data: Value) -> String {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper(
+            "currency",
+            Box::new(CurrencyHelper::new(LocaleSource::new("_", "en"))),
+        );
+        handlebars.render_template(template, &data).unwrap()
+    }
+
+    #[test]
+    fn numbers_by_locale() {
+        // A grouped negative, rounding that carries into a new group, a
+        // negative that rounds to zero, and a fraction kept as it is
+        let cases = [
+            ("en", ["-1,234,567.89", "1,000.00", "0.00", "1,234.5"]),
+            ("de", ["-1.234.567,89", "1.000,00", "0,00", "1.234,5"]),
+            ("fr", ["-1 234 567,89", "1 000,00", "0,00", "1 234,5"]),
+            ("es", ["-1.234.567,89", "1.000,00", "0,00", "1.234,5"]),
+            ("it", ["-1.234.567,89", "1.000,00", "0,00", "1.234,5"]),
+            ("nl", ["-1.234.567,89", "1.000,00", "0,00", "1.234,5"]),
+            ("pt", ["-1.234.567,89", "1.000,00", "0,00", "1.234,5"]),
+        ];
+        for (language, expected) in cases {
+            let conventions = conventions(language);
+            let formatted = [
+                format_number(-1234567.891, Some(2), conventions),
+                format_number(999.996, Some(2), conventions),
+                format_number(-0.004, Some(2), conventions),
+                format_number(1234.5, None, conventions),
+            ];
+            let expected = expected.map(|number| number.replace(' ', NNBSP));
+            assert_eq!(formatted, expected, "{}", language);
+        }
+    }
+
+    #[test]
+    fn small_numbers_are_not_grouped() {
+        for locale in &LOCALES {
+            assert_eq!(format_number(999.0, None, locale), "999");
+            assert_eq!(format_number(-5.0, Some(0), locale), "-5");
+        }
+    }
+
+    #[test]
+    fn currency_by_locale() {
+        let cases = [
+            ("en", "-€1,234.50"),
+            ("de", "-1.234,50 €"),
+            ("fr", "-1 234,50 €"),
+            ("es", "-1.234,50 €"),
+            ("it", "-1.234,50 €"),
+            ("nl", "-€ 1.234,50"),
+            ("pt", "-€ 1.234,50"),
+        ];
+        for (language, expected) in cases {
+            let rendered = render(
+                "{{currency amount \"EUR\" locale=language}}",
+                json!({"amount": -1234.5, "language": language}),
+            );
+            let expected = expected
+                .replace("1 234", &format!("1{}234", NNBSP))
+                .replace(' ', NBSP);
+            assert_eq!(rendered, expected, "{}", language);
+        }
+    }
+
+    #[test]
+    fn plural_categories_by_locale() {
+        let counts = [0.0, 1.0, 2.0, 5.0, 1.5];
+        let cases = [
+            ("en", [false, true, false, false, false]),
+            ("de", [false, true, false, false, false]),
+            ("fr", [true, true, false, false, true]),
+            ("es", [false, true, false, false, false]),
+            ("it", [false, true, false, false, false]),
+            ("nl", [false, true, false, false, false]),
+            ("pt", [true, true, false, false, true]),
+        ];
+        for (language, expected) in cases {
+            let conventions = conventions(language);
+            assert_eq!(
+                counts.map(|count| conventions.is_one(count)),
+                expected,
+                "{}",
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_languages_have_no_conventions() {
+        assert_eq!(lookup("de-AT").map(|locale| locale.language), Some("de"));
+        assert!(lookup("sv").is_none());
+    }
+}
@@ -0,0 +1,388 @@
+//! `{{activeClass "/blog" "active"}}` marks navigation links to the current
+//! page, using the reserved `path` context value. `linkAttrs` and
+//! `externalLink` give links to other sites `target="_blank"` and
+//! `rel="noopener noreferrer"`, as does `autolink` for the links it finds
+//! in plain text.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason, Renderable,
+};
+
+/// Used when no class is given.
+const DEFAULT_CLASS: &str = "active";
+
+pub struct ActiveClassHelper {
+    /// Context key holding the current path, e.g. `_path`.
+    path_key: String,
+}
+
+impl ActiveClassHelper {
+    pub fn new(reserved_prefix: &str) -> ActiveClassHelper {
+        ActiveClassHelper {
+            path_key: format!("{}path", reserved_prefix),
+        }
+    }
+}
+
+/// Whether `current` is `link` or, unless `exact`, below it. Matching is by
+/// whole segments, so `/blog` covers `/blog/post` but not `/blogroll`, and
+/// `/` only ever matches the home page.
+fn is_active(current: &str, link: &str, exact: bool) -> bool {
+    let current = current.trim_end_matches('/');
+    let link = link.trim_end_matches('/');
+    if current == link {
+        return true;
+    }
+    !exact
+        && !link.is_empty()
+        && current
+            .strip_prefix(link)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+impl HelperDef for ActiveClassHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let link = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("activeClass", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("path string"))?;
+        let class = match h.param(1) {
+            Some(param) => param
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("class string"))?,
+            None => DEFAULT_CLASS,
+        };
+        let exact = h
+            .hash_get("exact")
+            .and_then(|exact| exact.value().as_bool())
+            .unwrap_or(false);
+
+        let current = ctx
+            .data()
+            .get(&self.path_key)
+            .and_then(|path| path.as_str())
+            .unwrap_or_default();
+        if is_active(current, link, exact) {
+            out.write(&r.get_escape_fn()(class))?;
+        }
+        Ok(())
+    }
+}
+
+/// Added to links that leave the site.
+const EXTERNAL_ATTRIBUTES: &str = r#" target="_blank" rel="noopener noreferrer""#;
+
+/// The lowercased host of a `scheme://host/...` or `//host/...` URL, without
+/// credentials or port. Relative URLs and `mailto:` or `tel:` links have none.
+fn url_host(url: &str) -> Option<String> {
+    let url = url.trim();
+    let rest = match url.strip_prefix("//") {
+        Some(rest) => rest,
+        None => {
+            let (scheme, rest) = url.split_once("://")?;
+            if scheme.is_empty()
+                || !scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+            {
+                return None;
+            }
+            rest
+        }
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        // IPv6 literal
+        Some(literal) => literal.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    Some(host.to_ascii_lowercase())
+}
+
+/// Leaves the site: the URL names a host other than the one in `base_url`,
+/// ignoring `www.`. Without `base_url` every absolute URL is external.
+fn is_external(url: &str, site_host: Option<&str>) -> bool {
+    let Some(host) = url_host(url) else {
+        return false;
+    };
+    let bare = |host: &str| host.strip_prefix("www.").unwrap_or(host).to_string();
+    site_host.is_none_or(|site_host| bare(site_host) != bare(&host))
+}
+
+fn url_param(h: &Helper, name: &'static str) -> Result<String, RenderErrorReason> {
+    let value = h
+        .param(0)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 0))?
+        .value();
+    Ok(value.render())
+}
+
+/// `<a href="{{url}}"{{linkAttrs url}}>` adds the external link attributes,
+/// with their leading space, or nothing for internal links.
+pub struct LinkAttrsHelper {
+    site_host: Option<String>,
+}
+
+impl LinkAttrsHelper {
+    pub fn new(base_url: Option<&str>) -> LinkAttrsHelper {
+        LinkAttrsHelper {
+            site_host: base_url.and_then(url_host),
+        }
+    }
+}
+
+impl HelperDef for LinkAttrsHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let url = url_param(h, "linkAttrs")?;
+        if is_external(&url, self.site_host.as_deref()) {
+            out.write(EXTERNAL_ATTRIBUTES)?;
+        }
+        Ok(())
+    }
+}
+
+/// `{{#externalLink url class="btn"}}Docs{{/externalLink}}` renders the whole
+/// `<a>`, with hash arguments as attributes and the external link attributes
+/// when the URL leaves the site.
+pub struct ExternalLinkHelper {
+    site_host: Option<String>,
+}
+
+impl ExternalLinkHelper {
+    pub fn new(base_url: Option<&str>) -> ExternalLinkHelper {
+        ExternalLinkHelper {
+            site_host: base_url.and_then(url_host),
+        }
+    }
+}
+
+impl HelperDef for ExternalLinkHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let url = url_param(h, "externalLink")?;
+        let escape = r.get_escape_fn();
+
+        let mut tag = format!(r#"<a href="{}""#, escape(&url));
+        for (name, value) in h.hash() {
+            tag.push_str(&format!(
+                r#" {}="{}""#,
+                name,
+                escape(&value.value().render())
+            ));
+        }
+        if is_external(&url, self.site_host.as_deref()) {
+            tag.push_str(EXTERNAL_ATTRIBUTES);
+        }
+        tag.push('>');
+        out.write(&tag)?;
+
+        match h.template() {
+            Some(template) => template.render(r, ctx, rc, out)?,
+            None => out.write(&escape(&url))?,
+        }
+        out.write("</a>")?;
+        Ok(())
+    }
+}
+
+/// Trimmed from the end of an autolinked URL, as sentence punctuation.
+const TRAILING_PUNCTUATION: &str = "?!.,:;*_~'\"";
+
+/// `{{autolink text}}` escapes plain text and turns bare URLs and email
+/// addresses in it into links. Hash arguments: `urls=false` or
+/// `emails=false` to skip either, `class` for the links, and `rel` to add
+/// to the `rel` of external ones, e.g. `rel="ugc nofollow"`.
+pub struct AutolinkHelper {
+    site_host: Option<String>,
+}
+
+impl AutolinkHelper {
+    pub fn new(base_url: Option<&str>) -> AutolinkHelper {
+        AutolinkHelper {
+            site_host: base_url.and_then(url_host),
+        }
+    }
+}
+
+/// Where a URL may start: not inside a word, address or path.
+fn at_word_start(text: &str, at: usize) -> bool {
+    text[..at]
+        .chars()
+        .next_back()
+        .is_none_or(|c| !c.is_alphanumeric() && !"@./-_:".contains(c))
+}
+
+/// Length of the URL starting `text`, if one does: `http://`, `https://` or
+/// `www.` followed by a host, up to whitespace, without trailing punctuation
+/// or closing brackets that don't belong to it.
+fn url_len(text: &str) -> Option<usize> {
+    let prefix = ["https://", "http://", "www."].into_iter().find(|prefix| {
+        text.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    })?;
+    let mut url = text
+        .split(|c: char| c.is_whitespace() || "<>\"".contains(c))
+        .next()
+        .unwrap_or_default();
+    loop {
+        let balanced = |open, close| url.matches(open).count() >= url.matches(close).count();
+        url = match url.chars().next_back() {
+            Some(c) if TRAILING_PUNCTUATION.contains(c) => &url[..url.len() - 1],
+            Some(')') if !balanced('(', ')') => &url[..url.len() - 1],
+            Some(']') if !balanced('[', ']') => &url[..url.len() - 1],
+            _ => break,
+        };
+    }
+    let host_starts = url[prefix.len()..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric());
+    host_starts.then_some(url.len())
+}
+
+fn is_email_local(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._%+-".contains(c)
+}
+
+fn is_email_domain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ".-".contains(c)
+}
+
+/// The bounds of the email address around the `@` at `at`, if it is one:
+/// a local part, and a dotted domain ending in a top-level domain of letters.
+fn email_bounds(text: &str, at: usize) -> Option<(usize, usize)> {
+    let local = text[..at]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_email_local(c))
+        .last()
+        .map(|(start, _)| start)?;
+    let local = text[local..at]
+        .find(|c| c != '.')
+        .map(|offset| local + offset)
+        .filter(|&start| start < at)?;
+    let domain = &text[at + 1..];
+    let domain = &domain[..domain.find(|c| !is_email_domain(c)).unwrap_or(domain.len())];
+    let domain = domain.trim_end_matches(['.', '-']);
+    let (name, tld) = domain.rsplit_once('.')?;
+    let valid = !name.is_empty()
+        && !name.starts_with(['.', '-'])
+        && tld.len() >= 2
+        && tld.chars().all(|c| c.is_ascii_alphabetic());
+    valid.then_some((local, at + 1 + domain.len()))
+}
+
+impl HelperDef for AutolinkHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let text = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("autolink", 0))?
+            .value()
+            .render();
+        let enabled = |name| {
+            h.hash_get(name)
+                .and_then(|value| value.value().as_bool())
+                .unwrap_or(true)
+        };
+        let (urls, emails) = (enabled("urls"), enabled("emails"));
+        let escape = r.get_escape_fn();
+        let class = h
+            .hash_get("class")
+            .map(|class| format!(r#" class="{}""#, escape(&class.value().render())))
+            .unwrap_or_default();
+        let rel = h
+            .hash_get("rel")
+            .map(|rel| rel.value().render())
+            .filter(|rel| !rel.trim().is_empty());
+        let external = match &rel {
+            Some(rel) => format!(
+                r#" target="_blank" rel="noopener noreferrer {}""#,
+                escape(rel.trim())
+            ),
+            None => EXTERNAL_ATTRIBUTES.to_string(),
+        };
+
+        let link = |href: &str, label: &str, html: &mut String| {
+            html.push_str(&format!(r#"<a href="{}"{}"#, escape(href), class));
+            if is_external(href, self.site_host.as_deref()) {
+                html.push_str(&external);
+            }
+            html.push_str(&format!(">{}</a>", escape(label)));
+        };
+
+        let mut html = String::with_capacity(text.len());
+        // Start of the text not yet written
+        let mut pending = 0;
+        let mut at = 0;
+        while let Some(c) = text[at..].chars().next() {
+            if urls
+                && at_word_start(&text, at)
+                && let Some(len) = url_len(&text[at..])
+            {
+                let url = &text[at..at + len];
+                html.push_str(&escape(&text[pending..at]));
+                match url.get(..4) {
+                    Some(start) if start.eq_ignore_ascii_case("www.") => {
+                        link(&format!("https://{}", url), url, &mut html)
+                    }
+                    _ => link(url, url, &mut html),
+                }
+                at += len;
+                pending = at;
+                continue;
+            }
+            // An address overlapping a link just written isn't one
+            if emails
+                && c == '@'
+                && let Some((start, end)) = email_bounds(&text, at)
+                && start >= pending
+            {
+                let address = &text[start..end];
+                html.push_str(&escape(&text[pending..start]));
+                link(&format!("mailto:{}", address), address, &mut html);
+                at = end;
+                pending = at;
+                continue;
+            }
+            at += c.len_utf8();
+        }
+        html.push_str(&escape(&text[pending..]));
+
+        out.write(&html)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+//! `{{pageTitle}}` builds the `<title>` text of the page being rendered from
+//! its front matter `title` and the `[title]` config.
+
+use crate::config::{TitleConfig, TitlePosition};
+use crate::pages::Page;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonRender, RenderContext, RenderError, ScopedJson,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const DEFAULT_SEPARATOR: &str = " — ";
+
+pub struct PageTitleHelper {
+    config: TitleConfig,
+    /// Front matter titles by template name, e.g. `pages/about`.
+    titles: HashMap<String, String>,
+}
+
+impl PageTitleHelper {
+    pub fn new(config: &TitleConfig, pages: &[Page]) -> PageTitleHelper {
+        let titles = pages
+            .iter()
+            .filter_map(|page| {
+                let title = page.front_matter.get("title")?.render();
+                Some((page.template_name(), title))
+            })
+            .collect();
+
+        PageTitleHelper {
+            config: config.clone(),
+            titles,
+        }
+    }
+
+    fn title(&self, title: Option<String>) -> String {
+        let site_name = self.config.site_name.as_deref().unwrap_or_default();
+        let Some(title) = title.filter(|title| !title.is_empty()) else {
+            return self
+                .config
+                .default
+                .clone()
+                .unwrap_or_else(|| site_name.to_string());
+        };
+        if site_name.is_empty() {
+            return title;
+        }
+
+        let separator = self
+            .config
+            .separator
+            .as_deref()
+            .unwrap_or(DEFAULT_SEPARATOR);
+        match self.config.position {
+            TitlePosition::Prefix => format!("{}{}{}", site_name, separator, title),
+            TitlePosition::Suffix => format!("{}{}{}", title, separator, site_name),
+        }
+    }
+}
+
+impl HelperDef for PageTitleHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        // An explicit title, e.g. from a data file, wins over front matter
+        let title = match h.param(0).map(|param| param.value()) {
+            Some(Value::Null) | None => rc
+                .get_root_template_name()
+                .and_then(|template_name| self.titles.get(template_name))
+                .cloned(),
+            Some(value) => Some(value.render()),
+        };
+        Ok(Value::String(self.title(title)).into())
+    }
+}
@@ -0,0 +1,97 @@
+//! `{{#pageRange current total window=2}}` yields the entries of a compact
+//! pager, e.g. `1 … 4 5 [6] 7 8 … 20`: the first and last page, the pages
+//! within `window` of the current one, and gaps where pages are left out.
+//! Each entry is `{page, current, gap}`, with `gap` entries having no
+//! page number. As a subexpression it returns the entries.
+
+use super::collections::render_each;
+use super::units::as_number;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson,
+};
+use serde_json::{Value, json};
+
+/// Pages shown on each side of the current one unless `window=` is given.
+const DEFAULT_WINDOW: u64 = 2;
+
+/// Reads `current`, `total` and `window=`. Numbers may be strings, as from
+/// the query string; `current` is clamped to the pages that exist.
+fn arguments(h: &Helper) -> Result<(u64, u64, u64), RenderError> {
+    let number = |index: usize, kind: &'static str| -> Result<f64, RenderError> {
+        let value = h
+            .param(index)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("pageRange", index))?
+            .value();
+        as_number(value).ok_or_else(|| RenderErrorReason::InvalidParamType(kind).into())
+    };
+    let current = number(0, "current page number")?;
+    let total = number(1, "page count")?.max(0.0) as u64;
+    let window = match h.hash_get("window") {
+        Some(window) => as_number(window.value())
+            .filter(|window| *window >= 0.0)
+            .ok_or(RenderErrorReason::InvalidParamType("non-negative window"))?
+            as u64,
+        None => DEFAULT_WINDOW,
+    };
+    Ok((
+        current.clamp(1.0, total.max(1) as f64) as u64,
+        total,
+        window,
+    ))
+}
+
+/// The pager entries. A gap that would hide a single page shows the page
+/// instead, as it takes the same room.
+fn page_range(current: u64, total: u64, window: u64) -> Vec<Value> {
+    let page = |number: u64| json!({ "page": number, "current": number == current, "gap": false });
+
+    let mut numbers = vec![1, total];
+    numbers
+        .extend(current.saturating_sub(window).max(1)..=current.saturating_add(window).min(total));
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let mut entries = Vec::new();
+    let mut previous = 0;
+    for number in numbers
+        .into_iter()
+        .filter(|number| (1..=total).contains(number))
+    {
+        match number - previous {
+            1 => {}
+            2 => entries.push(page(previous + 1)),
+            _ => entries.push(json!({ "page": null, "current": false, "gap": true })),
+        }
+        entries.push(page(number));
+        previous = number;
+    }
+    entries
+}
+
+pub struct PageRangeHelper;
+
+impl HelperDef for PageRangeHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let (current, total, window) = arguments(h)?;
+        Ok(Value::Array(page_range(current, total, window)).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let (current, total, window) = arguments(h)?;
+        render_each(page_range(current, total, window), h, r, ctx, rc, out)
+    }
+}
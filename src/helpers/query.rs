@@ -0,0 +1,197 @@
+//! `query`, `queryInt` and `queryBool` read a parameter from the reserved
+//! `query` context value, e.g. `{{queryInt "page" 1}}`. They fall back to
+//! the default when the parameter is absent or doesn't convert, and when
+//! there is no query at all, as in static builds. `queryString` and
+//! `mergeQuery` build query strings for links.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonRender, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson,
+};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy)]
+pub enum QueryType {
+    String,
+    Int,
+    Bool,
+}
+
+impl QueryType {
+    pub fn helper_name(self) -> &'static str {
+        match self {
+            QueryType::String => "query",
+            QueryType::Int => "queryInt",
+            QueryType::Bool => "queryBool",
+        }
+    }
+
+    fn coerce(self, raw: &str) -> Option<Value> {
+        let raw = raw.trim();
+        match self {
+            QueryType::String => Some(Value::from(raw)),
+            QueryType::Int => raw.parse::<i64>().ok().map(Value::from),
+            // A bare `?debug` counts as set
+            QueryType::Bool => match raw.to_ascii_lowercase().as_str() {
+                "" | "1" | "true" | "yes" | "on" => Some(Value::Bool(true)),
+                "0" | "false" | "no" | "off" => Some(Value::Bool(false)),
+                _ => None,
+            },
+        }
+    }
+}
+
+pub struct QueryHelper {
+    kind: QueryType,
+    /// Context key holding the query parameters, e.g. `_query`.
+    query_key: String,
+}
+
+impl QueryHelper {
+    pub fn new(kind: QueryType, reserved_prefix: &str) -> QueryHelper {
+        QueryHelper {
+            kind,
+            query_key: format!("{}query", reserved_prefix),
+        }
+    }
+}
+
+impl HelperDef for QueryHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let name = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(
+                self.kind.helper_name(),
+                0,
+            ))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("parameter name string"))?;
+        let default = h
+            .param(1)
+            .map(|param| param.value().clone())
+            .unwrap_or(Value::Null);
+
+        let value = ctx
+            .data()
+            .get(&self.query_key)
+            .and_then(|query| query.get(name))
+            .and_then(Value::as_str)
+            .and_then(|raw| self.kind.coerce(raw));
+        Ok(value.unwrap_or(default).into())
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+pub(super) fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `?name=value&...` in key order, or nothing for no parameters. Arrays
+/// repeat the name and null values are left out.
+fn query_string(parameters: &Map<String, Value>) -> String {
+    let mut pairs = Vec::new();
+    for (name, value) in parameters {
+        let values = match value {
+            Value::Null => continue,
+            Value::Array(values) => values.iter().filter(|value| !value.is_null()).collect(),
+            value => vec![value],
+        };
+        for value in values {
+            pairs.push(format!("{}={}", encode(name), encode(&value.render())));
+        }
+    }
+
+    match pairs.is_empty() {
+        true => String::new(),
+        false => format!("?{}", pairs.join("&")),
+    }
+}
+
+/// `{{queryString filters}}` or `{{queryString page=2 sort="date"}}` →
+/// `?page=2&sort=date`
+pub struct QueryStringHelper;
+
+impl HelperDef for QueryStringHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let parameters = match h.param(0).map(|param| param.value()) {
+            Some(Value::Object(parameters)) => parameters.clone(),
+            Some(Value::Null) => Map::new(),
+            Some(_) => return Err(RenderErrorReason::InvalidParamType("object").into()),
+            None => h
+                .hash()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.value().clone()))
+                .collect(),
+        };
+        Ok(Value::String(query_string(&parameters)).into())
+    }
+}
+
+/// `{{mergeQuery "page" 2}}` or `{{mergeQuery page=2 sort="date"}}`: the
+/// current query with the given parameters set, and removed when `null`.
+pub struct MergeQueryHelper {
+    /// Context key holding the query parameters, e.g. `_query`.
+    query_key: String,
+}
+
+impl MergeQueryHelper {
+    pub fn new(reserved_prefix: &str) -> MergeQueryHelper {
+        MergeQueryHelper {
+            query_key: format!("{}query", reserved_prefix),
+        }
+    }
+}
+
+impl HelperDef for MergeQueryHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let mut parameters = match ctx.data().get(&self.query_key) {
+            Some(Value::Object(query)) => query.clone(),
+            _ => Map::new(),
+        };
+
+        let params = h.params();
+        if params.len() % 2 != 0 {
+            return Err(RenderErrorReason::InvalidParamType("name and value pairs").into());
+        }
+        for pair in params.chunks(2) {
+            let name = pair[0]
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("parameter name string"))?;
+            parameters.insert(name.to_string(), pair[1].value().clone());
+        }
+        for (name, value) in h.hash() {
+            parameters.insert(name.to_string(), value.value().clone());
+        }
+
+        Ok(Value::String(query_string(&parameters)).into())
+    }
+}
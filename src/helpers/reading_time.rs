@@ -0,0 +1,102 @@
+//! `{{readingTime post.body}}` estimates the minutes it takes to read a text
+//! or HTML value; `{{readingTimeLabel post.body}}` words it as `5 min read`.
+
+use crate::config::ReadingTimeConfig;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonRender, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson,
+};
+use serde_json::Value;
+
+/// Words outside of `<...>` tags.
+fn word_count(text: &str) -> usize {
+    let mut in_tag = false;
+    let stripped: String = text
+        .chars()
+        .map(|c| match c {
+            '<' => {
+                in_tag = true;
+                ' '
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                ' '
+            }
+            _ if in_tag => ' ',
+            c => c,
+        })
+        .collect();
+    stripped.split_whitespace().count()
+}
+
+/// Reading time rounded to whole minutes, so anything under half a minute,
+/// including empty content, is 0.
+pub fn minutes(text: &str, words_per_minute: u32) -> u64 {
+    (word_count(text) as f64 / words_per_minute.max(1) as f64).round() as u64
+}
+
+pub struct ReadingTimeHelper {
+    config: ReadingTimeConfig,
+    /// Renders the wording from the config instead of the number.
+    label: bool,
+}
+
+impl ReadingTimeHelper {
+    pub fn new(config: &ReadingTimeConfig, label: bool) -> ReadingTimeHelper {
+        ReadingTimeHelper {
+            config: config.clone(),
+            label,
+        }
+    }
+
+    fn describe(&self, minutes: u64) -> String {
+        match minutes {
+            0 => self
+                .config
+                .under_a_minute
+                .clone()
+                .unwrap_or_else(|| "less than a minute".to_string()),
+            _ => self
+                .config
+                .label
+                .as_deref()
+                .unwrap_or("{} min read")
+                .replace("{}", &minutes.to_string()),
+        }
+    }
+}
+
+impl HelperDef for ReadingTimeHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let name = match self.label {
+            true => "readingTimeLabel",
+            false => "readingTime",
+        };
+        let text = match h.param(0).map(|param| param.value()) {
+            Some(Value::Null) => String::new(),
+            Some(value) => value.render(),
+            None => return Err(RenderErrorReason::ParamNotFoundForIndex(name, 0).into()),
+        };
+        let words_per_minute = match h.hash_get("wpm").map(|wpm| wpm.value()) {
+            Some(wpm) => wpm
+                .as_u64()
+                .filter(|wpm| *wpm > 0)
+                .ok_or(RenderErrorReason::InvalidParamType("wpm positive integer"))?
+                as u32,
+            None => self.config.words_per_minute(),
+        };
+
+        let minutes = minutes(&text, words_per_minute);
+        Ok(match self.label {
+            true => Value::String(self.describe(minutes)),
+            false => Value::from(minutes),
+        }
+        .into())
+    }
+}
@@ -0,0 +1,69 @@
+//! `{{renderPage "widgets/stats"}}` renders another page template in place,
+//! with the current page's context.
+
+use crate::pages;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use std::cell::RefCell;
+
+/// How deeply embedded pages may nest.
+const MAX_DEPTH: usize = 8;
+
+thread_local! {
+    /// Pages being embedded on this thread, outermost first. A render never
+    /// moves between threads, so this tracks the current nesting.
+    static EMBEDDING: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+pub struct RenderPageHelper;
+
+impl HelperDef for RenderPageHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let page = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("renderPage", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType("page name string"))?;
+        let Some((template_name, _)) = pages::resolve_template(r, page.trim_matches('/')) else {
+            return Err(
+                RenderErrorReason::Other(format!("renderPage: page '{}' not found", page)).into(),
+            );
+        };
+
+        let (depth, chain) = EMBEDDING.with_borrow(|embedding| {
+            let mut chain = match embedding.first() {
+                Some(_) => embedding.clone(),
+                // The outermost page is the one being served
+                None => rc.get_root_template_name().cloned().into_iter().collect(),
+            };
+            chain.push(template_name.clone());
+            (embedding.len(), chain)
+        });
+        let is_cycle = chain[..chain.len() - 1].contains(&template_name);
+        if is_cycle || chain.len() > MAX_DEPTH {
+            let problem = match is_cycle {
+                true => "renderPage cycle".to_string(),
+                false => format!("renderPage nested more than {} levels", MAX_DEPTH),
+            };
+            return Err(
+                RenderErrorReason::Other(format!("{}: {}", problem, chain.join(" → "))).into(),
+            );
+        }
+
+        EMBEDDING.with_borrow_mut(|embedding| *embedding = chain);
+        let rendered = r.render_with_context(&template_name, ctx);
+        EMBEDDING.with_borrow_mut(|embedding| embedding.truncate(depth));
+
+        out.write(&rendered?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+//! `{{signedUrl "downloads/report.zip" ttl=3600}}` renders a link to
+//! `/static/downloads/report.zip` that stays valid for `ttl` seconds,
+//! `signed_ttl` under `[static]` by default; see `src/signed_urls.rs`.
+
+use super::query::encode;
+use super::units::as_number;
+use crate::config::StaticConfig;
+use crate::signed_urls::{self, EXPIRES_PARAM, SIGNATURE_PARAM};
+use crate::static_files::STATIC_DIR;
+use chrono::Utc;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::Value;
+
+pub struct SignedUrlHelper {
+    secret: Option<String>,
+    ttl: u64,
+}
+
+impl SignedUrlHelper {
+    pub fn new(config: &StaticConfig) -> SignedUrlHelper {
+        SignedUrlHelper {
+            secret: config.signing_secret(),
+            ttl: config.signed_ttl,
+        }
+    }
+}
+
+impl HelperDef for SignedUrlHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let path = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("signedUrl", 0))?
+            .value()
+            .as_str()
+            .ok_or(RenderErrorReason::InvalidParamType(
+                "static file path string",
+            ))?;
+        let ttl = match h.hash_get("ttl") {
+            Some(ttl) => as_number(ttl.value()).filter(|ttl| *ttl > 0.0).ok_or(
+                RenderErrorReason::InvalidParamType("positive ttl in seconds"),
+            )? as i64,
+            None => self.ttl as i64,
+        };
+        let Some(secret) = &self.secret else {
+            return Err(RenderErrorReason::Other(
+                "signedUrl: no signing secret is configured (signing_secret under [static])"
+                    .to_string(),
+            )
+            .into());
+        };
+
+        let path = signed_urls::normalize(path);
+        let expires = Utc::now().timestamp().saturating_add(ttl);
+        let encoded: Vec<String> = path.split('/').map(encode).collect();
+        Ok(Value::String(format!(
+            "/{}/{}?{}={}&{}={}",
+            STATIC_DIR,
+            encoded.join("/"),
+            EXPIRES_PARAM,
+            expires,
+            SIGNATURE_PARAM,
+            signed_urls::signature(secret, &path, expires)
+        ))
+        .into())
+    }
+}
@@ -0,0 +1,191 @@
+//! `{{table rows}}` renders an array of objects as a `<table>`, and
+//! `{{dl object}}` an object as a `<dl>`, for status pages and quick looks
+//! at data of any shape. Values are escaped unless `escape=false`.
+
+use handlebars::{
+    BlockContext, Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output,
+    RenderContext, RenderError, RenderErrorReason, Renderable, to_json,
+};
+use serde_json::{Map, Value};
+
+/// Escapes `text` through the registry's escape function, or not at all
+/// with `escape=false`.
+fn escaper<'a>(h: &Helper, r: &'a Handlebars) -> impl Fn(&str) -> String + 'a {
+    let escape = h
+        .hash_get("escape")
+        .is_none_or(|escape| escape.value().as_bool() != Some(false));
+    move |text: &str| match escape {
+        true => r.get_escape_fn()(text),
+        false => text.to_string(),
+    }
+}
+
+/// ` class="…"` from `class=`, or nothing.
+fn class_attribute(h: &Helper, r: &Handlebars) -> String {
+    match h.hash_get("class").map(|class| class.value().render()) {
+        Some(class) if !class.is_empty() => format!(" class=\"{}\"", r.get_escape_fn()(&class)),
+        _ => String::new(),
+    }
+}
+
+/// The text of a cell: arrays of plain values joined with `, `, other
+/// arrays and objects as JSON, `null` as nothing.
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Array(items) if !items.iter().any(|item| item.is_array() || item.is_object()) => {
+            items
+                .iter()
+                .map(JsonRender::render)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+        _ => value.render(),
+    }
+}
+
+/// The columns: `columns=` as an array or a comma-separated string, in that
+/// order, or the keys of all rows in the order they first appear.
+fn columns(h: &Helper, rows: &[&Map<String, Value>]) -> Result<Vec<String>, RenderError> {
+    match h.hash_get("columns").map(|columns| columns.value()) {
+        Some(Value::Array(columns)) => Ok(columns.iter().map(JsonRender::render).collect()),
+        Some(Value::String(columns)) => Ok(columns
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Some(_) => Err(RenderErrorReason::InvalidParamType(
+            "columns array or comma-separated string",
+        )
+        .into()),
+        None => {
+            let mut columns: Vec<String> = Vec::new();
+            for key in rows.iter().flat_map(|row| row.keys()) {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+            Ok(columns)
+        }
+    }
+}
+
+/// `{{table products columns="name,price" class="status"}}`. As a block, the
+/// block renders each cell, with the value as `this`, the column as `@key`
+/// and the row's position as `@index`; `{{else}}` renders when there are no
+/// rows. Items that aren't objects are skipped.
+pub struct TableHelper;
+
+impl HelperDef for TableHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let rows: Vec<&Map<String, Value>> = match h.param(0).map(|param| param.value()) {
+            Some(Value::Array(items)) => items.iter().filter_map(Value::as_object).collect(),
+            Some(Value::Null) => Vec::new(),
+            Some(_) => return Err(RenderErrorReason::InvalidParamType("array").into()),
+            None => return Err(RenderErrorReason::ParamNotFoundForIndex("table", 0).into()),
+        };
+        if rows.is_empty() {
+            if let Some(inverse) = h.inverse() {
+                inverse.render(r, ctx, rc, out)?;
+            }
+            return Ok(());
+        }
+
+        let columns = columns(h, &rows)?;
+        let escape = escaper(h, r);
+        out.write(&format!("<table{}><thead><tr>", class_attribute(h, r)))?;
+        for column in &columns {
+            out.write(&format!("<th>{}</th>", r.get_escape_fn()(column)))?;
+        }
+        out.write("</tr></thead><tbody>")?;
+
+        for (index, row) in rows.into_iter().enumerate() {
+            out.write("<tr>")?;
+            for column in &columns {
+                let value = row.get(column).cloned().unwrap_or(Value::Null);
+                out.write("<td>")?;
+                match h.template() {
+                    Some(template) => {
+                        let mut block = BlockContext::new();
+                        block.set_local_var("key", to_json(column));
+                        block.set_local_var("index", to_json(index));
+                        block.set_base_value(value);
+                        rc.push_block(block);
+                        let result = template.render(r, ctx, rc, out);
+                        rc.pop_block();
+                        result?;
+                    }
+                    None => out.write(&escape(&cell_text(&value)))?,
+                }
+                out.write("</td>")?;
+            }
+            out.write("</tr>")?;
+        }
+
+        out.write("</tbody></table>")?;
+        Ok(())
+    }
+}
+
+/// Writes `object` as a `<dl>`, with nested objects as nested lists.
+fn write_dl(
+    object: &Map<String, Value>,
+    class: &str,
+    escape: &dyn Fn(&str) -> String,
+    key_escape: &dyn Fn(&str) -> String,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&format!("<dl{}>", class))?;
+    for (key, value) in object {
+        out.write(&format!("<dt>{}</dt><dd>", key_escape(key)))?;
+        match value {
+            Value::Object(nested) => write_dl(nested, "", escape, key_escape, out)?,
+            _ => out.write(&escape(&cell_text(value)))?,
+        }
+        out.write("</dd>")?;
+    }
+    out.write("</dl>")?;
+    Ok(())
+}
+
+/// `{{dl server class="status"}}`. An empty object or `null` renders nothing.
+pub struct DlHelper;
+
+impl HelperDef for DlHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let object = match h.param(0).map(|param| param.value()) {
+            Some(Value::Object(object)) => object,
+            Some(Value::Null) => return Ok(()),
+            Some(_) => return Err(RenderErrorReason::InvalidParamType("object").into()),
+            None => return Err(RenderErrorReason::ParamNotFoundForIndex("dl", 0).into()),
+        };
+        if object.is_empty() {
+            return Ok(());
+        }
+
+        let escape = escaper(h, r);
+        write_dl(
+            object,
+            &class_attribute(h, r),
+            &escape,
+            &|key| r.get_escape_fn()(key),
+            out,
+        )
+    }
+}
@@ -0,0 +1,124 @@
+//! `{{timeAgo date}}` renders how long ago, or how far ahead, a date is, in
+//! the wording of `TimeAgoConfig`, falling back to the locale's.
+
+use super::locale_format::{Conventions, LocaleSource};
+use crate::config::TimeAgoConfig;
+use chrono::{DateTime, NaiveDate, Utc};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason,
+};
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// Upper bound in seconds for each unit, with the unit's length and its
+/// index in `TimeAgoConfig`. Amounts are rounded, so 40 minutes is "40
+/// minutes" but 50 is "1 hour".
+const THRESHOLDS: [(i64, i64, usize); 5] = [
+    (45 * MINUTE, MINUTE, 0),
+    (22 * HOUR, HOUR, 1),
+    (26 * DAY, DAY, 2),
+    (320 * DAY, 30 * DAY, 3),
+    (i64::MAX, 365 * DAY, 4),
+];
+
+/// Below this many seconds, either way, a date is "just now".
+const JUST_NOW: i64 = 45;
+
+/// Accepts RFC 3339 timestamps and plain `YYYY-MM-DD` dates (midnight UTC).
+pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+                .map(|date| date.and_utc())
+        })
+}
+
+pub struct TimeAgoHelper {
+    config: TimeAgoConfig,
+    locales: LocaleSource,
+}
+
+impl TimeAgoHelper {
+    pub fn new(config: &TimeAgoConfig, locales: LocaleSource) -> TimeAgoHelper {
+        TimeAgoHelper {
+            config: config.clone(),
+            locales,
+        }
+    }
+
+    fn unit<'a>(&'a self, index: usize, amount: i64, conventions: &'a Conventions) -> &'a str {
+        let configured = [
+            &self.config.minute,
+            &self.config.hour,
+            &self.config.day,
+            &self.config.month,
+            &self.config.year,
+        ][index];
+        let plural = usize::from(!conventions.is_one(amount as f64));
+        match configured {
+            Some(names) => &names[plural],
+            None => conventions.units[index][plural],
+        }
+    }
+
+    /// `seconds` is positive for past dates.
+    fn describe(&self, seconds: i64, conventions: &Conventions) -> String {
+        let distance = seconds.saturating_abs();
+        if distance < JUST_NOW {
+            return self
+                .config
+                .just_now
+                .as_deref()
+                .unwrap_or(conventions.just_now)
+                .to_string();
+        }
+
+        let (_, length, index) = THRESHOLDS
+            .into_iter()
+            .find(|(limit, _, _)| distance < *limit)
+            .unwrap_or(THRESHOLDS[THRESHOLDS.len() - 1]);
+        let amount = ((distance + length / 2) / length).max(1);
+        let phrase = format!("{} {}", amount, self.unit(index, amount, conventions));
+
+        let template = match seconds > 0 {
+            true => self.config.past.as_deref().unwrap_or(conventions.past),
+            false => self.config.future.as_deref().unwrap_or(conventions.future),
+        };
+        template.replacen("{}", &phrase, 1)
+    }
+}
+
+impl HelperDef for TimeAgoHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("timeAgo", 0))?
+            .value()
+            .render();
+
+        // Unparseable input is shown as is
+        let rendered = match parse(&value) {
+            Some(date) => self.describe(
+                Utc::now().signed_duration_since(date).num_seconds(),
+                self.locales.conventions(h, ctx),
+            ),
+            None => value,
+        };
+        out.write(&r.get_escape_fn()(&rendered))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+//! `{{t title}}` picks the active locale's entry from an object such as
+//! `{"en": "Hello", "de": "Hallo"}`, using the reserved `locale` value.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::Value;
+
+pub struct TranslateHelper {
+    /// Context key holding the active locale, e.g. `_locale`.
+    locale_key: String,
+    default_locale: String,
+}
+
+impl TranslateHelper {
+    pub fn new(reserved_prefix: &str, default_locale: &str) -> TranslateHelper {
+        TranslateHelper {
+            locale_key: format!("{}locale", reserved_prefix),
+            default_locale: default_locale.to_string(),
+        }
+    }
+}
+
+impl HelperDef for TranslateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("t", 0))?
+            .value();
+        // Anything but an object, such as a plain string, is used as is
+        let Value::Object(translations) = value else {
+            return Ok(value.clone().into());
+        };
+
+        let active = ctx
+            .data()
+            .get(&self.locale_key)
+            .and_then(Value::as_str)
+            .unwrap_or(&self.default_locale);
+        let selected = translations
+            .get(active)
+            .or_else(|| translations.get(&self.default_locale))
+            .or_else(|| translations.values().next())
+            .cloned()
+            .unwrap_or(Value::Null);
+        Ok(selected.into())
+    }
+}
@@ -0,0 +1,148 @@
+//! `{{filesize bytes}}` and `{{duration seconds}}` render numbers as
+//! `1.4 MB` and `2h 5m`, with the locale's decimal mark for file sizes.
+//! Anything that isn't a number is shown as is.
+
+use super::locale_format::{LocaleSource, localize_decimal};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderErrorReason,
+};
+use serde_json::Value;
+
+const DECIMAL_UNITS: [&str; 6] = ["kB", "MB", "GB", "TB", "PB", "EB"];
+const BINARY_UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Largest first, as used by `duration`.
+const DURATION_UNITS: [(u64, &str); 4] = [(86_400, "d"), (3_600, "h"), (60, "m"), (1, "s")];
+
+/// Numbers, and strings holding numbers.
+pub fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// `1.4 MB` with one decimal, dropped when it is zero, and whole bytes below
+/// the first unit.
+fn format_filesize(bytes: f64, binary: bool) -> String {
+    let (base, units) = match binary {
+        true => (1024.0, BINARY_UNITS),
+        false => (1000.0, DECIMAL_UNITS),
+    };
+    let sign = if bytes < 0.0 { "-" } else { "" };
+    let mut size = bytes.abs();
+    if size < base {
+        return format!("{}{} B", sign, size.round());
+    }
+
+    // Compare after rounding, so 999,999 bytes is `1 MB` rather than `1000 kB`
+    let mut index = 0;
+    size /= base;
+    while (size * 10.0).round() / 10.0 >= base && index + 1 < units.len() {
+        size /= base;
+        index += 1;
+    }
+    let unit = units[index];
+    let rounded = format!("{:.1}", size);
+    let rounded = rounded.strip_suffix(".0").unwrap_or(&rounded);
+    format!("{}{} {}", sign, rounded, unit)
+}
+
+/// The largest non-zero unit and the next one when it isn't zero, e.g.
+/// `2h 5m`, `45s` or `3d`.
+fn format_duration(seconds: f64) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let mut remaining = seconds.abs().round() as u64;
+    let mut parts = Vec::new();
+    for (size, unit) in DURATION_UNITS {
+        let amount = remaining / size;
+        remaining %= size;
+        if !parts.is_empty() {
+            if amount > 0 {
+                parts.push(format!("{}{}", amount, unit));
+            }
+            break;
+        }
+        if amount > 0 {
+            parts.push(format!("{}{}", amount, unit));
+        }
+    }
+    match parts.is_empty() {
+        true => "0s".to_string(),
+        false => format!("{}{}", sign, parts.join(" ")),
+    }
+}
+
+/// `{{filesize bytes}}`, or `{{filesize bytes units="binary"}}` for `KiB`.
+pub struct FilesizeHelper {
+    locales: LocaleSource,
+}
+
+impl FilesizeHelper {
+    pub fn new(locales: LocaleSource) -> FilesizeHelper {
+        FilesizeHelper { locales }
+    }
+}
+
+impl HelperDef for FilesizeHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("filesize", 0))?
+            .value();
+        let binary = match h.hash_get("units").and_then(|units| units.value().as_str()) {
+            None | Some("decimal") => false,
+            Some("binary") => true,
+            Some(_) => {
+                return Err(
+                    RenderErrorReason::InvalidParamType("units=\"decimal\" or \"binary\"").into(),
+                );
+            }
+        };
+
+        let rendered = match as_number(value) {
+            Some(bytes) => localize_decimal(
+                &format_filesize(bytes, binary),
+                self.locales.conventions(h, ctx),
+            ),
+            None => value.render(),
+        };
+        out.write(&r.get_escape_fn()(&rendered))?;
+        Ok(())
+    }
+}
+
+/// `{{duration seconds}}`
+pub struct DurationHelper;
+
+impl HelperDef for DurationHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let value = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("duration", 0))?
+            .value();
+
+        let rendered = match as_number(value) {
+            Some(seconds) => format_duration(seconds),
+            None => value.render(),
+        };
+        out.write(&r.get_escape_fn()(&rendered))?;
+        Ok(())
+    }
+}
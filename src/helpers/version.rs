@@ -0,0 +1,143 @@
+//! `{{#ifVersionGte release.version "2.0"}}...{{else}}...{{/ifVersionGte}}`
+//! renders its block when the first version is at least the second, ordered
+//! as semantic versions; inline it is a boolean for subexpressions.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderError, RenderErrorReason, Renderable, ScopedJson,
+};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// A pre-release identifier: numeric ones sort before alphanumeric ones.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Identifier {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+/// A semantic version. Build metadata is dropped, as it doesn't order.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Version {
+    core: [u64; 3],
+    pre: Vec<Identifier>,
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        // A pre-release sorts before its release
+        self.core
+            .cmp(&other.core)
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Parses `1.2.3`, `v1.2.3-beta.1+build.5` and the short forms `1` and
+/// `1.2`, whose missing parts are zero.
+pub fn parse(text: &str) -> Option<Version> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix(['v', 'V'])
+        .unwrap_or(text)
+        .split('+')
+        .next()?;
+    let (core, pre) = match text.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (text, None),
+    };
+
+    let parts: Vec<&str> = core.split('.').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let mut numbers = [0; 3];
+    for (number, part) in numbers.iter_mut().zip(parts) {
+        if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        *number = part.parse().ok()?;
+    }
+
+    let pre = match pre {
+        Some(pre) => pre
+            .split('.')
+            .map(|identifier| match identifier.parse() {
+                _ if identifier.is_empty() => None,
+                Ok(number) => Some(Identifier::Numeric(number)),
+                Err(_) => Some(Identifier::Alphanumeric(identifier.to_string())),
+            })
+            .collect::<Option<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    Some(Version { core: numbers, pre })
+}
+
+/// Whether the first parameter is a version at least the second. Anything
+/// that doesn't parse as a version compares false.
+fn is_at_least(h: &Helper) -> Result<bool, RenderError> {
+    let version = |index: usize| -> Result<Option<Version>, RenderError> {
+        let value = h
+            .param(index)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(
+                "ifVersionGte",
+                index,
+            ))?
+            .value();
+        Ok(match value {
+            Value::String(_) | Value::Number(_) => parse(&value.render()),
+            _ => None,
+        })
+    };
+    Ok(match (version(0)?, version(1)?) {
+        (Some(version), Some(minimum)) => version >= minimum,
+        _ => false,
+    })
+}
+
+pub struct IfVersionGteHelper;
+
+impl HelperDef for IfVersionGteHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        Ok(Value::Bool(is_at_least(h)?).into())
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let at_least = is_at_least(h)?;
+        if !h.is_block() {
+            out.write(&Value::Bool(at_least).render())?;
+            return Ok(());
+        }
+        let block = match at_least {
+            true => h.template(),
+            false => h.inverse(),
+        };
+        match block {
+            Some(block) => block.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
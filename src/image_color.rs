@@ -0,0 +1,715 @@
+//! The average color of a PNG or baseline JPEG image, for placeholders shown
+//! while the image loads. Only as much of each format is decoded as the
+//! average needs: PNG pixels in full, and for JPEG only the DC coefficient
+//! of each block, which is that block's mean.
+
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+use std::path::Path;
+
+/// An sRGB color, e.g. `[125, 107, 90]`.
+pub type Rgb = [u8; 3];
+
+/// `#7d6b5a`.
+pub fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// The average color of the image at `path`, told apart by its signature.
+pub fn average_color(path: &Path) -> Result<Rgb, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.starts_with(PNG_SIGNATURE) {
+        png(&bytes)
+    } else if bytes.starts_with(&[0xff, 0xd8]) {
+        jpeg(&bytes)
+    } else {
+        Err("only PNG and JPEG images are supported".to_string())
+    }
+}
+
+/// Sums colors weighted by alpha, so transparent pixels don't count.
+#[derive(Default)]
+struct Average {
+    sums: [f64; 3],
+    weight: f64,
+}
+
+impl Average {
+    fn add(&mut self, color: [f64; 3], alpha: f64) {
+        for (sum, channel) in self.sums.iter_mut().zip(color) {
+            *sum += channel * alpha;
+        }
+        self.weight += alpha;
+    }
+
+    fn color(&self) -> Result<Rgb, String> {
+        if self.weight == 0.0 {
+            return Err("the image has no visible pixels".to_string());
+        }
+        Ok(self
+            .sums
+            .map(|sum| (sum / self.weight).round().clamp(0.0, 255.0) as u8))
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The most decompressed PNG data decoded, 64 MiB, about a 4096 × 4096
+/// RGBA image.
+const MAX_PNG_BYTES: usize = 64 * 1024 * 1024;
+
+/// Column and row offsets and steps of the seven Adam7 interlacing passes.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn png(bytes: &[u8]) -> Result<Rgb, String> {
+    let truncated = || "truncated PNG".to_string();
+    let mut position = PNG_SIGNATURE.len();
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut palette_alpha: &[u8] = &[];
+    let mut compressed = Vec::new();
+    while position + 8 <= bytes.len() {
+        let length = be_u32(&bytes[position..]) as usize;
+        let kind = &bytes[position + 4..position + 8];
+        let data = bytes
+            .get(position + 8..position + 8 + length)
+            .ok_or_else(truncated)?;
+        match kind {
+            b"IHDR" if length >= 13 => header = Some(data),
+            b"PLTE" => palette = data,
+            b"tRNS" => palette_alpha = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Data is followed by a 4-byte CRC
+        position += 12 + length;
+    }
+    let header = header.ok_or("PNG without a header")?;
+
+    let width = be_u32(header) as usize;
+    let height = be_u32(&header[4..]) as usize;
+    let depth = header[8] as usize;
+    let color_type = header[9];
+    let interlaced = header[12] == 1;
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        other => return Err(format!("unknown PNG color type {}", other)),
+    };
+    if !matches!(depth, 1 | 2 | 4 | 8 | 16) {
+        return Err(format!("unsupported PNG bit depth {}", depth));
+    }
+
+    let passes: Vec<(usize, usize)> = match interlaced {
+        true => ADAM7
+            .iter()
+            .map(|&(x, y, dx, dy)| {
+                (
+                    width.saturating_sub(x).div_ceil(dx),
+                    height.saturating_sub(y).div_ceil(dy),
+                )
+            })
+            .collect(),
+        false => vec![(width, height)],
+    };
+
+    let bits_per_pixel = channels * depth;
+    // Filters look back this many bytes, at least one
+    let filter_step = bits_per_pixel.div_ceil(8);
+
+    // Each line is a filter byte and its pixels, so the data can't
+    // legitimately inflate to more than that
+    let expected = passes
+        .iter()
+        .filter(|&&(pass_width, _)| pass_width > 0)
+        .try_fold(0usize, |total, &(pass_width, pass_height)| {
+            let stride = pass_width.checked_mul(bits_per_pixel)?.div_ceil(8);
+            total.checked_add(stride.checked_add(1)?.checked_mul(pass_height)?)
+        })
+        .filter(|&expected| expected <= MAX_PNG_BYTES)
+        .ok_or("PNG too large")?;
+    let mut raw = Vec::new();
+    ZlibDecoder::new(compressed.as_slice())
+        .take(expected as u64 + 1)
+        .read_to_end(&mut raw)
+        .map_err(|e| format!("invalid PNG data: {}", e))?;
+    if raw.len() > expected {
+        return Err("PNG data larger than its dimensions".to_string());
+    }
+
+    let max_sample = ((1u32 << depth.min(8)) - 1) as f64;
+    let mut average = Average::default();
+    let mut rest = raw.as_slice();
+    for (pass_width, pass_height) in passes {
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+        let stride = (pass_width * bits_per_pixel).div_ceil(8);
+        let mut previous = vec![0u8; stride];
+        for _ in 0..pass_height {
+            if rest.len() < stride + 1 {
+                return Err(truncated());
+            }
+            let filter = rest[0];
+            let mut line = rest[1..=stride].to_vec();
+            rest = &rest[stride + 1..];
+            unfilter(filter, &mut line, &previous, filter_step)?;
+
+            // Samples of this line, reduced to 8 bits
+            let samples: Vec<u8> = match depth {
+                16 => line.iter().step_by(2).copied().collect(),
+                8 => line.clone(),
+                _ => line
+                    .iter()
+                    .flat_map(|byte| {
+                        (0..8 / depth)
+                            .map(move |i| (byte >> (8 - depth * (i + 1))) & ((1 << depth) - 1))
+                    })
+                    .collect(),
+            };
+            for pixel in samples.chunks(channels).take(pass_width) {
+                let scale = |sample: u8| sample as f64 * 255.0 / max_sample;
+                let (color, alpha) = match color_type {
+                    0 => ([scale(pixel[0]); 3], 255.0),
+                    2 => ([pixel[0] as f64, pixel[1] as f64, pixel[2] as f64], 255.0),
+                    3 => {
+                        let index = pixel[0] as usize;
+                        let entry = palette
+                            .get(index * 3..index * 3 + 3)
+                            .ok_or("PNG palette index out of range")?;
+                        let alpha = palette_alpha.get(index).copied().unwrap_or(255);
+                        (
+                            [entry[0] as f64, entry[1] as f64, entry[2] as f64],
+                            alpha as f64,
+                        )
+                    }
+                    4 => ([scale(pixel[0]); 3], scale(pixel[1])),
+                    _ => (
+                        [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64],
+                        pixel[3] as f64,
+                    ),
+                };
+                average.add(color, alpha);
+            }
+            previous = line;
+        }
+    }
+    average.color()
+}
+
+/// Reverses a PNG scanline filter in place.
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], step: usize) -> Result<(), String> {
+    for i in 0..line.len() {
+        let left = if i >= step { line[i - step] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= step { previous[i - step] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            other => return Err(format!("unknown PNG filter {}", other)),
+        };
+        line[i] = line[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// A canonical Huffman table, decoded a bit at a time.
+#[derive(Clone, Default)]
+struct Huffman {
+    /// Codes of each length, from 1 to 16 bits.
+    counts: [u8; 16],
+    symbols: Vec<u8>,
+}
+
+impl Huffman {
+    fn decode(&self, bits: &mut BitReader) -> Result<u8, String> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0usize;
+        for &count in &self.counts {
+            code |= bits.bit() as i32;
+            let count = count as i32;
+            if code - first < count {
+                return self
+                    .symbols
+                    .get(index + (code - first) as usize)
+                    .copied()
+                    .ok_or_else(|| "invalid JPEG Huffman table".to_string());
+            }
+            index += count as usize;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid JPEG Huffman code".to_string())
+    }
+}
+
+/// Reads entropy-coded JPEG data, skipping stuffed zero bytes. At a marker
+/// it yields zero bits without moving past it.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    available: u32,
+    /// Zero bytes yielded at a marker or the end since the last restart.
+    padding: usize,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.available == 0 {
+            let byte = match self.data.get(self.position) {
+                Some(0xff) if self.data.get(self.position + 1) == Some(&0) => {
+                    self.position += 2;
+                    0xff
+                }
+                Some(0xff) | None => {
+                    self.padding += 1;
+                    0
+                }
+                Some(&byte) => {
+                    self.position += 1;
+                    byte
+                }
+            };
+            self.buffer = byte as u32;
+            self.available = 8;
+        }
+        self.available -= 1;
+        (self.buffer >> self.available) & 1
+    }
+
+    fn bits(&mut self, count: u8) -> u32 {
+        (0..count).fold(0, |value, _| (value << 1) | self.bit())
+    }
+
+    /// Skips to the byte after the next restart marker.
+    fn restart(&mut self) {
+        self.available = 0;
+        self.padding = 0;
+        while self.position + 1 < self.data.len()
+            && !(self.data[self.position] == 0xff
+                && (0xd0..=0xd7).contains(&self.data[self.position + 1]))
+        {
+            self.position += 1;
+        }
+        self.position += 2;
+    }
+}
+
+/// Zero bytes a scan may read past its data before it counts as truncated.
+/// A complete scan needs at most a byte or two to finish its last unit.
+const MAX_PADDING: usize = 8;
+
+/// A `count`-bit magnitude as the signed value it encodes.
+fn extend(value: u32, count: u8) -> i32 {
+    if count == 0 {
+        0
+    } else if value < 1 << (count - 1) {
+        value as i32 - (1 << count) + 1
+    } else {
+        value as i32
+    }
+}
+
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    predictor: i32,
+    /// Sum and count of the block means, in the -128..127 sample range.
+    sum: f64,
+    blocks: f64,
+}
+
+fn jpeg(bytes: &[u8]) -> Result<Rgb, String> {
+    let truncated = || "truncated JPEG".to_string();
+    let mut quantization = [[1u16; 64]; 4];
+    let mut dc_tables = vec![Huffman::default(); 4];
+    let mut ac_tables = vec![Huffman::default(); 4];
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0usize, 0usize);
+    let mut restart_interval = 0usize;
+    let mut position = 2;
+
+    loop {
+        // Markers may be padded with extra 0xff bytes
+        while bytes.get(position) == Some(&0xff) && bytes.get(position + 1) == Some(&0xff) {
+            position += 1;
+        }
+        if bytes.get(position) != Some(&0xff) {
+            return Err("invalid JPEG marker".to_string());
+        }
+        let marker = *bytes.get(position + 1).ok_or_else(truncated)?;
+        position += 2;
+        if marker == 0xd9 {
+            break;
+        }
+        let length = u16::from_be_bytes([
+            *bytes.get(position).ok_or_else(truncated)?,
+            *bytes.get(position + 1).ok_or_else(truncated)?,
+        ]) as usize;
+        let segment = bytes
+            .get(position + 2..position + length)
+            .ok_or_else(truncated)?;
+        position += length;
+
+        match marker {
+            // Quantization tables
+            0xdb => {
+                let mut rest = segment;
+                while let Some(&info) = rest.first() {
+                    let wide = info >> 4 == 1;
+                    let table = &mut quantization[(info & 3) as usize];
+                    let size = if wide { 128 } else { 64 };
+                    let values = rest.get(1..1 + size).ok_or_else(truncated)?;
+                    for (i, value) in table.iter_mut().enumerate() {
+                        *value = match wide {
+                            true => u16::from_be_bytes([values[i * 2], values[i * 2 + 1]]),
+                            false => values[i] as u16,
+                        };
+                    }
+                    rest = &rest[1 + size..];
+                }
+            }
+            // Huffman tables
+            0xc4 => {
+                let mut rest = segment;
+                while let Some(&info) = rest.first() {
+                    let counts: [u8; 16] = rest
+                        .get(1..17)
+                        .ok_or_else(truncated)?
+                        .try_into()
+                        .map_err(|_| truncated())?;
+                    let total: usize = counts.iter().map(|&count| count as usize).sum();
+                    let symbols = rest.get(17..17 + total).ok_or_else(truncated)?.to_vec();
+                    let table = Huffman { counts, symbols };
+                    match info >> 4 {
+                        0 => dc_tables[(info & 3) as usize] = table,
+                        _ => ac_tables[(info & 3) as usize] = table,
+                    }
+                    rest = &rest[17 + total..];
+                }
+            }
+            // Baseline and extended sequential frames
+            0xc0 | 0xc1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err("only 8-bit JPEG images are supported".to_string());
+                }
+                height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+                width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+                for spec in segment[6..].chunks_exact(3).take(segment[5] as usize) {
+                    components.push(Component {
+                        id: spec[0],
+                        horizontal: (spec[1] >> 4).max(1) as usize,
+                        vertical: (spec[1] & 15).max(1) as usize,
+                        quantization: (spec[2] & 3) as usize,
+                        dc_table: 0,
+                        ac_table: 0,
+                        predictor: 0,
+                        sum: 0.0,
+                        blocks: 0.0,
+                    });
+                }
+            }
+            0xc2 | 0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => {
+                return Err("progressive and lossless JPEG images are not supported".to_string());
+            }
+            0xdd => {
+                restart_interval = u16::from_be_bytes([
+                    *segment.first().ok_or_else(truncated)?,
+                    *segment.get(1).ok_or_else(truncated)?,
+                ]) as usize;
+            }
+            // Start of scan, followed by the entropy-coded data
+            0xda => {
+                let count = *segment.first().ok_or_else(truncated)? as usize;
+                let mut in_scan = Vec::new();
+                for spec in segment[1..].chunks_exact(2).take(count) {
+                    let index = components
+                        .iter()
+                        .position(|component| component.id == spec[0])
+                        .ok_or("JPEG scan names an unknown component")?;
+                    components[index].dc_table = (spec[1] >> 4 & 3) as usize;
+                    components[index].ac_table = (spec[1] & 3) as usize;
+                    components[index].predictor = 0;
+                    in_scan.push(index);
+                }
+                let mut bits = BitReader {
+                    data: bytes,
+                    position,
+                    buffer: 0,
+                    available: 0,
+                    padding: 0,
+                };
+                decode_scan(
+                    &mut bits,
+                    &mut components,
+                    &in_scan,
+                    (width, height),
+                    restart_interval,
+                    &quantization,
+                    (&dc_tables, &ac_tables),
+                )?;
+
+                // Continue after the scan's data, at the next marker
+                position = bits.position;
+                while position + 1 < bytes.len()
+                    && !(bytes[position] == 0xff
+                        && bytes[position + 1] != 0
+                        && !(0xd0..=0xd7).contains(&bytes[position + 1]))
+                {
+                    position += 1;
+                }
+            }
+            _ => {}
+        }
+        if position >= bytes.len() {
+            break;
+        }
+    }
+
+    let mean = |component: &Component| match component.blocks {
+        0.0 => 0.0,
+        blocks => component.sum / blocks + 128.0,
+    };
+    let mut average = Average::default();
+    match components.as_slice() {
+        [gray] => average.add([mean(gray); 3], 1.0),
+        [y, cb, cr] => {
+            let (y, cb, cr) = (mean(y), mean(cb) - 128.0, mean(cr) - 128.0);
+            average.add(
+                [
+                    y + 1.402 * cr,
+                    y - 0.344136 * cb - 0.714136 * cr,
+                    y + 1.772 * cb,
+                ],
+                1.0,
+            );
+        }
+        [] => return Err("JPEG without a frame".to_string()),
+        _ => return Err("only grayscale and YCbCr JPEG images are supported".to_string()),
+    }
+    average.color()
+}
+
+/// Decodes one scan, adding the mean of every block to its component.
+fn decode_scan(
+    bits: &mut BitReader,
+    components: &mut [Component],
+    in_scan: &[usize],
+    (width, height): (usize, usize),
+    restart_interval: usize,
+    quantization: &[[u16; 64]; 4],
+    (dc_tables, ac_tables): (&[Huffman], &[Huffman]),
+) -> Result<(), String> {
+    let max_horizontal = components.iter().map(|c| c.horizontal).max().unwrap_or(1);
+    let max_vertical = components.iter().map(|c| c.vertical).max().unwrap_or(1);
+
+    // A scan of one component covers its blocks one at a time; otherwise
+    // each unit covers every component's blocks for a region
+    let (units, blocks_per_unit): (usize, Vec<usize>) = match in_scan {
+        [index] => {
+            let component = &components[*index];
+            let columns = (width * component.horizontal).div_ceil(max_horizontal);
+            let rows = (height * component.vertical).div_ceil(max_vertical);
+            (columns.div_ceil(8) * rows.div_ceil(8), vec![1])
+        }
+        _ => (
+            width.div_ceil(8 * max_horizontal) * height.div_ceil(8 * max_vertical),
+            in_scan
+                .iter()
+                .map(|&index| components[index].horizontal * components[index].vertical)
+                .collect(),
+        ),
+    };
+
+    for unit in 0..units {
+        // Data that ends early would otherwise decode as zeros for as
+        // many blocks as the header claims
+        if bits.padding > MAX_PADDING {
+            return Err("truncated JPEG".to_string());
+        }
+        if restart_interval > 0 && unit > 0 && unit % restart_interval == 0 {
+            bits.restart();
+            for &index in in_scan {
+                components[index].predictor = 0;
+            }
+        }
+        for (&index, &blocks) in in_scan.iter().zip(&blocks_per_unit) {
+            for _ in 0..blocks {
+                let component = &mut components[index];
+                let size = dc_tables[component.dc_table].decode(bits)?;
+                // Differences of 8-bit samples take at most 11 bits
+                if size > 11 {
+                    return Err("invalid JPEG DC coefficient size".to_string());
+                }
+                // Crafted data can keep adding the largest difference
+                component.predictor = component
+                    .predictor
+                    .wrapping_add(extend(bits.bits(size), size));
+                // Skip the AC coefficients, which don't change the mean
+                let mut k = 1;
+                while k < 64 {
+                    let symbol = ac_tables[component.ac_table].decode(bits)?;
+                    let (run, size) = (symbol >> 4, symbol & 15);
+                    if size == 0 {
+                        if run != 15 {
+                            break;
+                        }
+                        k += 16;
+                        continue;
+                    }
+                    bits.bits(size);
+                    k += run as usize + 1;
+                }
+                let dc =
+                    component.predictor as f64 * quantization[component.quantization][0] as f64;
+                component.sum += dc / 8.0;
+                component.blocks += 1.0;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    /// A PNG of `lines`, each a filter byte and its pixels. CRCs are left
+    /// zero, as they aren't checked.
+    fn png_file(width: u32, height: u32, color_type: u8, lines: &[u8]) -> Vec<u8> {
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+            chunk.extend_from_slice(kind);
+            chunk.extend_from_slice(data);
+            chunk.extend_from_slice(&[0; 4]);
+            chunk
+        };
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(lines).unwrap();
+
+        let mut file = PNG_SIGNATURE.to_vec();
+        file.extend(chunk(b"IHDR", &header));
+        file.extend(chunk(b"IDAT", &encoder.finish().unwrap()));
+        file.extend(chunk(b"IEND", &[]));
+        file
+    }
+
+    /// An 8 × 8 grayscale baseline JPEG of a single block whose samples
+    /// are all 200, with `frame` as its start-of-frame marker and
+    /// `dc_size` as the only DC code's symbol.
+    fn jpeg_file(frame: u8, dc_size: u8, dimension: u16) -> Vec<u8> {
+        let mut file = vec![0xff, 0xd8];
+        // Quantization table 0, all ones
+        file.extend_from_slice(&[0xff, 0xdb, 0x00, 0x43, 0x00]);
+        file.extend_from_slice(&[1; 64]);
+        file.extend_from_slice(&[0xff, frame, 0x00, 0x0b, 8]);
+        file.extend_from_slice(&dimension.to_be_bytes());
+        file.extend_from_slice(&dimension.to_be_bytes());
+        file.extend_from_slice(&[1, 1, 0x11, 0]);
+        // One 1-bit code per table: the DC size, and end-of-block
+        for (class, symbol) in [(0x00, dc_size), (0x10, 0x00)] {
+            file.extend_from_slice(&[0xff, 0xc4, 0x00, 0x14, class, 1]);
+            file.extend_from_slice(&[0; 15]);
+            file.push(symbol);
+        }
+        file.extend_from_slice(&[0xff, 0xda, 0x00, 0x08, 1, 1, 0x00, 0, 63, 0]);
+        // DC code, 576 in ten bits, end-of-block, then zeros, which decode
+        // as further empty blocks if the frame claims more
+        file.extend_from_slice(&[0b0100_1000, 0b0000_0000]);
+        file.extend_from_slice(&[0xff, 0xd9]);
+        file
+    }
+
+    #[test]
+    fn averages_png_pixels() {
+        let red_and_blue = [0, 255, 0, 0, 0, 0, 255];
+        assert_eq!(png(&png_file(2, 1, 2, &red_and_blue)), Ok([128, 0, 128]));
+        // The transparent pixel doesn't count
+        let green_and_clear = [0, 0, 255, 0, 255, 255, 255, 255, 0];
+        assert_eq!(png(&png_file(2, 1, 6, &green_and_clear)), Ok([0, 255, 0]));
+    }
+
+    #[test]
+    fn rejects_png_data_beyond_its_dimensions() {
+        let bomb = png_file(1, 1, 0, &vec![0; 1_000_000]);
+        assert_eq!(
+            png(&bomb),
+            Err("PNG data larger than its dimensions".to_string())
+        );
+        let huge = png_file(100_000, 100_000, 6, &[0, 0, 0, 0, 0]);
+        assert_eq!(png(&huge), Err("PNG too large".to_string()));
+    }
+
+    #[test]
+    fn averages_jpeg_blocks() {
+        assert_eq!(jpeg(&jpeg_file(0xc0, 10, 8)), Ok([200, 200, 200]));
+    }
+
+    #[test]
+    fn rejects_progressive_jpegs() {
+        let error = jpeg(&jpeg_file(0xc2, 10, 8)).unwrap_err();
+        assert!(error.contains("progressive"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_oversized_dc_coefficients() {
+        assert_eq!(
+            jpeg(&jpeg_file(0xc0, 15, 8)),
+            Err("invalid JPEG DC coefficient size".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_jpeg_data_shorter_than_its_frame() {
+        assert_eq!(
+            jpeg(&jpeg_file(0xc0, 10, u16::MAX)),
+            Err("truncated JPEG".to_string())
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! A floating panel listing the render context and the template being
+//! rendered, added to served HTML pages before `</body>`. It is opt-in with
+//! `inspector` under `[html]` and never added in `prod`.
+
+use crate::config::Config;
+use crate::render::template_path;
+use handlebars::html_escape;
+use serde_json::{Map, Value};
+
+const STYLE: &str = "<style>
+#hbs-inspector{position:fixed;right:12px;bottom:12px;z-index:2147483647;max-width:min(560px,90vw);max-height:70vh;overflow:auto;background:#1e1e1e;color:#ddd;font:12px/1.5 ui-monospace,monospace;border-radius:6px;box-shadow:0 2px 12px rgba(0,0,0,.4)}
+#hbs-inspector>summary{padding:6px 10px;cursor:pointer;color:#fff}
+#hbs-inspector ul{list-style:none;margin:0;padding-left:14px}
+#hbs-inspector>ul,#hbs-inspector>p{padding:0 10px 8px}
+#hbs-inspector p{margin:0}
+#hbs-inspector summary{cursor:pointer}
+#hbs-inspector .k{color:#9cdcfe}
+#hbs-inspector .v{color:#ce9178}
+#hbs-inspector .n{color:#808080}
+</style>";
+
+/// Only in the `dev` environment: the panel exposes the whole context,
+/// which must never reach a production or unlabelled deployment.
+pub fn is_enabled(config: &Config) -> bool {
+    config.html.inspector && config.is_dev()
+}
+
+/// A scalar as it would appear in JSON, e.g. `"text"`, `3` or `null`.
+fn scalar(value: &Value) -> String {
+    format!(
+        "<span class=\"v\">{}</span>",
+        html_escape(&value.to_string())
+    )
+}
+
+/// `{3}` for an object with three keys, `[3]` for an array of three.
+fn size(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(object) => Some(format!("{{{}}}", object.len())),
+        Value::Array(array) => Some(format!("[{}]", array.len())),
+        _ => None,
+    }
+}
+
+fn push_entry(html: &mut String, key: &str, value: &Value) {
+    let key = format!("<span class=\"k\">{}</span>", html_escape(key));
+    let Some(size) = size(value) else {
+        html.push_str(&format!("<li>{}: {}</li>", key, scalar(value)));
+        return;
+    };
+
+    html.push_str(&format!(
+        "<li><details><summary>{} <span class=\"n\">{}</span></summary><ul>",
+        key, size
+    ));
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                push_entry(html, key, value);
+            }
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                push_entry(html, &index.to_string(), value);
+            }
+        }
+        _ => {}
+    }
+    html.push_str("</ul></details></li>");
+}
+
+/// The panel for a page rendered from `template_name` with `context`:
+/// collapsed to a toggle, with each object and array collapsible in turn.
+/// Built before rendering, since the render consumes the context.
+pub fn panel(template_name: &str, context: &Map<String, Value>) -> String {
+    let mut html = String::from(STYLE);
+    html.push_str(&format!(
+        "<details id=\"hbs-inspector\"><summary>Context: {} keys</summary><p>Template: <span class=\"v\">{}</span></p><ul>",
+        context.len(),
+        html_escape(&template_path(template_name))
+    ));
+    for (key, value) in context {
+        push_entry(&mut html, key, value);
+    }
+    html.push_str("</ul></details>");
+    html
+}
+
+/// Adds the panel before `</body>`, or at the end of the page.
+pub fn inject(mut html: String, panel: &str) -> String {
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, panel),
+        None => html.push_str(panel),
+    }
+    html
+}
@@ -0,0 +1,242 @@
+//! Caps on rendered output size and partial nesting, so recursive partials
+//! or runaway data fail the render instead of exhausting the process.
+
+use crate::config::Config;
+use handlebars::template::TemplateElement;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+    RenderErrorReason, Renderable, Template,
+};
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::io::{self, Write};
+
+/// Internal helper wrapped around every partial call to count nesting.
+const DEPTH_HELPER: &str = "__partialDepth";
+
+thread_local! {
+    /// Partials currently being rendered on this thread. A render never
+    /// moves between threads, so this is the current nesting.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+struct PartialDepthHelper {
+    max_depth: usize,
+}
+
+impl HelperDef for PartialDepthHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let depth = DEPTH.get();
+        if depth >= self.max_depth {
+            return Err(RenderErrorReason::Other(format!(
+                "partials nested more than {} levels (max_partial_depth)",
+                self.max_depth
+            ))
+            .into());
+        }
+        let Some(template) = h.template() else {
+            return Ok(());
+        };
+
+        DEPTH.set(depth + 1);
+        let result = template.render(r, ctx, rc, out);
+        DEPTH.set(depth);
+        result
+    }
+}
+
+/// Wraps each partial call in `template`, at any block depth, in the depth
+/// helper. Every call stays a single element, so error positions still
+/// point at the original line.
+fn wrap_partials(template: &mut Template, wrapper: &TemplateElement) {
+    let name = template.name.clone();
+    for (index, element) in template.elements.iter_mut().enumerate() {
+        match element {
+            TemplateElement::PartialExpression(partial)
+            | TemplateElement::PartialBlock(partial) => {
+                if let Some(block) = &mut partial.template {
+                    wrap_partials(block, wrapper);
+                }
+                let mut body = Template::new();
+                body.name.clone_from(&name);
+                body.mapping.extend(template.mapping.get(index).cloned());
+                let call = std::mem::replace(element, wrapper.clone());
+                body.elements.push(call);
+                if let TemplateElement::HelperBlock(helper) = element {
+                    helper.template = Some(body);
+                }
+            }
+            TemplateElement::DecoratorBlock(decorator) => {
+                if let Some(block) = &mut decorator.template {
+                    wrap_partials(block, wrapper);
+                }
+            }
+            TemplateElement::HelperBlock(helper) => {
+                for block in [&mut helper.template, &mut helper.inverse]
+                    .into_iter()
+                    .flatten()
+                {
+                    wrap_partials(block, wrapper);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Registers the depth helper and rewrites every registered template so its
+/// partial calls go through it, enforcing `max_partial_depth`.
+pub fn guard_partials(handlebars: &mut Handlebars, config: &Config) {
+    handlebars.register_helper(
+        DEPTH_HELPER,
+        Box::new(PartialDepthHelper {
+            max_depth: config.render.max_partial_depth,
+        }),
+    );
+
+    let block = format!("{{{{#{0}}}}}{{{{/{0}}}}}", DEPTH_HELPER);
+    let Some(wrapper) = Template::compile(&block)
+        .ok()
+        .and_then(|template| template.elements.into_iter().next())
+    else {
+        return;
+    };
+
+    let templates: Vec<(String, Template)> = handlebars
+        .get_templates()
+        .iter()
+        .map(|(name, template)| (name.clone(), template.clone()))
+        .collect();
+    for (name, mut template) in templates {
+        wrap_partials(&mut template, &wrapper);
+        // Drop the file source first, otherwise dev mode reloads the raw file
+        handlebars.unregister_template(&name);
+        handlebars.register_template(&name, template);
+    }
+}
+
+/// The error for output that outgrew `max_output_bytes`.
+pub fn output_too_large(limit: usize) -> RenderError {
+    RenderErrorReason::Other(format!(
+        "rendered output exceeds {} bytes (max_output_bytes)",
+        limit
+    ))
+    .into()
+}
+
+/// Collects output, refusing to grow past `limit` bytes.
+struct LimitedWriter {
+    buffer: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() + data.len() > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("output limit reached"));
+        }
+        self.buffer.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders `template_name` like `Handlebars::render`, failing once the
+/// output grows past `max_output_bytes`.
+pub fn render(
+    handlebars: &Handlebars,
+    config: &Config,
+    template_name: &str,
+    context: &Map<String, Value>,
+) -> Result<String, RenderError> {
+    let limit = config.render.max_output_bytes;
+    let mut writer = LimitedWriter {
+        buffer: Vec::new(),
+        limit,
+        exceeded: false,
+    };
+    match handlebars.render_to_write(template_name, context, &mut writer) {
+        Err(_) if writer.exceeded => Err(output_too_large(limit)),
+        result => result.map(|_| String::from_utf8_lossy(&writer.buffer).into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(max_output_bytes: usize, max_partial_depth: usize) -> Config {
+        let mut config = Config::default();
+        config.render.max_output_bytes = max_output_bytes;
+        config.render.max_partial_depth = max_partial_depth;
+        config
+    }
+
+    fn context(value: Value) -> Map<String, Value> {
+        let Value::Object(context) = value else {
+            unreachable!()
+        };
+        context
+    }
+
+    fn nested_partials(max_partial_depth: usize) -> Handlebars<'static> {
+        let mut handlebars = Handlebars::new();
+        for (name, source) in [
+            ("one", "1{{> two}}"),
+            ("two", "2{{#if true}}{{> three}}{{/if}}"),
+            ("three", "3{{> four}}"),
+            ("four", "4"),
+            ("ping", "{{> pong}}"),
+            ("pong", "{{> ping}}"),
+        ] {
+            handlebars.register_template_string(name, source).unwrap();
+        }
+        guard_partials(&mut handlebars, &config(1024, max_partial_depth));
+        handlebars
+    }
+
+    #[test]
+    fn partials_nested_past_the_limit_fail() {
+        let config = config(1024, 3);
+        let empty = context(json!({}));
+        let handlebars = nested_partials(3);
+        assert_eq!(render(&handlebars, &config, "one", &empty).unwrap(), "1234");
+        let error = render(&handlebars, &config, "ping", &empty).unwrap_err();
+        assert!(error.to_string().contains("max_partial_depth"), "{}", error);
+
+        let handlebars = nested_partials(2);
+        let error = render(&handlebars, &config, "one", &empty).unwrap_err();
+        assert!(error.to_string().contains("max_partial_depth"), "{}", error);
+    }
+
+    #[test]
+    fn output_past_the_limit_fails() {
+        let config = config(32, 32);
+        let mut handlebars = Handlebars::new();
+        handlebars
+            .register_template_string("page", "{{#each items}}0123456789{{/each}}")
+            .unwrap();
+
+        let short = context(json!({"items": [1, 2, 3]}));
+        assert_eq!(
+            render(&handlebars, &config, "page", &short).unwrap().len(),
+            30
+        );
+        let long = context(json!({"items": [1, 2, 3, 4]}));
+        let error = render(&handlebars, &config, "page", &long).unwrap_err();
+        assert!(error.to_string().contains("max_output_bytes"), "{}", error);
+    }
+}
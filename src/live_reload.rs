@@ -0,0 +1,151 @@
+use crate::config::Config;
+use actix_web::{HttpResponse, Result, web};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// Polled by the injected script for the current version of the site.
+pub const ENDPOINT: &str = "/_live-reload";
+
+/// Reloads the page once the version served by `ENDPOINT` changes.
+pub const SCRIPT: &str = r#"<script>
+(function () {
+  var version;
+  setInterval(function () {
+    fetch("/_live-reload", { cache: "no-store" })
+      .then(function (response) { return response.text(); })
+      .then(function (latest) {
+        if (version !== undefined && latest !== version) location.reload();
+        version = latest;
+      })
+      .catch(function () {});
+  }, 1000);
+})();
+</script>"#;
+
+/// The most recent modification time below `path`, if anything exists there.
+fn latest_modification<'a>(
+    path: &'a Path,
+) -> Pin<Box<dyn Future<Output = Option<SystemTime>> + 'a>> {
+    Box::pin(async move {
+        let metadata = fs::metadata(path).await.ok()?;
+        let mut latest = metadata.modified().ok();
+
+        if metadata.is_dir() {
+            let mut entries = fs::read_dir(path).await.ok()?;
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let modified = latest_modification(&entry.path()).await;
+                latest = latest.max(modified);
+            }
+        }
+
+        latest
+    })
+}
+
+/// When a template, data file, locale overlay, static file or the globals
+/// file last changed.
+pub async fn site_modified(config: &Config) -> Option<SystemTime> {
+    let mut watched = vec![
+        config.templates_dir(),
+        config.data_dir(),
+        config.locales_dir(),
+        config.static_dir(),
+    ];
+    watched.extend(config.data.globals.clone());
+
+    let mut latest = None;
+    for path in &watched {
+        latest = latest.max(latest_modification(path).await);
+    }
+    latest
+}
+
+/// How often a serving site's sources are scanned for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The latest modification time each site's poll found, by root, in
+/// nanoseconds since the epoch, or 0 when nothing was found.
+static POLLED: Mutex<BTreeMap<PathBuf, Arc<AtomicU64>>> = Mutex::new(BTreeMap::new());
+
+fn to_nanos(time: Option<SystemTime>) -> u64 {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+fn from_nanos(nanos: u64) -> Option<SystemTime> {
+    (nanos != 0).then(|| UNIX_EPOCH + Duration::from_nanos(nanos))
+}
+
+/// `site_modified` as of the site's latest scan, at most `POLL_INTERVAL`
+/// old, so requests don't walk the sources themselves. The first call for a
+/// site scans them and starts a thread scanning them again every interval.
+pub async fn polled_modified(config: &Config) -> Option<SystemTime> {
+    let polled = POLLED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&config.root)
+        .cloned();
+    if let Some(polled) = polled {
+        return from_nanos(polled.load(Ordering::Relaxed));
+    }
+
+    let modified = site_modified(config).await;
+    let mut sites = POLLED.lock().unwrap_or_else(|e| e.into_inner());
+    if sites.contains_key(&config.root) {
+        return modified;
+    }
+    let polled = Arc::new(AtomicU64::new(to_nanos(modified)));
+    sites.insert(config.root.clone(), polled.clone());
+    // A thread of its own outlives the runtime of whichever request came first
+    let config = config.clone();
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                eprintln!(
+                    "Warning: failed to start watching {}: {}",
+                    config.root.display(),
+                    e
+                );
+                return;
+            }
+        };
+        runtime.block_on(async {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let modified = site_modified(&config).await;
+                polled.store(to_nanos(modified), Ordering::Relaxed);
+            }
+        });
+    });
+    modified
+}
+
+/// Responds with a token that changes whenever the site's sources change.
+pub async fn version(config: web::Data<Config>) -> Result<HttpResponse> {
+    // The polled time, so pages reload once the render cache sees the change
+    let token = to_nanos(polled_modified(&config).await);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(token.to_string()))
+}
+
+/// Adds the reload script before `</body>`, or at the end of the page.
+pub fn inject_script(mut html: String) -> String {
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, SCRIPT),
+        None => html.push_str(SCRIPT),
+    }
+    html
+}
@@ -0,0 +1,103 @@
+//! `/llms.txt`, describing the site to AI crawlers in Markdown, routed with
+//! `enabled = true` under `[llms_txt]`. A hand-written file is served as it
+//! is; otherwise one is generated from the config and the pages' front
+//! matter. Outside `prod` only a notice is served, so staging sites don't
+//! invite crawlers.
+
+use crate::config::Config;
+use crate::pages;
+use crate::render;
+use crate::sitemap;
+use crate::sites::{self, Registry};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use handlebars::JsonRender;
+use serde_json::Value;
+use tokio::fs;
+
+pub const ENDPOINT: &str = "/llms.txt";
+
+/// Crawlers may keep the file for an hour.
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+pub fn is_enabled(config: &Config) -> bool {
+    config.llms_txt.enabled
+}
+
+fn text(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, CACHE_CONTROL))
+        .body(body)
+}
+
+/// The site name, for the heading.
+fn site_name(config: &Config) -> &str {
+    config.title.site_name.as_deref().unwrap_or("Site")
+}
+
+pub async fn llms_txt(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    registry: Option<web::Data<Registry>>,
+) -> Result<HttpResponse> {
+    if !config.is_prod() {
+        return Ok(text(format!(
+            "# {}\n\n> This is not a production site (environment: {}), not meant to be used as a source.\n",
+            site_name(&config),
+            config.environment_name()
+        )));
+    }
+
+    let path = config.root.join(&config.llms_txt.file);
+    if let Ok(content) = fs::read_to_string(&path).await {
+        return Ok(text(content));
+    }
+
+    let site = match sites::site(&config, registry.as_ref()).await {
+        Ok(site) => site,
+        Err(e) => {
+            eprintln!("Failed to register templates: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load templates",
+                &e,
+            )));
+        }
+    };
+
+    let mut body = format!("# {}\n", site_name(&config));
+    if let Some(description) = &config.llms_txt.description {
+        body.push_str(&format!("\n> {}\n", description.trim()));
+    }
+
+    // The listed pages, in `_pages` order, that search engines may index too
+    let base_url = sitemap::base_url(&req, &config);
+    let listed = pages::pages_context(&site.pages, config.routing.trailing_slash);
+    let mut links = String::new();
+    for page in listed.as_array().into_iter().flatten() {
+        let meta = &page["meta"];
+        let flag = |name: &str| meta.get(name).and_then(Value::as_bool);
+        if flag("noindex") == Some(true)
+            || flag("sitemap") == Some(false)
+            || flag("llms") == Some(false)
+        {
+            continue;
+        }
+        links.push_str(&format!(
+            "- [{}]({}{})",
+            page["title"].render(),
+            base_url,
+            page["url"].as_str().unwrap_or_default()
+        ));
+        if let Some(description) = meta.get("description").and_then(Value::as_str) {
+            links.push_str(&format!(": {}", description.trim()));
+        }
+        links.push('\n');
+    }
+    if !links.is_empty() {
+        body.push_str(&format!("\n## Pages\n\n{}", links));
+    }
+
+    Ok(text(body))
+}
@@ -0,0 +1,101 @@
+use crate::config::I18nConfig;
+use actix_web::HttpRequest;
+use actix_web::http::header;
+use serde_json::{Value, json};
+
+/// Cookie that remembers a visitor's choice, e.g. from a language switcher.
+const COOKIE: &str = "lang";
+
+/// The configured spelling of `code`, if it is available. Case-insensitive.
+fn available<'a>(config: &'a I18nConfig, code: &str) -> Option<&'a str> {
+    config
+        .locales
+        .iter()
+        .find(|locale| locale.eq_ignore_ascii_case(code))
+        .map(String::as_str)
+}
+
+/// Splits a locale prefix off a page path: `de/about` → `(Some("de"), "about")`.
+/// A bare prefix such as `de` leaves `default_page`.
+pub fn split_prefix<'a>(
+    config: &I18nConfig,
+    path: &'a str,
+    default_page: &'a str,
+) -> (Option<&'a str>, &'a str) {
+    let (first, rest) = path.split_once('/').unwrap_or((path, ""));
+    match available(config, first) {
+        Some(_) if rest.is_empty() => (Some(first), default_page),
+        Some(_) => (Some(first), rest),
+        None => (None, path),
+    }
+}
+
+/// The most preferred available locale in an `Accept-Language` value. A tag
+/// matches exactly or by language, so `de-AT` picks `de` and `pt` picks `pt-BR`.
+fn negotiate<'a>(config: &'a I18nConfig, accept_language: &str) -> Option<&'a str> {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so equal weights keep the header's order
+    ranges.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let language = |tag: &str| tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+    ranges.into_iter().find_map(|(tag, _)| {
+        available(config, tag).or_else(|| {
+            config
+                .locales
+                .iter()
+                .find(|locale| language(locale) == language(tag))
+                .map(String::as_str)
+        })
+    })
+}
+
+/// Picks the locale for a request: the URL prefix, then the `lang` cookie,
+/// then `Accept-Language`, then the default.
+pub fn resolve<'a>(req: &HttpRequest, config: &'a I18nConfig, url_locale: Option<&str>) -> &'a str {
+    url_locale
+        .and_then(|code| available(config, code))
+        .or_else(|| {
+            req.cookie(COOKIE)
+                .and_then(|cookie| available(config, cookie.value()))
+        })
+        .or_else(|| {
+            req.headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| negotiate(config, value))
+        })
+        .unwrap_or_else(|| config.default_locale())
+}
+
+/// The `locales` context value: every available locale with the URL of the
+/// current page in it, for a language switcher. The default page is at the
+/// bare prefix.
+pub fn locales_context(config: &I18nConfig, active: &str, page: &str, default_page: &str) -> Value {
+    let path = match page == default_page {
+        true => String::new(),
+        false => format!("/{}", page),
+    };
+    Value::Array(
+        config
+            .locales
+            .iter()
+            .map(|code| {
+                json!({
+                    "code": code,
+                    "url": format!("/{}{}", code, path),
+                    "active": code == active,
+                })
+            })
+            .collect(),
+    )
+}
@@ -1,189 +1,1043 @@
-use actix_web::middleware;
-use actix_web::{App, HttpResponse, HttpServer, Result, middleware::Logger, web};
-use handlebars::{DirectorySourceOptions, Handlebars};
-use serde_json::{Map, Value};
-use std::collections::HashMap;
-use std::future::Future;
-use std::path::Path;
-use std::pin::Pin;
-use tokio::fs;
-
-type LoadDirectoryRecursiveResult = Result<Map<String, Value>, Box<dyn std::error::Error>>;
-
-fn load_directory_recursive(
-    dir_path: &Path,
-) -> Pin<Box<dyn Future<Output = LoadDirectoryRecursiveResult> + '_>> {
-    Box::pin(async move {
-        let mut data = Map::new();
-
-        if !dir_path.exists() {
-            return Ok(data);
-        }
-
-        let mut entries = fs::read_dir(dir_path).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            let metadata = entry.metadata().await?;
-
-            if metadata.is_dir() {
-                // Recursively load subdirectory
-                if let Some(dir_name) = path.file_name() {
-                    let key = dir_name.to_string_lossy().to_string();
-                    match load_directory_recursive(&path).await {
-                        Ok(subdir_data) => {
-                            data.insert(key, Value::Object(subdir_data));
-                            println!("Loaded directory: {}", path.display());
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to load directory {}: {}", path.display(), e);
-                        }
-                    }
+mod access_log;
+mod admin;
+mod auth;
+mod browser;
+mod build;
+mod cache_stats;
+mod cli;
+mod compress;
+mod config;
+mod data;
+mod debug;
+mod derived;
+mod fallback;
+mod flags;
+mod forms;
+mod fragments;
+mod front_matter;
+mod headings;
+mod health;
+mod helpers;
+mod image_color;
+mod inspector;
+mod limits;
+mod live_reload;
+mod llms_txt;
+mod locale;
+mod maintenance;
+mod manifest;
+mod metrics;
+mod pages;
+mod post_process;
+mod preview;
+mod rate_limit;
+mod remote_partials;
+mod render;
+mod render_api;
+mod render_cache;
+mod signed_urls;
+mod sitemap;
+mod sites;
+mod slow_renders;
+#[cfg(unix)]
+mod socket;
+mod static_files;
+mod streaming;
+mod summary;
+mod template_cache;
+mod tls;
+mod transforms;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::middleware::{self, TrailingSlash};
+use actix_web::{App, HttpRequest, HttpResponse, HttpResponseBuilder, HttpServer, Result, web};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{Config, RootPolicy};
+use data::{DataError, Globals};
+use health::Health;
+use pages::CachePolicy;
+use post_process::RenderedPage;
+use rate_limit::RateLimiter;
+use render::Site;
+use render_cache::{CachedPage, Lookup, RenderCache};
+use serde_json::Value;
+use sites::Registry;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Instant;
+
+async fn render_page(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<Config>,
+    globals: web::Data<Globals>,
+    cache: web::Data<RenderCache>,
+    health: web::Data<Health>,
+    registry: Option<web::Data<Registry>>,
+) -> Result<HttpResponse> {
+    // An empty path is the default page, `index` unless configured
+    let page = path.into_inner();
+    let page = match page.is_empty() {
+        true => config.routing.default_page().to_string(),
+        false => page,
+    };
+    // The keyed query parameters reach templates through `query`, so they
+    // are part of the key
+    let cache_key = render_cache::key(&config.render, &page, req.query_string());
+
+    let has_trailing_slash = page.ends_with('/');
+    let path = page.trim_end_matches('/');
+    let (url_locale, page) =
+        locale::split_prefix(&config.i18n, path, config.routing.default_page());
+    let i18n = config.i18n.is_enabled();
+    let active_locale = match i18n {
+        true => locale::resolve(&req, &config.i18n, url_locale),
+        false => "",
+    };
+
+    let mut response = HttpResponse::Ok();
+    // Without a URL prefix the locale depends on the cookie and header
+    if i18n && url_locale.is_none() {
+        response.insert_header((header::VARY, "Accept-Language, Cookie"));
+    }
+
+    // Serve a previous render while the sources are unchanged. The
+    // inspector's panel shows the context of each request, so it skips this.
+    let cache_version = match config.render.cache && !inspector::is_enabled(&config) {
+        true => Some(render_cache::version(&config).await),
+        false => None,
+    };
+    // An expired render is refreshed by one request; the others wait for it
+    // or get the expired render, and are answered from the cache too
+    let _refresh = match cache_version {
+        Some(version) => {
+            let on_expiry = config.render.on_expiry;
+            match cache
+                .lookup(version, &cache_key, active_locale, on_expiry)
+                .await
+            {
+                Lookup::Hit(cached) => return Ok(cached_response(&req, response, cached, "hit")),
+                Lookup::Stale(cached) => {
+                    return Ok(cached_response(&req, response, cached, "stale"));
                 }
-            } else if metadata.is_file() {
-                // Process files
-                if let Some(extension) = path.extension()
-                    && let Some(file_stem) = path.file_stem()
-                {
-                    let key = file_stem.to_string_lossy().to_string();
-
-                    match extension.to_string_lossy().as_ref() {
-                        "json" => match fs::read_to_string(&path).await {
-                            Ok(content) => match serde_json::from_str::<Value>(&content) {
-                                Ok(json_value) => {
-                                    data.insert(key, json_value);
-                                    println!("Loaded JSON file: {}", path.display());
-                                }
-                                Err(e) => {
-                                    eprintln!(
-                                        "Failed to parse JSON file {}: {}",
-                                        path.display(),
-                                        e
-                                    );
-                                }
-                            },
-                            Err(e) => {
-                                eprintln!("Failed to read file {}: {}", path.display(), e);
-                            }
-                        },
-                        "txt" => match fs::read_to_string(&path).await {
-                            Ok(content) => {
-                                data.insert(key, Value::String(content));
-                                println!("Loaded text file: {}", path.display());
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to read file {}: {}", path.display(), e);
-                            }
-                        },
-                        _ => {
-                            // Ignore files with other extensions
-                        }
-                    }
+                Lookup::Miss(refresh) => {
+                    response.insert_header((render_cache::HEADER, "miss"));
+                    refresh
                 }
             }
         }
+        None => None,
+    };
 
-        Ok(data)
-    })
-}
-
-async fn load_data_files() -> Result<HashMap<String, Value>, Box<dyn std::error::Error>> {
-    let data_dir = Path::new("data");
+    let site = match sites::site(&config, registry.as_ref()).await {
+        Ok(site) => {
+            health.templates_loaded();
+            site
+        }
+        Err(e) => {
+            eprintln!("Failed to register templates: {}", e);
+            health.templates_failed(&e);
+            metrics::count_error(metrics::ErrorKind::TemplateLoad);
+            return Ok(render::error_response(
+                &config,
+                &req,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "templates_failed",
+                "Failed to load templates",
+                &e,
+            ));
+        }
+    };
 
-    if !data_dir.exists() {
-        println!("Data directory 'data/' does not exist, creating empty context");
-        return Ok(HashMap::new());
+    // Retired pages are answered before any template is looked up
+    if config.routing.gone.contains(&format!("/{}", page)) {
+        return Ok(render_gone(&req, &config, &globals, &site, page).await);
     }
 
-    let data_map = load_directory_recursive(data_dir).await?;
+    // `/amp/article` and `/article?amp=1` render the AMP variant of a page
+    // that has one, with the page's data; other requests, and pages without
+    // a variant, resolve as usual
+    let amp_page = match page.strip_prefix(pages::AMP_PATH) {
+        Some("") => Some(config.routing.default_page()),
+        Some(rest) => rest.strip_prefix('/'),
+        None => wants_amp(&req).then_some(page),
+    };
+    let amp_resolved = amp_page.and_then(|amp_page| {
+        let (template_name, is_index) = pages::resolve_template(&site.handlebars, amp_page)?;
+        let variant = format!("{}{}", template_name, pages::AMP_SUFFIX);
+        site.handlebars
+            .has_template(&variant)
+            .then_some((amp_page, (variant, is_index)))
+    });
 
-    // Convert Map<String, Value> to HashMap<String, Value>
-    let mut data = HashMap::new();
-    for (key, value) in data_map {
-        data.insert(key, value);
+    // Resolve the template, falling back to the directory index
+    let (page, resolved) = match amp_resolved {
+        Some((amp_page, variant)) => (amp_page, Some(variant)),
+        None => (page, pages::resolve_template(&site.handlebars, page)),
+    };
+    // Variants are only served at their page's AMP URL
+    if pages::is_amp(page) {
+        metrics::label_route(&req, metrics::NOT_FOUND_ROUTE);
+        let message = format!("Page '{}' not found", page);
+        let reason = "AMP variants are served at /amp/<page> or with ?amp=1";
+        return Ok(render::error_response(
+            &config,
+            &req,
+            StatusCode::NOT_FOUND,
+            "not_found",
+            &message,
+            &reason,
+        ));
+    }
+    match &resolved {
+        Some((template_name, _)) => metrics::label_route(&req, template_name),
+        None => metrics::label_route(&req, metrics::NOT_FOUND_ROUTE),
     }
 
-    Ok(data)
-}
+    // Redirect to the canonical form of the URL. The home page never gets a
+    // trailing slash, as `/` already redirects to it
+    let wants_trailing_slash = resolved.as_ref().and_then(|(_, is_index)| {
+        let wants = config.routing.trailing_slash.wants_slash(*is_index)?;
+        Some(wants && page != config.routing.default_page())
+    });
+    if let Some(wants_trailing_slash) = wants_trailing_slash
+        && config.routing.redirect
+        && has_trailing_slash != wants_trailing_slash
+    {
+        let mut location = format!("/{}", path);
+        if wants_trailing_slash {
+            location.push('/');
+        }
+        if !req.query_string().is_empty() {
+            location = format!("{}?{}", location, req.query_string());
+        }
+        return Ok(HttpResponse::MovedPermanently()
+            .insert_header((header::LOCATION, location))
+            .finish());
+    }
 
-async fn render_page(
-    path: web::Path<String>,
-    _hb: web::Data<Handlebars<'_>>,
-) -> Result<HttpResponse> {
-    // Get page name, default to "index" if None
-    let page = path.into_inner();
-    let page = match page.is_empty() {
-        true => "index".to_string(),
-        false => page,
+    // Template path
+    let page_exists = resolved.is_some();
+    let template_name = match resolved {
+        Some((template_name, _)) => template_name,
+        None => format!("{}/{}", pages::PAGES_DIR, page),
     };
 
-    // Create a fresh Handlebars instance for this request
-    let mut handlebars = Handlebars::new();
-    handlebars.set_dev_mode(true);
-
-    // Register all templates from the templates directory
-    if let Err(e) =
-        handlebars.register_templates_directory("templates", DirectorySourceOptions::default())
-    {
-        eprintln!("Failed to register templates: {}", e);
-        return Ok(HttpResponse::InternalServerError().body("Failed to load templates"));
+    // Front matter may declare a non-HTML type, which skips HTML post-processing
+    let (content_type, is_html, cache_policy) = match site.page(&template_name) {
+        Some(page) => (
+            page.content_type().to_string(),
+            page.is_html(),
+            page.cache_policy(config.render.max_age),
+        ),
+        None => (
+            pages::DEFAULT_CONTENT_TYPE.to_string(),
+            true,
+            CachePolicy::NoStore,
+        ),
+    };
+    let cache_control = cache_policy.header();
+    if let Some(cache_control) = &cache_control {
+        response.insert_header((header::CACHE_CONTROL, cache_control.clone()));
     }
 
     // Load all data files
-    let data = match load_data_files().await {
-        Ok(data) => data,
+    let started = Instant::now();
+    let loaded = site
+        .context_with_problems(&config, &globals, page, active_locale)
+        .await;
+    let data_load = started.elapsed();
+    metrics::observe_data_load(data_load);
+    let mut context = match loaded {
+        Ok((context, problems)) => {
+            health.data_loaded(problems);
+            context
+        }
         Err(e) => {
             eprintln!("Failed to load data files: {}", e);
-            return Ok(HttpResponse::InternalServerError().body("Failed to load data files"));
+            health.data_failed(&e);
+            metrics::count_error(metrics::ErrorKind::DataError);
+            if let DataError::Unavailable(_) = e {
+                return Ok(render_unavailable(&req, &config, &globals, &site, page, &e));
+            }
+            return Ok(render::error_response(
+                &config,
+                &req,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "data_failed",
+                "Failed to load data files",
+                &e,
+            ));
         }
     };
 
-    // Convert HashMap to serde_json::Map for template context
-    let mut context = Map::new();
-    for (key, value) in data {
-        context.insert(key, value);
+    // A cached render is served whatever the other parameters are, so it
+    // must not depend on them
+    let mut query = render::query_context(&req);
+    if cache_version.is_some() && cache_policy.is_cacheable() {
+        render_cache::retain_keyed(&config.render, &mut query);
+    }
+    render::insert_reserved(&mut context, &config, "query", query);
+    if let Some(flags) = context.get_mut(&format!("{}flags", config.reserved_prefix())) {
+        flags::apply_query(&config, &req, flags);
+    }
+    if i18n {
+        let locales = locale::locales_context(
+            &config.i18n,
+            active_locale,
+            page,
+            config.routing.default_page(),
+        );
+        render::insert_reserved(&mut context, &config, "locale", Value::from(active_locale));
+        render::insert_reserved(&mut context, &config, "locales", locales);
     }
 
-    // Template path
-    let template_name = format!("pages/{}", page);
+    let panel = match inspector::is_enabled(&config) && is_html {
+        true => Some(inspector::panel(&template_name, &context)),
+        false => None,
+    };
 
-    // Render the template
-    match handlebars.render(&template_name, &context) {
-        Ok(rendered) => Ok(HttpResponse::Ok()
-            .content_type("text/html; charset=utf-8")
-            .body(rendered)),
-        Err(e) => {
+    // Render the template, streaming large output when enabled
+    let started = Instant::now();
+    let handlebars = site
+        .scoped_registry(&template_name)
+        .map_or_else(|| site.handlebars.clone(), Arc::new);
+    let rendered = match streaming::is_streamable(&config) && page_exists && is_html {
+        true => streaming::render(handlebars, template_name.clone(), context, &config).await,
+        false => limits::render(&handlebars, &config, &template_name, &context)
+            .map(streaming::Rendered::Complete),
+    };
+    response.content_type(content_type.clone());
+
+    let rendered = match rendered {
+        Ok(streaming::Rendered::Complete(rendered)) => rendered,
+        Ok(streaming::Rendered::Streaming(body)) => {
+            metrics::observe_render(started.elapsed());
+            slow_renders::check(&config, &req, &template_name, data_load, started.elapsed());
+            return Ok(response.streaming(body));
+        }
+        Err(e) if !page_exists => {
+            metrics::count_error(metrics::ErrorKind::TemplateMissing);
             eprintln!("Template rendering error for '{}': {}", template_name, e);
-            Ok(HttpResponse::NotFound()
-                .body(format!("Template '{}' not found or rendering failed", page)))
+            let message = format!("Template '{}' not found or rendering failed", page);
+            return Ok(render::error_response(
+                &config,
+                &req,
+                StatusCode::NOT_FOUND,
+                "not_found",
+                &message,
+                &e,
+            ));
+        }
+        Err(e) if render::prefers_json(&req) => {
+            metrics::count_error(metrics::ErrorKind::RenderError);
+            return Ok(render::render_failure_json(
+                &config,
+                &req,
+                &template_name,
+                &e,
+            ));
+        }
+        Err(e) => {
+            metrics::count_error(metrics::ErrorKind::RenderError);
+            return Ok(render::render_failure(&config, &template_name, &e));
         }
+    };
+
+    if config.fail_on_empty() && rendered.trim().is_empty() {
+        metrics::count_error(metrics::ErrorKind::RenderError);
+        return Ok(render::empty_output(&config, &req, &template_name));
+    }
+
+    let page = RenderedPage {
+        template_name: &template_name,
+        content_type: &content_type,
+        panel: panel.as_deref(),
+    };
+    let rendered = render::post_process(&config, &page, rendered);
+    let render = started.elapsed();
+    metrics::observe_render(render);
+    slow_renders::check(&config, &req, &template_name, data_load, render);
+
+    if let Some(version) = cache_version
+        && cache_policy.is_cacheable()
+    {
+        let cached = CachedPage {
+            template_name,
+            content_type,
+            body: rendered.clone(),
+            cache_control,
+            expires: cache_policy.ttl().map(|ttl| Instant::now() + ttl),
+        };
+        cache.insert(version, &cache_key, active_locale, cached);
+    }
+
+    Ok(response.body(rendered))
+}
+
+/// Serves a render from the cache, reporting `lookup` in `X-Render-Cache`.
+fn cached_response(
+    req: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    cached: CachedPage,
+    lookup: &str,
+) -> HttpResponse {
+    metrics::label_route(req, &cached.template_name);
+    if let Some(cache_control) = cached.cache_control {
+        response.insert_header((header::CACHE_CONTROL, cache_control));
+    }
+    response
+        .content_type(cached.content_type)
+        .insert_header((render_cache::HEADER, lookup))
+        .body(cached.body)
+}
+
+/// Whether the query asks for the AMP variant, as `amp=1` or a bare `amp`.
+fn wants_amp(req: &HttpRequest) -> bool {
+    req.query_string()
+        .split('&')
+        .any(|pair| pair == "amp" || pair == "amp=1")
+}
+
+/// A 410 Gone for a retired page, rendering `pages/410.hbs` with the page's
+/// context when it exists.
+async fn render_gone(
+    req: &HttpRequest,
+    config: &Config,
+    globals: &Globals,
+    site: &Site,
+    page: &str,
+) -> HttpResponse {
+    let message = format!("Page '{}' has been removed", page);
+    let reason = "The path is listed under gone in [routing]";
+    let template_name = format!("{}/{}", pages::PAGES_DIR, pages::GONE_PAGE);
+    if render::prefers_json(req) || !site.handlebars.has_template(&template_name) {
+        return render::error_response(config, req, StatusCode::GONE, "gone", &message, &reason);
+    }
+
+    let rendered = match site.context(config, globals, page).await {
+        Ok(context) => limits::render(&site.handlebars, config, &template_name, &context)
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match rendered {
+        Ok(rendered) => HttpResponse::Gone()
+            .content_type(pages::DEFAULT_CONTENT_TYPE)
+            .body(render::post_process(
+                config,
+                &RenderedPage::html(&template_name),
+                rendered,
+            )),
+        Err(e) => {
+            eprintln!("Failed to render '{}': {}", template_name, e);
+            render::error_response(config, req, StatusCode::GONE, "gone", &message, &e)
+        }
+    }
+}
+
+/// A 503 for a page whose data is unavailable, rendering `pages/503.hbs`
+/// with the globals when it exists. `Retry-After` and `no-store` tell
+/// clients and caches to try again rather than keep the failure.
+fn render_unavailable(
+    req: &HttpRequest,
+    config: &Config,
+    globals: &Globals,
+    site: &Site,
+    page: &str,
+    error: &DataError,
+) -> HttpResponse {
+    let message = format!("Data for '{}' is temporarily unavailable", page);
+    let template_name = format!("{}/{}", pages::PAGES_DIR, pages::UNAVAILABLE_PAGE);
+    let rendered = match render::prefers_json(req) || !site.handlebars.has_template(&template_name)
+    {
+        true => None,
+        false => {
+            let context = render::globals_context(config, globals, page);
+            match limits::render(&site.handlebars, config, &template_name, &context) {
+                Ok(rendered) => Some(rendered),
+                Err(e) => {
+                    eprintln!("Failed to render '{}': {}", template_name, e);
+                    None
+                }
+            }
+        }
+    };
+
+    let mut response = match rendered {
+        Some(rendered) => HttpResponse::ServiceUnavailable()
+            .content_type(pages::DEFAULT_CONTENT_TYPE)
+            .body(render::post_process(
+                config,
+                &RenderedPage::html(&template_name),
+                rendered,
+            )),
+        None => render::error_response(
+            config,
+            req,
+            StatusCode::SERVICE_UNAVAILABLE,
+            "data_unavailable",
+            &message,
+            error,
+        ),
+    };
+    let headers = response.headers_mut();
+    headers.insert(
+        header::RETRY_AFTER,
+        header::HeaderValue::from(config.server.retry_after),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-store"),
+    );
+    response
+}
+
+/// Renders `smoke_test_page` as it would be served, logging the outcome.
+/// Returns whether it rendered.
+async fn smoke_test(config: &Config, globals: &Globals) -> bool {
+    let page = config
+        .server
+        .smoke_test_page
+        .as_deref()
+        .unwrap_or(config.routing.default_page());
+    let page = format!("/{}", page.trim_matches('/'));
+    let site = match config.root.as_os_str().is_empty() {
+        true => String::new(),
+        false => format!(" of {}", config.root.display()),
+    };
+    match build::render_page(config, globals, &page).await {
+        Ok(rendered) => {
+            println!(
+                "Smoke test: rendered {}{} ({} bytes)",
+                page,
+                site,
+                rendered.len()
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("Smoke test failed: rendering {}{}: {}", page, site, e);
+            false
+        }
+    }
+}
+
+/// The routes every site serves, for the site described by `config`.
+fn site_routes(cfg: &mut web::ServiceConfig, config: &Config) {
+    cfg.route(health::ENDPOINT, web::get().to(health::ready));
+    if static_files::needs_handler(&config.static_files) {
+        cfg.service(
+            web::resource("/static/{path:.*}")
+                .route(web::get().to(static_files::serve))
+                .route(web::head().to(static_files::serve)),
+        );
+    } else {
+        cfg.service(
+            actix_files::Files::new("/static", config.static_dir())
+                .prefer_utf8(true)
+                .use_last_modified(config.static_files.last_modified),
+        );
+    }
+    if config.live_reload() {
+        cfg.route(live_reload::ENDPOINT, web::get().to(live_reload::version));
+    }
+    if config.manifest.is_some() {
+        cfg.route(manifest::ENDPOINT, web::get().to(manifest::manifest));
+    }
+    if admin::is_enabled(config) {
+        cfg.route(admin::RELOAD_ENDPOINT, web::post().to(admin::reload));
+        cfg.route(admin::SELFTEST_ENDPOINT, web::get().to(admin::selftest));
+        cfg.route(
+            cache_stats::DEBUG_ENDPOINT,
+            web::get().to(cache_stats::debug),
+        );
+        cfg.route(
+            slow_renders::DEBUG_ENDPOINT,
+            web::get().to(slow_renders::debug),
+        );
+    }
+    if metrics::is_enabled(config) {
+        cfg.route(metrics::ENDPOINT, web::get().to(metrics::metrics));
+    }
+    if debug::is_enabled(config) {
+        cfg.route(debug::DATA_ENDPOINT, web::get().to(debug::data));
+    }
+    if preview::is_enabled(config) {
+        cfg.service(
+            web::resource(preview::ENDPOINT)
+                .app_data(web::JsonConfig::default().limit(preview::BODY_LIMIT))
+                .route(web::post().to(preview::preview)),
+        );
+    }
+    if render_api::is_enabled(config) {
+        cfg.service(
+            web::resource(render_api::ENDPOINT)
+                .app_data(web::JsonConfig::default().limit(render_api::BODY_LIMIT))
+                .route(web::post().to(render_api::render)),
+        );
+    }
+    cfg.route("/sitemap.xml", web::get().to(sitemap::sitemap));
+    if llms_txt::is_enabled(config) {
+        cfg.route(llms_txt::ENDPOINT, web::get().to(llms_txt::llms_txt));
+    }
+
+    // Rendering at `/` lets the page route match it with an empty name
+    let pattern = match &config.routing.root {
+        RootPolicy::Render => "/{page:.*}",
+        RootPolicy::Redirect(_) => "/{page:.+}",
+    };
+    cfg.service(
+        web::resource(pattern)
+            .route(web::get().to(render_page))
+            .route(web::post().to(forms::handle_form)),
+    );
+    if let Some(target) = config.routing.root_redirect() {
+        let status = StatusCode::from_u16(config.root_status().code())
+            .expect("redirect statuses are valid status codes");
+        cfg.service(web::Redirect::new("/", target).using_status_code(status));
     }
 }
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
+    let mut config = match Config::load().await {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config file {}: {}", config::CONFIG_FILE, e);
+            std::process::exit(1);
+        }
+    };
+
     // Initialize logger
-    env_logger::init();
+    if let Err(e) = access_log::validate(&config.access_log) {
+        eprintln!("Invalid access log exclude pattern: {}", e);
+        std::process::exit(1);
+    }
+    access_log::init(&config.access_log);
+
+    if let Some(workers) = cli.workers {
+        config.server.workers = Some(workers);
+    }
+    if let Some(path) = cli.bind_uds {
+        config.server.uds = Some(path);
+    }
+    if let Err(e) = config.server.validate() {
+        eprintln!("Invalid [server] settings: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = helpers::validate_declared(&config) {
+        eprintln!("Invalid [[helpers]] settings: {}", e);
+        std::process::exit(1);
+    }
+    if cli.compress {
+        config.build.compress = true;
+    }
+    for (key, path) in &cli.data_files {
+        match data::load_value_file(path).await {
+            Ok(value) => {
+                config.data.overrides.insert(key.clone(), value);
+            }
+            Err(e) => {
+                eprintln!("Failed to load data file {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    for (key, value) in cli.data {
+        config.data.overrides.insert(key, value);
+    }
+    if cli.stdin {
+        if cli.command.is_none() && cli.build.is_none() {
+            eprintln!("Error: --stdin only works with render and --build");
+            std::process::exit(1);
+        }
+        match data::load_stdin() {
+            Ok(values) => data::merge_map(&mut config.data.overrides, values),
+            Err(e) => {
+                eprintln!("Failed to read the context from stdin: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let globals = match Globals::load(&config.data).await {
+        Ok(globals) => globals,
+        Err(e) => {
+            eprintln!("Failed to load globals file: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Every registry includes them, so they are needed before any render
+    let failed = remote_partials::refresh(&config).await;
+    if !failed.is_empty() {
+        for (name, e) in &failed {
+            eprintln!("Failed to load remote partial '{}': {}", name, e);
+        }
+        std::process::exit(1);
+    }
+
+    let host_sites = match sites::load(&config).await {
+        Ok(host_sites) => host_sites,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // `[[sites]]` sites check theirs when their registry is built
+    fallback::check_overlays(&config).await;
+
+    // Catch a wrong working directory before every request 404s
+    let mut warnings = Vec::new();
+    let site_configs = std::iter::once(&config).chain(host_sites.iter().map(|site| &**site.config));
+    for site_config in site_configs {
+        match pages::check_pages_dir(&site_config.templates_dir()).await {
+            Ok(_) => warnings.extend(pages::check_default_page(
+                &site_config.templates_dir(),
+                site_config.routing.default_page(),
+            )),
+            Err(e) if config.server.require_pages => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            Err(e) => warnings.push(e),
+        }
+        warnings.extend(static_files::warnings(&site_config.static_files));
+        if let Some(app_manifest) = &site_config.manifest {
+            warnings.extend(manifest::warnings(app_manifest, &site_config.static_dir()));
+        }
+    }
+
+    // Print one page instead of serving; stdout carries only the page
+    if let Some(Command::Render { page }) = &cli.command {
+        for warning in &warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        match build::render_page(&config, &globals, page).await {
+            // A closed pipe, as with `| head`, is not a failure
+            Ok(rendered) => {
+                return match std::io::stdout().write_all(rendered.as_bytes()) {
+                    Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e),
+                    _ => Ok(()),
+                };
+            }
+            Err(e) => {
+                eprintln!("Failed to render '{}': {}", page, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Static export instead of serving
+    if let Some(out_dir) = &cli.build {
+        for warning in &warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        if cli.watch {
+            build::watch(&config, out_dir).await;
+            return Ok(());
+        }
+        if let Err(e) = build::build(&config, &globals, out_dir).await {
+            eprintln!("Build failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let tcp_address = config.server.tcp_address().map(str::to_string);
+    let uds = config.server.uds.clone();
+    let workers = config
+        .server
+        .workers
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+
+    let tls = match &config.server.tls {
+        Some(tls_config) => match tls::acceptor(tls_config) {
+            Ok(acceptor) => Some((tls_config.address.clone(), acceptor)),
+            Err(e) => {
+                eprintln!("Failed to load the [server.tls] certificate or key: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-    // Create a dummy Handlebars instance (we'll create fresh ones per request)
-    let handlebars = Handlebars::new();
+    let mut listening = Vec::new();
+    if let Some(address) = &tcp_address {
+        listening.push(match config.server.h2c {
+            true => format!("http://{} (HTTP/1.1 and h2c)", address),
+            false => format!("http://{}", address),
+        });
+    }
+    if let Some(tls) = &config.server.tls {
+        listening.push(format!("https://{} (HTTP/1.1 and h2)", tls.address));
+    }
+    if let Some(path) = &uds {
+        listening.push(format!("unix:{}", path.display()));
+    }
+    let templates = template_cache::template_names(&config).await;
+    summary::print(&config, &listening, workers, &templates, warnings).await;
 
-    println!("Server starting on http://127.0.0.1:8080");
-    println!("Templates directory: ./templates/");
-    println!("Data directory: ./data/");
-    println!("Auto-detecting new templates and data files on each request");
+    // Surface a broken homepage now rather than on the first request
+    if config.server.smoke_test {
+        let sites = std::iter::once((&config, &globals)).chain(
+            host_sites
+                .iter()
+                .map(|site| (&**site.config, &**site.globals)),
+        );
+        let mut passed = true;
+        for (site_config, site_globals) in sites {
+            passed &= smoke_test(site_config, site_globals).await;
+        }
+        if !passed && config.server.require_smoke_test {
+            std::process::exit(1);
+        }
+    }
 
     // Create and run the HTTP server
-    HttpServer::new(move || {
+    let uds_mode = config.server.uds_mode.clone();
+    let h2c = config.server.h2c;
+    let render_cache = web::Data::new(RenderCache::new(config.render.cache_capacity));
+    let health = web::Data::new(Health::default());
+    health.check(&config, &templates).await;
+    let rate_limiter = RateLimiter::new(&config.rate_limit).map(web::Data::new);
+    let app = move || {
         App::new()
-            .app_data(web::Data::new(handlebars.clone()))
-            .wrap(Logger::default())
-            .wrap(middleware::NormalizePath::trim())
-            .service(actix_files::Files::new("/static", "./static").prefer_utf8(true))
-            .route("/{page}", web::get().to(render_page))
-            .service(web::Redirect::new("/", "/index").permanent())
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+            .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::new(globals.clone()))
+            .app_data(render_cache.clone())
+            .app_data(health.clone())
+            .wrap(middleware::from_fn(maintenance::check))
+            .wrap(middleware::Condition::new(
+                rate_limiter.is_some(),
+                middleware::from_fn(rate_limit::limit),
+            ))
+            .wrap(middleware::Condition::new(
+                metrics::is_enabled(&config),
+                middleware::from_fn(metrics::record),
+            ))
+            .wrap(access_log::logger(&config))
+            .wrap(middleware::NormalizePath::new(TrailingSlash::MergeOnly))
+            .configure(|cfg| {
+                if let Some(rate_limiter) = &rate_limiter {
+                    cfg.app_data(rate_limiter.clone());
+                }
+            })
+            .configure(|cfg| {
+                // Each host's site shadows the default site's data and routes
+                for site in &host_sites {
+                    cfg.service(
+                        web::scope("")
+                            .guard(site.guard())
+                            .app_data(site.config.clone())
+                            .app_data(site.globals.clone())
+                            .app_data(site.cache.clone())
+                            .app_data(site.health.clone())
+                            .app_data(site.registry.clone())
+                            .configure(|cfg| site_routes(cfg, &site.config)),
+                    );
+                }
+                site_routes(cfg, &config);
+            })
+    };
+    let mut server = HttpServer::new(app.clone()).workers(workers);
+
+    if let Some(address) = &tcp_address {
+        server = match h2c {
+            true => server.bind_auto_h2c(address)?,
+            false => server.bind(address)?,
+        };
+    }
+
+    let tls_server = match tls {
+        Some((address, acceptor)) => Some(tls::serve(
+            std::net::TcpListener::bind(address)?,
+            workers,
+            acceptor,
+            app,
+        )?),
+        None => None,
+    };
+
+    if let Some(path) = &uds {
+        #[cfg(unix)]
+        {
+            let mode = uds_mode.as_deref().map(socket::parse_mode).transpose()?;
+            socket::remove_stale(path)?;
+            server = server.bind_uds(path)?;
+            if let Some(mode) = mode {
+                socket::set_mode(path, mode)?;
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = uds_mode;
+            eprintln!("Unix domain sockets are not supported on this platform");
+            std::process::exit(1);
+        }
+    }
+
+    // Binding succeeded, so the server is listening
+    if cli.open {
+        match server.addrs().first() {
+            Some(address) => browser::open(&browser::url(*address)),
+            None => eprintln!("Not opening a browser: the server has no TCP address"),
+        }
+    }
+
+    let result = match tls_server {
+        Some(tls_server) => futures_util::future::try_join(server.run(), tls_server)
+            .await
+            .map(|_| ()),
+        None => server.run().await,
+    };
+
+    // Graceful shutdown: the socket file is no longer served
+    if let Some(path) = &uds
+        && let Err(e) = std::fs::remove_file(path)
+    {
+        eprintln!("Failed to remove socket {}: {}", path.display(), e);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HttpDate;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    /// A site with `static/app.css`, removed when dropped.
+    struct StaticSite(PathBuf);
+
+    impl StaticSite {
+        fn new(name: &str) -> StaticSite {
+            let root =
+                std::env::temp_dir().join(format!("hbs-composer-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(root.join("static")).unwrap();
+            std::fs::write(root.join("static/app.css"), "body{}").unwrap();
+            StaticSite(root)
+        }
+
+        fn modified(&self) -> SystemTime {
+            std::fs::metadata(self.0.join("static/app.css"))
+                .unwrap()
+                .modified()
+                .unwrap()
+        }
+    }
+
+    impl Drop for StaticSite {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn if_modified_since(config: Config, since: SystemTime) -> StatusCode {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(config.clone()))
+                .configure(|cfg| site_routes(cfg, &config)),
+        )
+        .await;
+        let request = TestRequest::get()
+            .uri("/static/app.css")
+            .insert_header((header::IF_MODIFIED_SINCE, HttpDate::from(since)))
+            .to_request();
+        call_service(&app, request).await.status()
+    }
+
+    #[actix_web::test]
+    async fn static_files_answer_not_modified() {
+        let site = StaticSite::new("files-304");
+        let config = Config {
+            root: site.0.clone(),
+            ..Config::default()
+        };
+        assert!(!static_files::needs_handler(&config.static_files));
+
+        let modified = site.modified();
+        assert_eq!(
+            if_modified_since(config.clone(), modified).await,
+            StatusCode::NOT_MODIFIED
+        );
+        let earlier = modified - Duration::from_secs(60);
+        assert_eq!(if_modified_since(config, earlier).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn the_static_handler_answers_not_modified() {
+        let site = StaticSite::new("serve-304");
+        let mut config = Config {
+            root: site.0.clone(),
+            ..Config::default()
+        };
+        config
+            .static_files
+            .mime_types
+            .insert("css".to_string(), "text/css".to_string());
+        assert!(static_files::needs_handler(&config.static_files));
+
+        let modified = site.modified();
+        assert_eq!(
+            if_modified_since(config.clone(), modified).await,
+            StatusCode::NOT_MODIFIED
+        );
+        let earlier = modified - Duration::from_secs(60);
+        assert_eq!(if_modified_since(config, earlier).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn h2c_multiplexes_asset_requests() {
+        let site = StaticSite::new("h2c");
+        let config = Config {
+            root: site.0.clone(),
+            ..Config::default()
+        };
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(config.clone()))
+                .configure(|cfg| site_routes(cfg, &config))
+        })
+        .workers(1)
+        .bind_auto_h2c("127.0.0.1:0")
+        .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        // Prior knowledge: HTTP/2 from the first byte, no upgrade
+        let tcp = tokio::net::TcpStream::connect(address).await.unwrap();
+        let (client, connection) = h2::client::handshake(tcp).await.unwrap();
+        actix_web::rt::spawn(connection);
+        let mut client = client.ready().await.unwrap();
+
+        // Every stream is open before any response is read
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            let request = http::Request::get(format!("http://{}/static/app.css", address))
+                .body(())
+                .unwrap();
+            let (response, _) = client.send_request(request, true).unwrap();
+            responses.push(response);
+        }
+        for response in responses {
+            let response = response.await.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.version(), http::Version::HTTP_2);
+            let chunk = response.into_body().data().await.unwrap().unwrap();
+            assert_eq!(&chunk[..], b"body{}");
+        }
+        handle.stop(false).await;
+    }
 }
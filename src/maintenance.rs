@@ -0,0 +1,123 @@
+//! Maintenance mode: while `enabled = true` under `[maintenance]`, or while
+//! the `.maintenance` file exists, every request gets a 503 rendering
+//! `pages/maintenance.hbs` instead of being routed. The file is checked on
+//! every request, so a deploy script can flip the mode without a restart.
+//! Health, metrics and admin routes are always served, so orchestration
+//! keeps working.
+
+use crate::config::{self, Config};
+use crate::data::Globals;
+use crate::health;
+use crate::limits;
+use crate::live_reload;
+use crate::metrics;
+use crate::pages;
+use crate::post_process::RenderedPage;
+use crate::render::{self, Site};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{StatusCode, header};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use tokio::fs;
+
+/// Labels requests answered with the maintenance page in metrics.
+const MAINTENANCE_ROUTE: &str = "maintenance";
+
+/// Whether the path is served during maintenance: the readiness probe,
+/// metrics, live reload, admin and debug routes, and `exempt` prefixes.
+fn is_exempt(config: &Config, path: &str) -> bool {
+    path == health::ENDPOINT
+        || path == metrics::ENDPOINT
+        || path == live_reload::ENDPOINT
+        || path.starts_with("/_admin/")
+        || path.starts_with("/_debug/")
+        || config
+            .maintenance
+            .exempt
+            .iter()
+            .any(|prefix| config::is_path_under(path, prefix))
+}
+
+async fn is_active(config: &Config) -> bool {
+    config.maintenance.enabled
+        || fs::try_exists(config.maintenance_file())
+            .await
+            .unwrap_or(false)
+}
+
+/// Middleware answering non-exempt requests with the maintenance page while
+/// maintenance mode is on.
+pub async fn check(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let config = req.app_data::<web::Data<Config>>().cloned();
+    let globals = req.app_data::<web::Data<Globals>>().cloned();
+    let (Some(config), Some(globals)) = (config, globals) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if is_exempt(&config, req.path()) || !is_active(&config).await {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    metrics::label_route(req.request(), MAINTENANCE_ROUTE);
+    let response = render_maintenance(req.request(), &config, &globals).await;
+    Ok(req.into_response(response))
+}
+
+/// A 503 rendering `pages/maintenance.hbs` with the globals when it exists,
+/// or a plain message. `Retry-After` and `no-store` keep clients and CDNs
+/// from holding on to it.
+async fn render_maintenance(req: &HttpRequest, config: &Config, globals: &Globals) -> HttpResponse {
+    let template_name = format!("{}/{}", pages::PAGES_DIR, pages::MAINTENANCE_PAGE);
+    let route = req.path().trim_matches('/');
+    let rendered = match render::prefers_json(req) {
+        true => None,
+        false => match Site::load(config).await {
+            Ok(site) if site.handlebars.has_template(&template_name) => {
+                let context = render::globals_context(config, globals, route);
+                match limits::render(&site.handlebars, config, &template_name, &context) {
+                    Ok(rendered) => Some(rendered),
+                    Err(e) => {
+                        eprintln!("Failed to render '{}': {}", template_name, e);
+                        None
+                    }
+                }
+            }
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Failed to register templates: {}", e);
+                None
+            }
+        },
+    };
+
+    let mut response = match rendered {
+        Some(rendered) => HttpResponse::ServiceUnavailable()
+            .content_type(pages::DEFAULT_CONTENT_TYPE)
+            .body(render::post_process(
+                config,
+                &RenderedPage::html(&template_name),
+                rendered,
+            )),
+        None => render::error_response(
+            config,
+            req,
+            StatusCode::SERVICE_UNAVAILABLE,
+            "maintenance",
+            "The site is down for maintenance",
+            &"maintenance mode is on",
+        ),
+    };
+    let headers = response.headers_mut();
+    headers.insert(
+        header::RETRY_AFTER,
+        header::HeaderValue::from(config.server.retry_after),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-store"),
+    );
+    response
+}
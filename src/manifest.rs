@@ -0,0 +1,123 @@
+use crate::config::{Config, ManifestConfig};
+use crate::static_files;
+use actix_web::{HttpResponse, Result, web};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Served when `[manifest]` is configured.
+pub const ENDPOINT: &str = "/manifest.webmanifest";
+
+/// Used when neither `name` nor `short_name` is set.
+const FALLBACK_NAME: &str = "Site";
+
+/// `src` as a URL: paths relative to `static/` become `/static/...`, while
+/// absolute paths and URLs are kept.
+fn icon_url(src: &str) -> String {
+    match src.starts_with('/') || src.contains("://") {
+        true => src.to_string(),
+        false => format!("/static/{}", src),
+    }
+}
+
+/// The image type, from `type` or else the file extension.
+fn icon_type(src: &str) -> Option<&'static str> {
+    let extension = src.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "jpg" | "jpeg" => "image/jpeg",
+        "ico" => "image/x-icon",
+        _ => return None,
+    })
+}
+
+/// Problems that keep browsers from offering to install the site, for the
+/// startup summary. The manifest is still served.
+pub fn warnings(manifest: &ManifestConfig, static_dir: &Path) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if manifest.name.is_none() && manifest.short_name.is_none() {
+        warnings.push(format!(
+            "[manifest] sets neither name nor short_name; using \"{}\"",
+            FALLBACK_NAME
+        ));
+    }
+    if manifest.icons.is_empty() {
+        warnings.push("[manifest] lists no icons".to_string());
+    }
+    for icon in &manifest.icons {
+        if icon.src.starts_with('/') || icon.src.contains("://") {
+            continue;
+        }
+        let exists =
+            static_files::resolve(static_dir, &icon.src).is_some_and(|path| path.is_file());
+        if !exists {
+            warnings.push(format!(
+                "[manifest] icon {} not found in {}/",
+                icon.src,
+                static_files::STATIC_DIR
+            ));
+        }
+    }
+    warnings
+}
+
+/// The manifest document. Unset optional members are left out.
+fn manifest_json(manifest: &ManifestConfig) -> Value {
+    let mut json = Map::new();
+    let mut insert = |key: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            json.insert(key.to_string(), Value::from(value.as_str()));
+        }
+    };
+
+    let name = manifest
+        .name
+        .clone()
+        .or_else(|| manifest.short_name.clone())
+        .unwrap_or_else(|| FALLBACK_NAME.to_string());
+    insert("name", &Some(name));
+    insert("short_name", &manifest.short_name);
+    insert("description", &manifest.description);
+    insert("start_url", &Some(manifest.start_url.clone()));
+    insert("scope", &manifest.scope);
+    insert("display", &Some(manifest.display.clone()));
+    insert("background_color", &manifest.background_color);
+    insert("theme_color", &manifest.theme_color);
+
+    let icons = manifest
+        .icons
+        .iter()
+        .map(|icon| {
+            let mut entry = Map::new();
+            entry.insert("src".to_string(), Value::from(icon_url(&icon.src)));
+            let icon_type = icon.icon_type.as_deref().or_else(|| icon_type(&icon.src));
+            for (key, value) in [
+                ("sizes", icon.sizes.as_deref()),
+                ("type", icon_type),
+                ("purpose", icon.purpose.as_deref()),
+            ] {
+                if let Some(value) = value {
+                    entry.insert(key.to_string(), Value::from(value));
+                }
+            }
+            Value::Object(entry)
+        })
+        .collect();
+    json.insert("icons".to_string(), Value::Array(icons));
+
+    Value::Object(json)
+}
+
+/// Serves the web app manifest built from `[manifest]`.
+pub async fn manifest(config: web::Data<Config>) -> Result<HttpResponse> {
+    let Some(manifest) = &config.manifest else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut body = serde_json::to_string_pretty(&manifest_json(manifest))?;
+    body.push('\n');
+    Ok(HttpResponse::Ok()
+        .content_type("application/manifest+json")
+        .body(body))
+}
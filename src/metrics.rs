@@ -0,0 +1,222 @@
+//! Prometheus metrics at `/metrics`, routed with `enabled = true` under
+//! `[metrics]`: requests by route and status, render and data-load
+//! durations, errors by kind, and the cache statistics. Routes are labeled
+//! by page template or route pattern, never by raw path, so the number of
+//! series stays bounded however many URLs are requested.
+
+use crate::admin;
+use crate::cache_stats;
+use crate::config::Config;
+use crate::render_cache::RenderCache;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse, Result, web};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+pub const ENDPOINT: &str = "/metrics";
+
+/// Labels requests no route matched.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Labels page requests for templates that don't exist.
+pub const NOT_FOUND_ROUTE: &str = "not_found";
+
+/// Upper bounds of the duration histogram buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A request's route label, set by handlers that know better than the
+/// route pattern, e.g. `pages/blog/post` rather than `/{path:.*}`.
+pub struct RouteLabel(pub String);
+
+/// Labels the request with the page template it rendered.
+pub fn label_route(req: &HttpRequest, route: &str) {
+    req.extensions_mut().insert(RouteLabel(route.to_string()));
+}
+
+struct Histogram {
+    /// Observations at or below each bound, not cumulative.
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Histogram {
+        Histogram {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn write(&self, text: &mut String, name: &str, help: &str) {
+        text.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} histogram\n",
+            name, help, name
+        ));
+        let mut cumulative = 0;
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            text.push_str(&format!(
+                "{}_bucket{{le=\"{}\"}} {}\n",
+                name, bound, cumulative
+            ));
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        text.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", name, count));
+        text.push_str(&format!(
+            "{}_sum {}\n",
+            name,
+            self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        ));
+        text.push_str(&format!("{}_count {}\n", name, count));
+    }
+}
+
+/// Why a page request failed.
+#[derive(Debug, Clone, Copy)]
+pub enum ErrorKind {
+    /// No template for the requested page.
+    TemplateMissing,
+    /// The template failed to render, or rendered nothing.
+    RenderError,
+    /// The data failed to load or was unavailable.
+    DataError,
+    /// The templates failed to register.
+    TemplateLoad,
+}
+
+impl ErrorKind {
+    const ALL: [ErrorKind; 4] = [
+        ErrorKind::TemplateMissing,
+        ErrorKind::RenderError,
+        ErrorKind::DataError,
+        ErrorKind::TemplateLoad,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ErrorKind::TemplateMissing => "template_missing",
+            ErrorKind::RenderError => "render_error",
+            ErrorKind::DataError => "data_error",
+            ErrorKind::TemplateLoad => "template_load",
+        }
+    }
+}
+
+static REQUESTS: Mutex<BTreeMap<(String, u16), u64>> = Mutex::new(BTreeMap::new());
+static RENDER: Histogram = Histogram::new();
+static DATA_LOAD: Histogram = Histogram::new();
+static ERRORS: [AtomicU64; ErrorKind::ALL.len()] =
+    [const { AtomicU64::new(0) }; ErrorKind::ALL.len()];
+
+pub fn is_enabled(config: &Config) -> bool {
+    config.metrics.enabled
+}
+
+pub fn observe_render(duration: Duration) {
+    RENDER.observe(duration);
+}
+
+pub fn observe_data_load(duration: Duration) {
+    DATA_LOAD.observe(duration);
+}
+
+pub fn count_error(kind: ErrorKind) {
+    ERRORS[kind as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Middleware counting every response by route and status.
+pub async fn record(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let response = next.call(req).await?.map_into_boxed_body();
+    let route = match response.request().extensions().get::<RouteLabel>() {
+        Some(label) => label.0.clone(),
+        None => response
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string()),
+    };
+    let status = response.status().as_u16();
+    *REQUESTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry((route, status))
+        .or_default() += 1;
+    Ok(response)
+}
+
+/// `"` and `\` escaped for a label value.
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Serves the metrics. When `token` is set under `[admin]`, it is required
+/// as a bearer token, as for `/_debug/cache`.
+pub async fn metrics(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    render_cache: web::Data<RenderCache>,
+) -> Result<HttpResponse> {
+    if admin::is_enabled(&config) && !admin::is_authorized(&req, &config) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+
+    let mut text = String::from(
+        "# HELP hbs_composer_requests_total Responses by route and status.\n# TYPE hbs_composer_requests_total counter\n",
+    );
+    for ((route, status), count) in REQUESTS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        text.push_str(&format!(
+            "hbs_composer_requests_total{{route=\"{}\",status=\"{}\"}} {}\n",
+            label_value(route),
+            status,
+            count
+        ));
+    }
+    RENDER.write(
+        &mut text,
+        "hbs_composer_render_duration_seconds",
+        "Time to render a page, including post-processing.",
+    );
+    DATA_LOAD.write(
+        &mut text,
+        "hbs_composer_data_load_duration_seconds",
+        "Time to load the data for a page.",
+    );
+    text.push_str(
+        "# HELP hbs_composer_errors_total Failed page requests by kind.\n# TYPE hbs_composer_errors_total counter\n",
+    );
+    for kind in ErrorKind::ALL {
+        text.push_str(&format!(
+            "hbs_composer_errors_total{{kind=\"{}\"}} {}\n",
+            kind.label(),
+            ERRORS[kind as usize].load(Ordering::Relaxed)
+        ));
+    }
+    cache_stats::write_metrics(&mut text, &render_cache);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(text))
+}
@@ -0,0 +1,464 @@
+use crate::config::TrailingSlashPolicy;
+use crate::front_matter;
+use handlebars::Handlebars;
+use serde_json::{Map, Value, json};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::fs;
+
+/// Directory under the templates root that holds routable pages.
+pub const PAGES_DIR: &str = "pages";
+
+/// Error pages are rendered by the server, never linked to directly.
+const ERROR_PAGES: [&str; 5] = ["404", "410", "500", "503", MAINTENANCE_PAGE];
+
+/// Rendered for paths listed under `gone` in `[routing]`, when it exists.
+pub const GONE_PAGE: &str = "410";
+
+/// Rendered when a page's data is unavailable, when it exists.
+pub const UNAVAILABLE_PAGE: &str = "503";
+
+/// Rendered for every request while maintenance mode is on, when it exists.
+pub const MAINTENANCE_PAGE: &str = "maintenance";
+
+pub const TEMPLATE_EXTENSION: &str = "hbs";
+
+/// Served for pages whose front matter doesn't set `content_type`.
+pub const DEFAULT_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+
+/// Marks the AMP variant of a page, e.g. `pages/article.amp.hbs` for
+/// `pages/article.hbs`.
+pub const AMP_SUFFIX: &str = ".amp";
+
+/// AMP variants are served below this path, e.g. `/amp/article`.
+pub const AMP_PATH: &str = "amp";
+
+/// Whether a template is the AMP variant of a page, e.g. `pages/article.amp`.
+pub fn is_amp(template_name: &str) -> bool {
+    template_name.ends_with(AMP_SUFFIX)
+}
+
+/// Where the AMP variant of the page at `url` is served: `/amp/article` for
+/// `/article`, and `/amp` for `/`.
+pub fn amp_url(url: &str) -> String {
+    match url {
+        "/" => format!("/{}", AMP_PATH),
+        url => format!("/{}{}", AMP_PATH, url),
+    }
+}
+
+/// Whether a content type is HTML, e.g. `text/html; charset=utf-8`.
+pub fn is_html(content_type: &str) -> bool {
+    content_type.starts_with("text/html")
+}
+
+/// A template under `templates/pages/`.
+pub struct Page {
+    /// Path relative to the pages directory without extension, e.g. `blog/post`.
+    pub name: String,
+    pub front_matter: Map<String, Value>,
+    /// Template source with the front matter removed, if the file had any.
+    body: Option<String>,
+}
+
+impl Page {
+    pub fn template_name(&self) -> String {
+        format!("{}/{}", PAGES_DIR, self.name)
+    }
+
+    pub fn url(&self, trailing_slash: TrailingSlashPolicy) -> String {
+        if self.name == "index" {
+            return "/".to_string();
+        }
+
+        // Directory indexes are served at the directory URL
+        let (path, is_index) = match self.name.strip_suffix("/index") {
+            Some(dir) => (dir, true),
+            None => (self.name.as_str(), false),
+        };
+        match trailing_slash.wants_slash(is_index) {
+            Some(true) => format!("/{}/", path),
+            _ => format!("/{}", path),
+        }
+    }
+
+    fn flag(&self, name: &str) -> Option<bool> {
+        self.front_matter.get(name).and_then(Value::as_bool)
+    }
+
+    /// Whether the page or one of its directories starts with `_`.
+    pub fn is_private(&self) -> bool {
+        self.name.split('/').any(|segment| segment.starts_with('_'))
+    }
+
+    /// Error pages, private pages and AMP variants are never listed.
+    pub fn is_public(&self) -> bool {
+        !ERROR_PAGES.contains(&self.name.as_str()) && !self.is_private() && !is_amp(&self.name)
+    }
+
+    /// Whether the page should appear in the `pages` context list.
+    fn is_listed(&self) -> bool {
+        self.is_public() && !self.flag("hidden").unwrap_or(false)
+    }
+
+    /// Whether the page should appear in the sitemap. Pages are included
+    /// unless their front matter sets `noindex: true` or `sitemap: false`.
+    pub fn in_sitemap(&self) -> bool {
+        self.is_public()
+            && !self.flag("noindex").unwrap_or(false)
+            && self.flag("sitemap").unwrap_or(true)
+    }
+
+    /// The response content type, e.g. `application/xml` for a feed page.
+    pub fn content_type(&self) -> &str {
+        self.front_matter
+            .get("content_type")
+            .and_then(Value::as_str)
+            .unwrap_or(DEFAULT_CONTENT_TYPE)
+    }
+
+    /// Whether the page renders HTML, so HTML post-processing applies.
+    pub fn is_html(&self) -> bool {
+        is_html(self.content_type())
+    }
+
+    /// How long the page may be cached, from `cache` in front matter: a
+    /// number of seconds, or `false` (or `0`) for pages using the current
+    /// time, such as `{{now}}`. Other pages get `default_max_age`.
+    pub fn cache_policy(&self, default_max_age: Option<u64>) -> CachePolicy {
+        let default = match default_max_age {
+            Some(seconds) => CachePolicy::MaxAge(seconds),
+            None => CachePolicy::Unset,
+        };
+        match self.front_matter.get("cache") {
+            Some(Value::Bool(false)) => CachePolicy::NoStore,
+            Some(value) => match value.as_u64() {
+                Some(0) => CachePolicy::NoStore,
+                Some(seconds) => CachePolicy::MaxAge(seconds),
+                None => default,
+            },
+            None => default,
+        }
+    }
+
+    fn order(&self) -> Option<f64> {
+        self.front_matter.get("order").and_then(Value::as_f64)
+    }
+
+    /// Whether the page appears in `_nav`: listed, and not `nav: false`.
+    fn in_nav(&self) -> bool {
+        self.is_listed() && self.flag("nav").unwrap_or(true)
+    }
+
+    fn title(&self) -> Value {
+        self.front_matter
+            .get("title")
+            .cloned()
+            .unwrap_or_else(|| Value::String(self.name.clone()))
+    }
+
+    fn to_value(&self, trailing_slash: TrailingSlashPolicy) -> Value {
+        json!({
+            "url": self.url(trailing_slash),
+            "name": self.name,
+            "template": self.template_name(),
+            "title": self.title(),
+            "order": self.front_matter.get("order"),
+            "meta": self.front_matter,
+        })
+    }
+}
+
+type DiscoverPagesResult = Result<Vec<Page>, Box<dyn std::error::Error>>;
+
+fn discover_pages_recursive<'a>(
+    dir_path: &'a Path,
+    prefix: String,
+) -> Pin<Box<dyn Future<Output = DiscoverPagesResult> + 'a>> {
+    Box::pin(async move {
+        let mut pages = Vec::new();
+        let mut entries = fs::read_dir(dir_path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+            let Some(file_name) = path.file_name() else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                let prefix = format!("{}{}/", prefix, file_name.to_string_lossy());
+                pages.extend(discover_pages_recursive(&path, prefix).await?);
+            } else if metadata.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == TEMPLATE_EXTENSION)
+                && let Some(file_stem) = path.file_stem()
+            {
+                let name = format!("{}{}", prefix, file_stem.to_string_lossy());
+                let content = fs::read_to_string(&path).await?;
+
+                let (front_matter, body) = match front_matter::split(&content) {
+                    Some((source, body)) => match front_matter::parse(source) {
+                        Ok(front_matter) => (front_matter, Some(body.to_string())),
+                        Err(e) => {
+                            eprintln!("Failed to parse front matter in {}: {}", path.display(), e);
+                            (Map::new(), Some(body.to_string()))
+                        }
+                    },
+                    None => (Map::new(), None),
+                };
+
+                pages.push(Page {
+                    name,
+                    front_matter,
+                    body,
+                });
+            }
+        }
+
+        Ok(pages)
+    })
+}
+
+/// Finds every page template and reads its front matter.
+pub async fn discover_pages(templates_dir: &Path) -> DiscoverPagesResult {
+    let pages_dir = templates_dir.join(PAGES_DIR);
+
+    if !pages_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    discover_pages_recursive(&pages_dir, String::new()).await
+}
+
+/// A warning when `default_page`, served at an empty path, has no template.
+pub fn check_default_page(templates_dir: &Path, default_page: &str) -> Option<String> {
+    let pages_dir = templates_dir.join(PAGES_DIR);
+    let file = |name: &str| pages_dir.join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+    let index = format!("{}/index", default_page);
+    if file(default_page).is_file() || file(&index).is_file() {
+        return None;
+    }
+    Some(format!(
+        "default_page \"{}\" under [routing] has no template; expected {}",
+        default_page,
+        file(default_page).display()
+    ))
+}
+
+/// Checks at startup that there is something to serve, returning the number
+/// of pages found or a description of what is missing.
+pub async fn check_pages_dir(templates_dir: &Path) -> Result<usize, String> {
+    let pages_dir = templates_dir.join(PAGES_DIR);
+
+    if !templates_dir.is_dir() {
+        return Err(format!(
+            "templates directory {} does not exist; run the server from the project root",
+            templates_dir.display()
+        ));
+    }
+    if !pages_dir.is_dir() {
+        return Err(format!(
+            "pages directory {} does not exist; pages are served from templates under it",
+            pages_dir.display()
+        ));
+    }
+
+    match discover_pages(templates_dir).await {
+        Ok(pages) if pages.is_empty() => Err(format!(
+            "pages directory {} contains no .{} templates",
+            pages_dir.display(),
+            TEMPLATE_EXTENSION
+        )),
+        Ok(pages) => Ok(pages.len()),
+        Err(e) => Err(format!(
+            "failed to read pages directory {}: {}",
+            pages_dir.display(),
+            e
+        )),
+    }
+}
+
+/// Re-registers pages that have front matter so it isn't rendered as output.
+pub fn register_page_bodies(handlebars: &mut Handlebars, pages: &[Page]) {
+    for page in pages {
+        if let Some(body) = &page.body {
+            let template_name = page.template_name();
+            // Drop the file source first, otherwise dev mode reloads the raw file
+            handlebars.unregister_template(&template_name);
+            if let Err(e) = handlebars.register_template_string(&template_name, body) {
+                eprintln!("Failed to register page '{}': {}", page.name, e);
+            }
+        }
+    }
+}
+
+/// Builds the `pages` context value: listed pages sorted by front matter
+/// `order` (pages without one come last), then by name.
+/// By `order`, with unordered items last, then by name.
+fn by_order(a: (Option<f64>, &str), b: (Option<f64>, &str)) -> Ordering {
+    match (a.0, b.0) {
+        (Some(a_order), Some(b_order)) => a_order.total_cmp(&b_order).then(a.1.cmp(b.1)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.1.cmp(b.1),
+    }
+}
+
+pub fn pages_context(pages: &[Page], trailing_slash: TrailingSlashPolicy) -> Value {
+    let mut listed: Vec<&Page> = pages.iter().filter(|page| page.is_listed()).collect();
+
+    listed.sort_by(|a, b| by_order((a.order(), &a.name), (b.order(), &b.name)));
+
+    Value::Array(
+        listed
+            .into_iter()
+            .map(|page| page.to_value(trailing_slash))
+            .collect(),
+    )
+}
+
+/// A directory of `pages/`, or a page, in the `_nav` tree. A directory
+/// with an index page is both.
+#[derive(Default)]
+struct NavNode<'a> {
+    page: Option<&'a Page>,
+    children: BTreeMap<String, NavNode<'a>>,
+}
+
+impl NavNode<'_> {
+    fn order(&self) -> Option<f64> {
+        self.page.and_then(Page::order)
+    }
+
+    /// The node as a `_nav` item; `path` is its directory path or page
+    /// name and `current` the URL path of the page being rendered.
+    fn to_value(&self, path: &str, trailing_slash: TrailingSlashPolicy, current: &str) -> Value {
+        let mut children: Vec<(&String, &NavNode)> = self.children.iter().collect();
+        children.sort_by(|(a_key, a), (b_key, b)| by_order((a.order(), a_key), (b.order(), b_key)));
+        let children: Vec<Value> = children
+            .into_iter()
+            .map(|(key, child)| {
+                let child_path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}/{}", path, key),
+                };
+                child.to_value(&child_path, trailing_slash, current)
+            })
+            .collect();
+
+        let url = self.page.map(|page| page.url(trailing_slash));
+        let active = url
+            .as_deref()
+            .is_some_and(|url| match url.trim_end_matches('/') {
+                "" => current == "/",
+                url => url == current,
+            });
+        let open = active
+            || children
+                .iter()
+                .any(|child| child["open"].as_bool().unwrap_or(false));
+        let title = self
+            .page
+            .and_then(|page| page.front_matter.get("nav_title").cloned())
+            .or_else(|| self.page.map(Page::title))
+            .unwrap_or_else(|| Value::from(path.rsplit('/').next().unwrap_or(path)));
+        json!({
+            "name": path,
+            "url": url,
+            "title": title,
+            "order": self.order(),
+            "meta": self.page.map_or_else(Map::new, |page| page.front_matter.clone()),
+            "active": active,
+            "open": open,
+            "children": children,
+        })
+    }
+}
+
+/// The `_nav` tree: the listed pages nested like the directories of
+/// `pages/`, with a directory's `index` page standing for the directory.
+/// `nav: false` leaves a page out, and on a directory index the whole
+/// directory. `current` is the URL path of the page being rendered.
+pub fn nav_context(pages: &[Page], trailing_slash: TrailingSlashPolicy, current: &str) -> Value {
+    let hidden_dirs: Vec<String> = pages
+        .iter()
+        .filter(|page| page.is_listed() && !page.in_nav())
+        .filter_map(|page| page.name.strip_suffix("/index"))
+        .map(|dir| format!("{}/", dir))
+        .collect();
+
+    let mut root = NavNode::default();
+    for page in pages.iter().filter(|page| page.in_nav()) {
+        if hidden_dirs.iter().any(|dir| page.name.starts_with(dir)) {
+            continue;
+        }
+        let path = page.name.strip_suffix("/index").unwrap_or(&page.name);
+        let node = path.split('/').fold(&mut root, |node, segment| {
+            node.children.entry(segment.to_string()).or_default()
+        });
+        node.page = Some(page);
+    }
+
+    root.to_value("", trailing_slash, current)["children"].take()
+}
+
+/// Caching of a page response, by browsers and proxies through
+/// `Cache-Control` and by the render cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// No `Cache-Control`; the render cache keeps the page until the sources
+    /// change.
+    Unset,
+    /// `max-age`, and the render cache keeps the page for as long.
+    MaxAge(u64),
+    /// `no-store`, and the page is rendered on every request.
+    NoStore,
+}
+
+impl CachePolicy {
+    /// The `Cache-Control` value, if any.
+    pub fn header(self) -> Option<String> {
+        match self {
+            CachePolicy::Unset => None,
+            CachePolicy::MaxAge(seconds) => Some(format!("max-age={}", seconds)),
+            CachePolicy::NoStore => Some("no-store".to_string()),
+        }
+    }
+
+    /// Whether the render cache may keep the page.
+    pub fn is_cacheable(self) -> bool {
+        self != CachePolicy::NoStore
+    }
+
+    /// How long the render cache keeps the page, unless the sources change
+    /// first.
+    pub fn ttl(self) -> Option<Duration> {
+        match self {
+            CachePolicy::MaxAge(seconds) => Some(Duration::from_secs(seconds)),
+            _ => None,
+        }
+    }
+}
+
+/// Resolves a requested page path to a registered template, falling back to
+/// the directory index (`blog` → `pages/blog/index`). Returns the template
+/// name and whether the index fallback was used.
+pub fn resolve_template(handlebars: &Handlebars, page: &str) -> Option<(String, bool)> {
+    let template_name = format!("{}/{}", PAGES_DIR, page);
+    if handlebars.has_template(&template_name) {
+        return Some((template_name, false));
+    }
+
+    let index_name = format!("{}/index", template_name);
+    if handlebars.has_template(&index_name) {
+        return Some((index_name, true));
+    }
+
+    None
+}
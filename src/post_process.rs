@@ -0,0 +1,247 @@
+//! Stages run over a rendered page before it is sent or written. Each
+//! implements `HtmlPostProcessor`; `pipeline` builds the built-in stages the
+//! config turns on, in the order they run, once per combination of settings,
+//! and further stages can be added there.
+
+use crate::config::Config;
+use crate::headings;
+use crate::inspector;
+use crate::live_reload;
+use crate::pages;
+use std::sync::{Arc, Mutex};
+
+/// The page a stage is run over.
+pub struct RenderedPage<'a> {
+    /// Template the page was rendered from, e.g. `pages/blog/post`.
+    pub template_name: &'a str,
+    /// Response content type, e.g. `text/html; charset=utf-8`.
+    pub content_type: &'a str,
+    /// The context inspector's panel, when it is on for this page.
+    pub panel: Option<&'a str>,
+}
+
+impl<'a> RenderedPage<'a> {
+    /// An HTML page without an inspector panel, such as a form response.
+    pub fn html(template_name: &'a str) -> RenderedPage<'a> {
+        RenderedPage {
+            template_name,
+            content_type: pages::DEFAULT_CONTENT_TYPE,
+            panel: None,
+        }
+    }
+}
+
+/// Changes the rendered output of a page.
+pub trait HtmlPostProcessor: Send + Sync {
+    /// Whether the stage runs for pages of `content_type`; by default only
+    /// for HTML, so feeds and other front matter types are left alone.
+    fn applies_to(&self, content_type: &str) -> bool {
+        pages::is_html(content_type)
+    }
+
+    fn process(&self, html: String, page: &RenderedPage) -> String;
+}
+
+/// A pipeline's stages, in the order they run.
+pub type Pipeline = Arc<[Box<dyn HtmlPostProcessor>]>;
+
+/// The settings a pipeline is built from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Settings {
+    heading_ids: bool,
+    heading_anchors: bool,
+    live_reload: bool,
+    minify: bool,
+    inspector: bool,
+}
+
+impl Settings {
+    fn of(config: &Config) -> Settings {
+        Settings {
+            heading_ids: config.html.heading_ids,
+            heading_anchors: config.html.heading_anchors,
+            live_reload: config.live_reload(),
+            minify: config.minify(),
+            inspector: inspector::is_enabled(config),
+        }
+    }
+}
+
+/// Pipelines already built, by their settings. There are at most a few:
+/// one per config, plus the variants of it that builds and previews use.
+static PIPELINES: Mutex<Vec<(Settings, Pipeline)>> = Mutex::new(Vec::new());
+
+/// The built-in stages the config turns on: heading ids, the live reload
+/// script, minification, then the inspector panel. Minifying after the
+/// script is injected shrinks it too, while the panel is added last so it
+/// keeps its formatting. Built once for each combination of settings and
+/// shared after that.
+pub fn pipeline(config: &Config) -> Pipeline {
+    let settings = Settings::of(config);
+    let mut pipelines = PIPELINES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, pipeline)) = pipelines.iter().find(|(built, _)| *built == settings) {
+        return pipeline.clone();
+    }
+
+    let mut stages: Vec<Box<dyn HtmlPostProcessor>> = Vec::new();
+    if settings.heading_ids {
+        stages.push(Box::new(HeadingIds {
+            anchors: settings.heading_anchors,
+        }));
+    }
+    if settings.live_reload {
+        stages.push(Box::new(LiveReload));
+    }
+    if settings.minify {
+        stages.push(Box::new(Minify::new()));
+    }
+    if settings.inspector {
+        stages.push(Box::new(InspectorPanel));
+    }
+    let pipeline: Pipeline = stages.into();
+    pipelines.push((settings, pipeline.clone()));
+    pipeline
+}
+
+/// Runs each stage that applies to the page, in order.
+pub fn apply(stages: &[Box<dyn HtmlPostProcessor>], page: &RenderedPage, html: String) -> String {
+    stages
+        .iter()
+        .filter(|stage| stage.applies_to(page.content_type))
+        .fold(html, |html, stage| stage.process(html, page))
+}
+
+/// Gives headings without one an `id`, see `src/headings.rs`.
+struct HeadingIds {
+    anchors: bool,
+}
+
+impl HtmlPostProcessor for HeadingIds {
+    fn process(&self, html: String, page: &RenderedPage) -> String {
+        match headings::add_heading_ids(&html, self.anchors) {
+            Ok(with_ids) => with_ids,
+            Err(e) => {
+                eprintln!(
+                    "Failed to add heading ids to '{}': {}",
+                    page.template_name, e
+                );
+                html
+            }
+        }
+    }
+}
+
+struct LiveReload;
+
+impl HtmlPostProcessor for LiveReload {
+    fn process(&self, html: String, page: &RenderedPage) -> String {
+        // AMP pages may not carry custom scripts
+        match pages::is_amp(page.template_name) {
+            true => html,
+            false => live_reload::inject_script(html),
+        }
+    }
+}
+
+struct Minify {
+    config: minify_html::Cfg,
+}
+
+impl Minify {
+    fn new() -> Minify {
+        let mut config = minify_html::Cfg::new();
+        config.keep_closing_tags = true;
+        config.keep_html_and_head_opening_tags = true;
+        config.minify_css = true;
+        Minify { config }
+    }
+}
+
+impl HtmlPostProcessor for Minify {
+    fn process(&self, html: String, _: &RenderedPage) -> String {
+        String::from_utf8_lossy(&minify_html::minify(html.as_bytes(), &self.config)).into_owned()
+    }
+}
+
+/// Adds the panel built before rendering, for pages that have one.
+struct InspectorPanel;
+
+impl HtmlPostProcessor for InspectorPanel {
+    fn process(&self, html: String, page: &RenderedPage) -> String {
+        match page.panel {
+            Some(panel) if !pages::is_amp(page.template_name) => inspector::inject(html, panel),
+            _ => html,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "<html><body>\n  <h2>Hello World</h2>\n</body></html>";
+
+    fn page<'a>(template_name: &'a str, panel: Option<&'a str>) -> RenderedPage<'a> {
+        RenderedPage {
+            template_name,
+            content_type: pages::DEFAULT_CONTENT_TYPE,
+            panel,
+        }
+    }
+
+    #[test]
+    fn heading_ids_are_added() {
+        let stage = HeadingIds { anchors: false };
+        let html = stage.process(PAGE.to_string(), &page("pages/index", None));
+        assert!(html.contains(r#"<h2 id="hello-world">"#), "{}", html);
+    }
+
+    #[test]
+    fn live_reload_skips_amp_pages() {
+        let html = LiveReload.process(PAGE.to_string(), &page("pages/index", None));
+        assert!(html.contains(live_reload::SCRIPT));
+        assert!(html.ends_with("</body></html>"));
+        let amp = LiveReload.process(PAGE.to_string(), &page("pages/index.amp", None));
+        assert_eq!(amp, PAGE);
+    }
+
+    #[test]
+    fn minify_collapses_whitespace() {
+        let html = Minify::new().process(PAGE.to_string(), &page("pages/index", None));
+        assert_eq!(html, "<html><body><h2>Hello World</h2></body></html>");
+    }
+
+    #[test]
+    fn inspector_panel_needs_a_panel() {
+        let panel = "<div id=hbs-inspector></div>";
+        let html = InspectorPanel.process(PAGE.to_string(), &page("pages/index", Some(panel)));
+        assert!(html.ends_with("<div id=hbs-inspector></div></body></html>"));
+        let without = InspectorPanel.process(PAGE.to_string(), &page("pages/index", None));
+        assert_eq!(without, PAGE);
+        let amp = InspectorPanel.process(PAGE.to_string(), &page("pages/index.amp", Some(panel)));
+        assert_eq!(amp, PAGE);
+    }
+
+    #[test]
+    fn stages_skip_other_content_types() {
+        let stages: Vec<Box<dyn HtmlPostProcessor>> = vec![Box::new(Minify::new())];
+        let feed = RenderedPage {
+            template_name: "pages/feed",
+            content_type: "application/rss+xml",
+            panel: None,
+        };
+        assert_eq!(apply(&stages, &feed, PAGE.to_string()), PAGE);
+    }
+
+    #[test]
+    fn pipelines_are_built_once_per_settings() {
+        let mut config = Config::default();
+        config.html.heading_ids = true;
+        let first = pipeline(&config);
+        assert_eq!(first.len(), 1);
+        assert!(Arc::ptr_eq(&first, &pipeline(&config.clone())));
+
+        config.html.heading_anchors = true;
+        assert!(!Arc::ptr_eq(&first, &pipeline(&config)));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::auth;
+use crate::config::Config;
+use crate::data::{Globals, merge_map};
+use crate::limits;
+use crate::pages;
+use crate::post_process::RenderedPage;
+use crate::render;
+use crate::sites::{self, Registry};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Pages are previewed with a POST to `/_preview/<page>`.
+pub const ENDPOINT: &str = "/_preview/{page:.+}";
+
+/// Preview contexts may be a whole site's worth of data.
+pub const BODY_LIMIT: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PreviewQuery {
+    /// Render against the posted context alone instead of merging it over
+    /// the normal one.
+    replace: bool,
+}
+
+/// Whether the preview endpoint should be routed: it needs a token and is
+/// never served in `prod`.
+pub fn is_enabled(config: &Config) -> bool {
+    config.preview.token.is_some() && !config.is_prod()
+}
+
+fn is_authorized(req: &HttpRequest, config: &Config) -> bool {
+    config
+        .preview
+        .token
+        .as_deref()
+        .is_some_and(|token| auth::has_bearer_token(req, token))
+}
+
+/// Renders a page against a posted JSON object, merged over the normal
+/// context or, with `?replace=true`, instead of it. Nothing is written to disk.
+pub async fn preview(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PreviewQuery>,
+    config: web::Data<Config>,
+    globals: web::Data<Globals>,
+    registry: Option<web::Data<Registry>>,
+    body: web::Json<Value>,
+) -> Result<HttpResponse> {
+    if !is_authorized(&req, &config) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+
+    let Value::Object(overlay) = body.into_inner() else {
+        return Ok(HttpResponse::BadRequest().body("Preview context must be a JSON object"));
+    };
+
+    let site = match sites::site(&config, registry.as_ref()).await {
+        Ok(site) => site,
+        Err(e) => {
+            eprintln!("Failed to register templates: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load templates",
+                &e,
+            )));
+        }
+    };
+
+    let page = path.into_inner();
+    let page = page.trim_end_matches('/');
+    let Some((template_name, _)) = pages::resolve_template(&site.handlebars, page) else {
+        return Ok(HttpResponse::NotFound().body(format!("Page '{}' not found", page)));
+    };
+
+    let context =
+        match query.replace {
+            true => overlay,
+            false => {
+                let mut context: Map<String, Value> =
+                    match site.context(&config, &globals, page).await {
+                        Ok(context) => context,
+                        Err(e) => {
+                            eprintln!("Failed to load data files: {}", e);
+                            return Ok(HttpResponse::InternalServerError().body(
+                                render::error_body(&config, "Failed to load data files", &e),
+                            ));
+                        }
+                    };
+                merge_map(&mut context, overlay);
+                context
+            }
+        };
+
+    // A reload would re-request the preview as a GET, so don't inject it
+    let mut output_config = config.get_ref().clone();
+    output_config.html.live_reload = Some(false);
+
+    let scoped = site.scoped_registry(&template_name);
+    let handlebars = scoped.as_ref().unwrap_or(&*site.handlebars);
+    match limits::render(handlebars, &config, &template_name, &context) {
+        Ok(rendered) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .body(render::post_process(
+                &output_config,
+                &RenderedPage::html(&template_name),
+                rendered,
+            ))),
+        Err(e) => Ok(render::render_failure(&config, &template_name, &e)),
+    }
+}
@@ -0,0 +1,142 @@
+use crate::config::{self, Config, RateLimitConfig};
+use crate::render;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{StatusCode, header};
+use actix_web::middleware::Next;
+use actix_web::{Error, web};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often buckets that have refilled completely are dropped.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bucket key for clients without an address, such as Unix socket peers.
+const UNKNOWN_CLIENT: &str = "unknown";
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    clients: HashMap<String, Bucket>,
+    cleaned: Instant,
+}
+
+/// Token buckets keyed by client address, shared by every worker. A bucket
+/// holds `burst` tokens and refills at `requests` per `window`.
+pub struct RateLimiter {
+    capacity: f64,
+    /// Tokens added per second.
+    rate: f64,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// `None` when `[rate_limit]` sets no `requests`.
+    pub fn new(config: &RateLimitConfig) -> Option<RateLimiter> {
+        let requests = config.requests.filter(|&requests| requests > 0)?;
+        Some(RateLimiter {
+            capacity: f64::from(config.burst.unwrap_or(requests).max(1)),
+            rate: f64::from(requests) / config.window.max(1) as f64,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                cleaned: Instant::now(),
+            }),
+        })
+    }
+
+    /// Takes a token for `client`, or returns how long until one is available.
+    fn acquire(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        // A full bucket is the same as none, so idle clients are forgotten
+        if now.duration_since(buckets.cleaned) >= CLEANUP_INTERVAL {
+            let (capacity, rate) = (self.capacity, self.rate);
+            buckets.clients.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+            buckets.cleaned = now;
+        }
+
+        let bucket = buckets.clients.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+    }
+}
+
+/// The client address: the last entry of the trusted proxy header, which is
+/// the one the proxy itself added, or else the peer address.
+fn client_key(req: &ServiceRequest, config: &RateLimitConfig) -> String {
+    let forwarded = config
+        .client_ip_header
+        .as_deref()
+        .and_then(|name| req.headers().get(name))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    match forwarded {
+        Some(address) => address.to_string(),
+        None => req
+            .peer_addr()
+            .map(|address| address.ip().to_string())
+            .unwrap_or_else(|| UNKNOWN_CLIENT.to_string()),
+    }
+}
+
+/// Middleware answering `429 Too Many Requests` with `Retry-After` once a
+/// client's bucket is empty. Exempt paths pass through untouched.
+pub async fn limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let limiter = req.app_data::<web::Data<RateLimiter>>().cloned();
+    let config = req.app_data::<web::Data<Config>>().cloned();
+    let (Some(limiter), Some(config)) = (limiter, config) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let exempt = config
+        .rate_limit
+        .exempt
+        .iter()
+        .any(|prefix| config::is_path_under(req.path(), prefix));
+    if exempt {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let client = client_key(&req, &config.rate_limit);
+    match limiter.acquire(&client) {
+        Ok(()) => Ok(next.call(req).await?.map_into_boxed_body()),
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = render::error_response(
+                &config,
+                req.request(),
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+                "Too many requests",
+                &format!("retry in {} seconds", retry_after),
+            );
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, header::HeaderValue::from(retry_after));
+            Ok(req.into_response(response))
+        }
+    }
+}
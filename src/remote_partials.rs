@@ -0,0 +1,270 @@
+//! Partials fetched over HTTP, declared with `[[remote_partials]]`, e.g. a
+//! header shared by several sites. They are fetched once at startup and
+//! again on `POST /_admin/reload`, and the fetched sources are registered
+//! into every registry, since each request builds a new one.
+
+use crate::cache_stats::CacheStats;
+use crate::config::{Config, RemoteFailurePolicy, RemotePartialConfig};
+use handlebars::{Handlebars, Template, TemplateError};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+/// A slow partial service shouldn't hold up startup for long.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sources by partial name, shared by all workers and sites.
+static FETCHED: Mutex<BTreeMap<String, Arc<str>>> = Mutex::new(BTreeMap::new());
+
+/// When a partial's source last changed, so cached renders can be dropped.
+static REFRESHED: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+pub fn refreshed_at() -> Option<SystemTime> {
+    *REFRESHED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The fetched sources, which are replaced on reload rather than looked up.
+pub fn stats() -> CacheStats {
+    let fetched = FETCHED.lock().unwrap_or_else(|e| e.into_inner());
+    CacheStats {
+        name: "remote_partials",
+        hits: None,
+        misses: None,
+        entries: fetched.len(),
+        bytes: Some(fetched.values().map(|source| source.len()).sum()),
+        reloaded_at: refreshed_at(),
+    }
+}
+
+async fn fetch_url(url: &str) -> Result<String, reqwest::Error> {
+    reqwest::Client::new()
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await
+}
+
+/// The partial's source from its URL, or else from its `fallback` file.
+/// A source that doesn't parse as a template counts as a failure, so it
+/// never replaces one that does.
+async fn fetch_one(partial: &RemotePartialConfig) -> Result<String, String> {
+    let error = match fetch_url(&partial.url).await {
+        Ok(source) => match Template::compile(&source) {
+            Ok(_) => return Ok(source),
+            Err(e) => format!("fetched {}, which doesn't parse: {}", partial.url, e),
+        },
+        Err(e) => format!("failed to fetch {}: {}", partial.url, e),
+    };
+    let Some(fallback) = &partial.fallback else {
+        return Err(error);
+    };
+
+    match fs::read_to_string(fallback).await {
+        Ok(source) if let Err(e) = Template::compile(&source) => Err(format!(
+            "{}, and {} doesn't parse: {}",
+            error,
+            fallback.display(),
+            e
+        )),
+        Ok(source) => {
+            eprintln!(
+                "Warning: remote partial '{}' {}; using {}",
+                partial.name,
+                error,
+                fallback.display()
+            );
+            Ok(source)
+        }
+        Err(e) => Err(format!(
+            "{}, and failed to read {}: {}",
+            error,
+            fallback.display(),
+            e
+        )),
+    }
+}
+
+/// Held while the partials are being fetched.
+static REFRESHING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// How many refreshes finished, and the errors of the last one.
+static LAST_REFRESH: Mutex<(u64, BTreeMap<String, String>)> = Mutex::new((0, BTreeMap::new()));
+
+/// Fetches every remote partial and stores the ones that loaded, returning
+/// an error per partial that didn't, by name. A partial that fails keeps its
+/// previous source; with `on_failure = "empty"` a partial that never loaded
+/// is registered empty instead, with a warning, and is not reported.
+///
+/// Calls made while a refresh is running wait for it and return its errors
+/// rather than fetching every partial again.
+pub async fn refresh(config: &Config) -> BTreeMap<String, String> {
+    let finished = LAST_REFRESH.lock().unwrap_or_else(|e| e.into_inner()).0;
+    let _refreshing = REFRESHING.lock().await;
+    {
+        let last = LAST_REFRESH.lock().unwrap_or_else(|e| e.into_inner());
+        if last.0 != finished {
+            return last.1.clone();
+        }
+    }
+
+    let errors = fetch_all(config).await;
+    let mut last = LAST_REFRESH.lock().unwrap_or_else(|e| e.into_inner());
+    *last = (last.0 + 1, errors.clone());
+    errors
+}
+
+async fn fetch_all(config: &Config) -> BTreeMap<String, String> {
+    let mut errors = BTreeMap::new();
+    for partial in &config.remote_partials {
+        let result = fetch_one(partial).await;
+        let mut fetched = FETCHED.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(source) => {
+                log::debug!("Fetched remote partial '{}'", partial.name);
+                if fetched.get(&partial.name).is_none_or(|old| **old != source) {
+                    fetched.insert(partial.name.clone(), source.into());
+                    *REFRESHED.lock().unwrap_or_else(|e| e.into_inner()) = Some(SystemTime::now());
+                }
+            }
+            Err(e) if fetched.contains_key(&partial.name) => {
+                errors.insert(partial.name.clone(), e);
+            }
+            Err(e) => match partial.on_failure {
+                RemoteFailurePolicy::Abort => {
+                    errors.insert(partial.name.clone(), e);
+                }
+                RemoteFailurePolicy::Empty => {
+                    eprintln!(
+                        "Warning: remote partial '{}' {}; registering it empty",
+                        partial.name, e
+                    );
+                    fetched.insert(partial.name.clone(), "".into());
+                }
+            },
+        }
+    }
+    errors
+}
+
+/// Registers the fetched partials, replacing local templates of the same
+/// name.
+pub fn register(handlebars: &mut Handlebars, config: &Config) -> Result<(), TemplateError> {
+    let fetched = FETCHED.lock().unwrap_or_else(|e| e.into_inner());
+    for partial in &config.remote_partials {
+        if let Some(source) = fetched.get(&partial.name) {
+            handlebars.register_template_string(&partial.name, &**source)?;
+        }
+    }
+    Ok(())
+}
+
+/// The fetched sources of the configured partials, by URL.
+pub fn sources(config: &Config) -> BTreeMap<String, Arc<str>> {
+    let fetched = FETCHED.lock().unwrap_or_else(|e| e.into_inner());
+    config
+        .remote_partials
+        .iter()
+        .filter_map(|partial| Some((partial.url.clone(), fetched.get(&partial.name)?.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Held by each test, as refreshes share their state across the process.
+    static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Serves `sources` in turn after `delay`, the last one from then on,
+    /// counting requests.
+    fn partial_server(
+        sources: &'static [&'static str],
+        delay: Duration,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/header.hbs", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let served = counted.fetch_add(1, Ordering::SeqCst);
+                let source = sources[served.min(sources.len() - 1)];
+                let _ = stream.read(&mut [0; 1024]);
+                std::thread::sleep(delay);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    source.len(),
+                    source
+                );
+            }
+        });
+        (url, requests)
+    }
+
+    fn remote_config(name: &str, url: String) -> Config {
+        Config {
+            remote_partials: vec![RemotePartialConfig {
+                name: name.to_string(),
+                url,
+                fallback: None,
+                on_failure: RemoteFailurePolicy::Abort,
+            }],
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_refreshes_fetch_once() {
+        const CONCURRENT: usize = 8;
+        let _serial = SERIAL.lock().await;
+        let (url, requests) = partial_server(&["<header/>"], Duration::from_millis(300));
+        let config = Arc::new(remote_config("partials/concurrent-header", url));
+
+        let barrier = Arc::new(tokio::sync::Barrier::new(CONCURRENT));
+        let mut tasks = Vec::new();
+        for _ in 0..CONCURRENT {
+            let (config, barrier) = (config.clone(), barrier.clone());
+            tasks.push(tokio::spawn(async move {
+                barrier.wait().await;
+                refresh(&config).await
+            }));
+        }
+        for task in tasks {
+            assert!(task.await.unwrap().is_empty());
+        }
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        let sources = sources(&config);
+        assert_eq!(
+            sources.values().map(|s| &**s).collect::<Vec<_>>(),
+            ["<header/>"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_source_that_does_not_parse_keeps_the_previous_one() {
+        let _serial = SERIAL.lock().await;
+        let (url, _) = partial_server(&["<nav/>", "{{#if}}"], Duration::ZERO);
+        let config = remote_config("partials/unparsable-nav", url);
+
+        assert!(refresh(&config).await.is_empty());
+        let failed = refresh(&config).await;
+        let error = &failed["partials/unparsable-nav"];
+        assert!(error.contains("which doesn't parse"), "{}", error);
+
+        let mut handlebars = Handlebars::new();
+        register(&mut handlebars, &config).unwrap();
+        assert_eq!(
+            handlebars.render("partials/unparsable-nav", &()).unwrap(),
+            "<nav/>"
+        );
+    }
+}
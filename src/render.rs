@@ -0,0 +1,440 @@
+use crate::config::Config;
+use crate::data::{self, DataError, Globals, load_data_files, load_locale_files, merge_map};
+use crate::derived;
+use crate::fallback;
+use crate::flags;
+use crate::fragments;
+use crate::helpers;
+use crate::limits;
+use crate::pages::{self, Page};
+use crate::post_process::{self, RenderedPage};
+use crate::remote_partials;
+use crate::transforms::{self, ContextTransform};
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, mime, web};
+use chrono::{DateTime, Utc};
+use handlebars::{DirectorySourceOptions, Handlebars, RenderError, RenderErrorReason, html_escape};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs;
+
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// The templates registered for a single request, along with the pages found.
+pub struct Site {
+    /// Shared with streamed renders, which outlive the request's borrow.
+    pub handlebars: Arc<Handlebars<'static>>,
+    pub pages: Vec<Page>,
+    /// Run over each context, after the `[[derived]]` keys.
+    pub transforms: Vec<Box<dyn ContextTransform>>,
+}
+
+impl Site {
+    /// Creates a fresh registry from the templates directory, so new and
+    /// changed templates are picked up without a restart.
+    pub async fn load(config: &Config) -> Result<Site, Box<dyn std::error::Error>> {
+        let mut handlebars = Handlebars::new();
+        handlebars.set_dev_mode(true);
+        handlebars.set_strict_mode(config.strict());
+        helpers::register(&mut handlebars, config);
+
+        // Register all templates from the templates directory
+        let templates_dir = config.templates_dir();
+        handlebars
+            .register_templates_directory(&templates_dir, DirectorySourceOptions::default())?;
+        fragments::register(&mut handlebars, config).await;
+        remote_partials::register(&mut handlebars, config)?;
+
+        // Read page front matter and register the page bodies without it
+        let pages = pages::discover_pages(&templates_dir).await?;
+        pages::register_page_bodies(&mut handlebars, &pages);
+        helpers::register_page_helpers(&mut handlebars, config, &pages);
+        limits::guard_partials(&mut handlebars, config);
+
+        Ok(Site {
+            handlebars: Arc::new(handlebars),
+            pages,
+            transforms: transforms::chain(config),
+        })
+    }
+
+    /// The page registered as `template_name`, e.g. `pages/blog/index`.
+    pub fn page(&self, template_name: &str) -> Option<&Page> {
+        self.pages
+            .iter()
+            .find(|page| page.template_name() == template_name)
+    }
+
+    /// A registry for `template_name` with the page's own helpers added, when
+    /// its front matter declares any. Otherwise the shared registry is used.
+    pub fn scoped_registry(&self, template_name: &str) -> Option<Handlebars<'static>> {
+        helpers::scoped_registry(&self.handlebars, self.page(template_name)?)
+    }
+
+    /// Builds the template context for a request to `route`, without a
+    /// locale overlay. Later layers take precedence: `[data.defaults]`, the
+    /// globals and the data directory, the page's own data, `--data-file`
+    /// and `--data` values, built-in keys, then `[[derived]]` keys. The
+    /// transforms run last.
+    pub async fn context(
+        &self,
+        config: &Config,
+        globals: &Globals,
+        route: &str,
+    ) -> Result<Map<String, Value>, Box<dyn std::error::Error>> {
+        Ok(self
+            .context_with_problems(config, globals, route, "")
+            .await?
+            .0)
+    }
+
+    /// `context` in `locale`, whose overlay under `locales/` goes between
+    /// the data directory and the page's own data, along with the data
+    /// files that failed to load and were left out of it.
+    pub async fn context_with_problems(
+        &self,
+        config: &Config,
+        globals: &Globals,
+        route: &str,
+        locale: &str,
+    ) -> Result<(Map<String, Value>, Vec<String>), DataError> {
+        let mut data = load_data_files(config, route).await?;
+        let overlay = load_locale_files(config, locale, route).await?;
+        for (key, time) in overlay.modified {
+            let latest = data.modified.entry(key).or_insert(time);
+            *latest = (*latest).max(time);
+        }
+        data.problems.extend(overlay.problems);
+        let version = data.modified.values().max().copied();
+        let modified = modified_context(config, globals, &data.modified).await;
+        let overlay: Map<String, Value> = overlay.values.into_iter().collect();
+
+        // Deep-merge the layers over the defaults, most specific last, so a
+        // key missing from one falls back to the one before it
+        let values: Map<String, Value> = data.values.into_iter().collect();
+        let page_data = fallback::page_data(&values, route);
+        let mut context = config.data.defaults.clone();
+        merge_map(&mut context, globals.0.clone());
+        merge_map(&mut context, values);
+        merge_map(&mut context, overlay);
+        if let Some((path, value)) = page_data {
+            fallback::merge_at(&mut context, &path, value);
+        }
+        merge_map(&mut context, config.data.overrides.clone());
+
+        // Expose the current path and the page list for navigation
+        insert_reserved(
+            &mut context,
+            config,
+            "path",
+            Value::String(route_path(route)),
+        );
+        insert_reserved(
+            &mut context,
+            config,
+            "pages",
+            pages::pages_context(&self.pages, config.routing.trailing_slash),
+        );
+        insert_reserved(
+            &mut context,
+            config,
+            "nav",
+            pages::nav_context(
+                &self.pages,
+                config.routing.trailing_slash,
+                &route_path(route),
+            ),
+        );
+        insert_reserved(&mut context, config, "modified", modified);
+        let flags = flags::context(config, &context);
+        insert_reserved(&mut context, config, "flags", Value::Object(flags));
+
+        derived::apply(&config.derived, &mut context);
+        transforms::apply(&self.transforms, &mut context, route);
+        data::check_context_budget(config, route, locale, version, &context);
+
+        Ok((context, data.problems))
+    }
+}
+
+/// The context for pages rendered without the data directory, such as
+/// `pages/503.hbs`: the defaults, the globals and `_path`.
+pub fn globals_context(config: &Config, globals: &Globals, route: &str) -> Map<String, Value> {
+    let mut context = config.data.defaults.clone();
+    merge_map(&mut context, globals.0.clone());
+    insert_reserved(
+        &mut context,
+        config,
+        "path",
+        Value::String(route_path(route)),
+    );
+    context
+}
+
+/// When the files behind each top-level key last changed, as RFC 3339
+/// timestamps. A key set by both the globals file and `data/` reports the
+/// later of the two.
+async fn modified_context(
+    config: &Config,
+    globals: &Globals,
+    data: &BTreeMap<String, SystemTime>,
+) -> Value {
+    let mut modified: BTreeMap<&str, SystemTime> = BTreeMap::new();
+    if let Some(path) = &config.data.globals
+        && let Ok(globals_modified) = fs::metadata(path).await.and_then(|m| m.modified())
+    {
+        for key in globals.0.keys() {
+            modified.insert(key, globals_modified);
+        }
+    }
+    for (key, time) in data {
+        let latest = modified.entry(key).or_insert(*time);
+        *latest = (*latest).max(*time);
+    }
+    Value::Object(
+        modified
+            .into_iter()
+            .map(|(key, time)| {
+                let time: DateTime<Utc> = time.into();
+                (key.to_string(), Value::String(time.to_rfc3339()))
+            })
+            .collect(),
+    )
+}
+
+/// The URL path of a route without trailing slash or `index`: `blog/index`
+/// and `blog` are both `/blog`, and `index` is `/`.
+pub fn route_path(route: &str) -> String {
+    let route = route.trim_matches('/');
+    let route = route.strip_suffix("/index").unwrap_or(route);
+    match route {
+        "" | "index" => "/".to_string(),
+        route => format!("/{}", route),
+    }
+}
+
+/// Inserts a server-provided value under `name` with the reserved prefix,
+/// e.g. `_pages`. It takes precedence over user data with the same key.
+pub fn insert_reserved(
+    context: &mut Map<String, Value>,
+    config: &Config,
+    name: &str,
+    value: Value,
+) {
+    let key = format!("{}{}", config.reserved_prefix(), name);
+    if context.contains_key(&key) {
+        eprintln!(
+            "Warning: data key '{}' is shadowed by the built-in '{}' value; rename the data or change `reserved_prefix` under [render]",
+            key, name
+        );
+    }
+    context.insert(key, value);
+}
+
+/// Runs the post-processing pipeline the config sets up over a rendered
+/// page, see `src/post_process.rs`.
+pub fn post_process(config: &Config, page: &RenderedPage, html: String) -> String {
+    post_process::apply(&post_process::pipeline(config), page, html)
+}
+
+/// Body for an error response, with the underlying error appended when
+/// `error_details` is on.
+pub fn error_body(config: &Config, message: &str, error: &dyn std::fmt::Display) -> String {
+    match config.error_details() {
+        true => format!("{}\n\n{}", message, error),
+        false => message.to_string(),
+    }
+}
+
+/// Whether the client ranks JSON above HTML in its `Accept` header, e.g. an
+/// API client sending `Accept: application/json`. `*/*` counts as HTML.
+pub fn prefers_json(req: &HttpRequest) -> bool {
+    let Some(accept) = req.get_header::<header::Accept>() else {
+        return false;
+    };
+    accept
+        .ranked()
+        .into_iter()
+        .find_map(|mime| {
+            if mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON) {
+                Some(true)
+            } else if mime.subtype() == mime::HTML || mime.type_() == mime::STAR {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(false)
+}
+
+/// `{"error": {"status", "code", "message", "path"}}`, with the underlying
+/// error as `detail` when `error_details` is on.
+fn json_error(
+    config: &Config,
+    status: StatusCode,
+    code: &str,
+    message: &str,
+    path: &str,
+    detail: &dyn std::fmt::Display,
+) -> HttpResponse {
+    let mut error = json!({
+        "status": status.as_u16(),
+        "code": code,
+        "message": message,
+        "path": path,
+    });
+    if config.error_details() {
+        error["detail"] = Value::String(detail.to_string());
+    }
+    HttpResponse::build(status).json(json!({ "error": error }))
+}
+
+/// The request's query parameters as an object of strings. When a name
+/// repeats, the last value wins.
+pub fn query_context(req: &HttpRequest) -> Value {
+    let parameters = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    Value::Object(
+        parameters
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect(),
+    )
+}
+
+/// An error response in the format the client prefers: the plain
+/// `error_body`, or a JSON object carrying the machine-readable `code`.
+pub fn error_response(
+    config: &Config,
+    req: &HttpRequest,
+    status: StatusCode,
+    code: &str,
+    message: &str,
+    error: &dyn std::fmt::Display,
+) -> HttpResponse {
+    match prefers_json(req) {
+        true => json_error(config, status, code, message, req.path(), error),
+        false => HttpResponse::build(status).body(error_body(config, message, error)),
+    }
+}
+
+/// The file a registered template was loaded from, e.g. `templates/pages/about.hbs`.
+pub fn template_path(template_name: &str) -> String {
+    format!(
+        "{}/{}.{}",
+        TEMPLATES_DIR,
+        template_name,
+        pages::TEMPLATE_EXTENSION
+    )
+}
+
+/// Explains the common causes of a render failure, naming what was missing
+/// and where it was referenced.
+fn describe_render_error(template_name: &str, error: &RenderError) -> Option<String> {
+    let source = template_path(error.template_name.as_deref().unwrap_or(template_name));
+    let location = match (error.line_no, error.column_no) {
+        (Some(line), Some(column)) => format!("{}:{}:{}", source, line, column),
+        _ => source,
+    };
+
+    match error.reason() {
+        RenderErrorReason::PartialNotFound(partial) => Some(format!(
+            "Partial '{}' not found, referenced from {}. Partials are named by their path under {}/, so it should be {}.",
+            partial,
+            location,
+            TEMPLATES_DIR,
+            template_path(partial)
+        )),
+        RenderErrorReason::HelperNotFound(helper) => Some(format!(
+            "Helper '{}' is not registered, used in {}. Check the helper name for typos.",
+            helper, location
+        )),
+        // Without arguments, `{{name}}` is looked up as a variable, so a
+        // mistyped helper shows up as a missing variable
+        RenderErrorReason::MissingVariable(Some(name)) => Some(format!(
+            "Variable '{}' is not defined, used in {}. If '{}' is meant to be a helper, it is not registered.",
+            name, location, name
+        )),
+        _ => None,
+    }
+}
+
+fn log_render_failure(template_name: &str, error: &RenderError) -> Option<String> {
+    eprintln!(
+        "Template rendering error for '{}': {}",
+        template_name, error
+    );
+    let diagnostic = describe_render_error(template_name, error);
+    if let Some(diagnostic) = &diagnostic {
+        eprintln!("{}", diagnostic);
+    }
+    diagnostic
+}
+
+/// `render_failure` for clients that prefer JSON; the diagnostic, if any,
+/// becomes the `detail`.
+pub fn render_failure_json(
+    config: &Config,
+    req: &HttpRequest,
+    template_name: &str,
+    error: &RenderError,
+) -> HttpResponse {
+    let diagnostic = log_render_failure(template_name, error);
+    let message = format!("Rendering '{}' failed", template_name);
+    let detail = diagnostic.unwrap_or_else(|| error.to_string());
+    json_error(
+        config,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "render_failed",
+        &message,
+        req.path(),
+        &detail,
+    )
+}
+
+/// Responds with a 500 to a page that rendered to nothing but whitespace,
+/// which usually means the data it expects wasn't loaded.
+pub fn empty_output(config: &Config, req: &HttpRequest, template_name: &str) -> HttpResponse {
+    let message = format!("Rendering '{}' produced no output", template_name);
+    let reason = format!(
+        "{} rendered only whitespace. Check that the data files it uses exist and define the keys it reads, or set fail_on_empty = false under [render] to allow blank pages.",
+        template_path(template_name)
+    );
+    eprintln!("{}", reason);
+    error_response(
+        config,
+        req,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "empty_output",
+        &message,
+        &reason,
+    )
+}
+
+/// Responds to a failed render of an existing template with a 500. With
+/// `error_details` on, the page explains the cause; otherwise it is only logged.
+pub fn render_failure(config: &Config, template_name: &str, error: &RenderError) -> HttpResponse {
+    let diagnostic = log_render_failure(template_name, error);
+
+    let mut response = HttpResponse::InternalServerError();
+    if !config.error_details() {
+        return response.body(format!("Rendering '{}' failed", template_name));
+    }
+
+    let mut body = format!(
+        "<!DOCTYPE html>\n<html>\n<head><title>Rendering failed</title></head>\n<body>\n<h1>Rendering '{}' failed</h1>\n",
+        html_escape(template_name)
+    );
+    if let Some(diagnostic) = diagnostic {
+        body.push_str(&format!("<p>{}</p>\n", html_escape(&diagnostic)));
+    }
+    body.push_str(&format!(
+        "<pre>{}</pre>\n</body>\n</html>\n",
+        html_escape(&error.to_string())
+    ));
+
+    response.content_type("text/html; charset=utf-8").body(body)
+}
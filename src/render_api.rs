@@ -0,0 +1,125 @@
+//! `POST /_render/<template>` renders any registered template, such as an
+//! email or a partial, against the posted JSON object, for server-driven UI
+//! and other services. Routed when `token` is set under `[render_api]` and
+//! requiring it as a bearer token.
+
+use crate::auth;
+use crate::config::Config;
+use crate::limits;
+use crate::pages;
+use crate::post_process::RenderedPage;
+use crate::render;
+use crate::sites::{self, Registry};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use serde_json::{Value, json};
+
+pub const ENDPOINT: &str = "/_render/{template:.+}";
+
+/// Contexts may be a whole page's worth of data.
+pub const BODY_LIMIT: usize = 4 * 1024 * 1024;
+
+pub fn is_enabled(config: &Config) -> bool {
+    config.render_api.token.is_some()
+}
+
+fn is_authorized(req: &HttpRequest, config: &Config) -> bool {
+    config
+        .render_api
+        .token
+        .as_deref()
+        .is_some_and(|token| auth::has_bearer_token(req, token))
+}
+
+/// Whether the client asked for the JSON envelope rather than the output
+/// itself.
+fn wants_envelope(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.trim_start().starts_with("application/json"))
+}
+
+/// Renders `template`, e.g. `emails/welcome`, against the posted object
+/// alone; neither `data/` nor the reserved keys are added. The content type
+/// is the one set for the template under `[render_api.content_types]`, else
+/// its front matter `content_type` for pages, else HTML. With
+/// `Accept: application/json` the output is wrapped as
+/// `{"template", "content_type", "output"}` instead.
+pub async fn render(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<Config>,
+    registry: Option<web::Data<Registry>>,
+    body: web::Json<Value>,
+) -> Result<HttpResponse> {
+    if !is_authorized(&req, &config) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+
+    let Value::Object(context) = body.into_inner() else {
+        return Ok(HttpResponse::BadRequest().body("Render context must be a JSON object"));
+    };
+
+    let site = match sites::site(&config, registry.as_ref()).await {
+        Ok(site) => site,
+        Err(e) => {
+            eprintln!("Failed to register templates: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load templates",
+                &e,
+            )));
+        }
+    };
+
+    let template_name = path.into_inner();
+    let template_name = template_name.trim_matches('/');
+    if !site.handlebars.has_template(template_name) {
+        return Ok(HttpResponse::NotFound().body(format!("Template '{}' not found", template_name)));
+    }
+
+    let content_type = match config.render_api.content_types.get(template_name) {
+        Some(content_type) => content_type.as_str(),
+        None => site
+            .page(template_name)
+            .map_or(pages::DEFAULT_CONTENT_TYPE, |page| page.content_type()),
+    };
+
+    // The caller is not a browser, so never inject the reload script
+    let mut output_config = config.get_ref().clone();
+    output_config.html.live_reload = Some(false);
+
+    let scoped = site.scoped_registry(template_name);
+    let handlebars = scoped.as_ref().unwrap_or(&*site.handlebars);
+    let rendered = match limits::render(handlebars, &config, template_name, &context) {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            return Ok(render::render_failure_json(
+                &config,
+                &req,
+                template_name,
+                &e,
+            ));
+        }
+    };
+    let page = RenderedPage {
+        template_name,
+        content_type,
+        panel: None,
+    };
+    let output = render::post_process(&output_config, &page, rendered);
+
+    let mut response = HttpResponse::Ok();
+    response.insert_header((header::CACHE_CONTROL, "no-store"));
+    Ok(match wants_envelope(&req) {
+        true => response.json(json!({
+            "template": template_name,
+            "content_type": content_type,
+            "output": output,
+        })),
+        false => response.content_type(content_type).body(output),
+    })
+}
@@ -0,0 +1,478 @@
+use crate::cache_stats::{CacheStats, Counters};
+use crate::config::{Config, ExpiryPolicy, RenderConfig};
+use crate::live_reload;
+use crate::remote_partials;
+use actix_web::web;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+use tokio::sync::watch;
+
+/// Response header reporting `hit`, `stale` or `miss` while the cache is on.
+pub const HEADER: &str = "X-Render-Cache";
+
+/// Query parameters the server itself reads, which are always keyed: `amp`
+/// selects the AMP variant and `flags` overrides feature flags in `dev`.
+const KEYED_PARAMETERS: [&str; 2] = ["amp", "flags"];
+
+/// By requested page path, with its keyed query parameters, and locale.
+type Key = (String, String);
+
+/// A finished page response.
+#[derive(Clone)]
+pub struct CachedPage {
+    /// The page template, e.g. `pages/about`.
+    pub template_name: String,
+    pub content_type: String,
+    pub body: String,
+    /// The `Cache-Control` the page was served with.
+    pub cache_control: Option<String>,
+    /// When the page's front matter `cache` or the default `max_age` runs
+    /// out, if either is set.
+    pub expires: Option<Instant>,
+}
+
+impl CachedPage {
+    fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= Instant::now())
+    }
+}
+
+struct Entry {
+    page: CachedPage,
+    /// The `Entries::clock` value when the page was last served or stored.
+    used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    /// When the sources last changed as of the cached renders.
+    version: Option<SystemTime>,
+    pages: HashMap<Key, Entry>,
+    /// The cached keys by when they were last used, least recent first.
+    recency: BTreeMap<u64, Key>,
+    clock: u64,
+    /// Pages a request is rendering, as they weren't cached or expired. The
+    /// receiver reports when that request is done, as its sender is dropped.
+    refreshing: HashMap<Key, watch::Receiver<()>>,
+}
+
+impl Entries {
+    /// Marks `key` as the most recently used.
+    fn touch(&mut self, key: &Key) {
+        if let Some(entry) = self.pages.get_mut(key) {
+            self.recency.remove(&entry.used);
+            self.clock += 1;
+            entry.used = self.clock;
+            self.recency.insert(self.clock, key.clone());
+        }
+    }
+
+    fn insert(&mut self, key: Key, page: CachedPage, capacity: usize) {
+        self.remove(&key);
+        if capacity == 0 {
+            return;
+        }
+        while self.pages.len() >= capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.pages.remove(&oldest);
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.pages.insert(
+            key,
+            Entry {
+                page,
+                used: self.clock,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.pages.remove(key) {
+            self.recency.remove(&entry.used);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.recency.clear();
+    }
+}
+
+/// What `RenderCache::lookup` found.
+pub enum Lookup<'a> {
+    /// A render that hasn't expired.
+    Hit(CachedPage),
+    /// An expired render, served while another request renders the page
+    /// again, with `on_expiry = "stale"`.
+    Stale(CachedPage),
+    /// Nothing to serve, so the caller renders the page. With a `Refresh`,
+    /// the caller is the one request rendering it until that is dropped.
+    Miss(Option<Refresh<'a>>),
+}
+
+/// Held by the request rendering a page that isn't cached or expired. Other
+/// requests for the page wait for it, or get the expired render, until it
+/// is dropped.
+pub struct Refresh<'a> {
+    cache: &'a RenderCache,
+    key: Key,
+    _done: watch::Sender<()>,
+}
+
+impl Drop for Refresh<'_> {
+    fn drop(&mut self) {
+        let mut entries = self.cache.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.refreshing.remove(&self.key);
+        // The render failed or wasn't cacheable; the waiting requests render
+        // the page themselves rather than one after another
+        if entries
+            .pages
+            .get(&self.key)
+            .is_some_and(|entry| entry.page.is_expired())
+        {
+            entries.remove(&self.key);
+        }
+    }
+}
+
+/// Rendered pages, shared by all workers and dropped as a whole whenever a
+/// template, data file, static file or the globals file changes. Past
+/// `capacity` renders, the least recently used is evicted.
+pub struct RenderCache {
+    entries: Mutex<Entries>,
+    counters: Counters,
+    capacity: usize,
+}
+
+/// The sources' version to key renders by: their latest modification time
+/// as of the last poll, so a request only reads it. Reloading the remote
+/// partials counts as a change too.
+pub async fn version(config: &Config) -> Option<SystemTime> {
+    live_reload::polled_modified(config)
+        .await
+        .max(remote_partials::refreshed_at())
+}
+
+/// Whether renders are keyed by the query parameter `name`.
+fn is_keyed(config: &RenderConfig, name: &str) -> bool {
+    KEYED_PARAMETERS.contains(&name) || config.cache_query.iter().any(|keyed| keyed == name)
+}
+
+/// The path `page` requested with `query`, as the cache keys it: only the
+/// keyed parameters are kept, sorted by name, so other parameters can't
+/// fill the cache with copies of a page.
+pub fn key(config: &RenderConfig, page: &str, query: &str) -> String {
+    let mut parameters: Vec<(String, String)> =
+        web::Query::<Vec<(String, String)>>::from_query(query)
+            .map(web::Query::into_inner)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(name, _)| is_keyed(config, name))
+            .collect();
+    if parameters.is_empty() {
+        return page.to_string();
+    }
+    parameters.sort_by(|a, b| a.0.cmp(&b.0));
+    // JSON keeps names and values apart, whatever they contain
+    let parameters = serde_json::to_string(&parameters).unwrap_or_default();
+    format!("{}?{}", page, parameters)
+}
+
+/// Drops the parameters renders aren't keyed by from the `query` context of
+/// a cacheable page, as its render is reused whatever their values.
+pub fn retain_keyed(config: &RenderConfig, query: &mut Value) {
+    if let Value::Object(query) = query {
+        query.retain(|name, _| is_keyed(config, name));
+    }
+}
+
+impl RenderCache {
+    pub fn new(capacity: usize) -> RenderCache {
+        RenderCache {
+            entries: Mutex::default(),
+            counters: Counters::default(),
+            capacity,
+        }
+    }
+
+    /// The cached render of `page` in `locale`. When there is none, or it
+    /// expired, the first request renders the page while the others wait for
+    /// that render, or get the expired one with `on_expiry = "stale"`, so a
+    /// miss under load costs a single render. Should that render not be
+    /// stored, the waiting requests render the page themselves.
+    pub async fn lookup(
+        &self,
+        version: Option<SystemTime>,
+        page: &str,
+        locale: &str,
+        on_expiry: ExpiryPolicy,
+    ) -> Lookup<'_> {
+        let key = (page.to_string(), locale.to_string());
+        let mut waited = false;
+        loop {
+            let mut refreshed = {
+                let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+                let entries = &mut *entries;
+                if entries.version != version {
+                    entries.version = version;
+                    entries.clear();
+                    self.counters.reloaded();
+                }
+                let cached = entries.pages.get(&key).map(|entry| entry.page.clone());
+                if let Some(cached) = &cached
+                    && !cached.is_expired()
+                {
+                    entries.touch(&key);
+                    self.counters.hit();
+                    return Lookup::Hit(cached.clone());
+                }
+                match (entries.refreshing.get(&key), cached) {
+                    (Some(_), Some(cached)) if on_expiry == ExpiryPolicy::Stale => {
+                        self.counters.hit();
+                        return Lookup::Stale(cached);
+                    }
+                    // The render waited for wasn't stored
+                    (_, None) if waited => {
+                        self.counters.miss();
+                        return Lookup::Miss(None);
+                    }
+                    (Some(refreshed), _) => refreshed.clone(),
+                    (None, _) => {
+                        let (done, refreshed) = watch::channel(());
+                        entries.refreshing.insert(key.clone(), refreshed);
+                        self.counters.miss();
+                        return Lookup::Miss(Some(Refresh {
+                            cache: self,
+                            key,
+                            _done: done,
+                        }));
+                    }
+                }
+            };
+            // Fails once the rendering request drops its sender
+            let _ = refreshed.changed().await;
+            waited = true;
+        }
+    }
+
+    /// How many renders are cached.
+    pub fn len(&self) -> usize {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.pages.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let bytes = entries
+            .pages
+            .values()
+            .map(|entry| entry.page.body.len())
+            .sum();
+        self.counters
+            .stats("render", entries.pages.len(), Some(bytes))
+    }
+
+    pub fn insert(
+        &self,
+        version: Option<SystemTime>,
+        page: &str,
+        locale: &str,
+        cached: CachedPage,
+    ) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        // The sources changed while rendering; the next request re-renders
+        if entries.version != version {
+            return;
+        }
+        let key = (page.to_string(), locale.to_string());
+        entries.insert(key, cached, self.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn page(body: &str) -> CachedPage {
+        CachedPage {
+            template_name: "pages/index".to_string(),
+            content_type: "text/html".to_string(),
+            body: body.to_string(),
+            cache_control: None,
+            expires: None,
+        }
+    }
+
+    #[test]
+    fn key_keeps_only_keyed_parameters() {
+        let config = RenderConfig {
+            cache_query: vec!["page".to_string()],
+            ..RenderConfig::default()
+        };
+        assert_eq!(key(&config, "blog", ""), "blog");
+        assert_eq!(key(&config, "blog", "utm_source=x&ref=y"), "blog");
+        assert_eq!(
+            key(&config, "blog", "page=2&utm_source=x"),
+            key(&config, "blog", "utm_source=y&page=2")
+        );
+        assert_ne!(
+            key(&config, "blog", "page=2"),
+            key(&config, "blog", "page=3")
+        );
+        // Encoded names are keyed like plain ones
+        assert_eq!(
+            key(&config, "blog", "%70age=2"),
+            key(&config, "blog", "page=2")
+        );
+        assert_ne!(key(&config, "blog", "amp"), "blog");
+    }
+
+    #[test]
+    fn retain_keyed_drops_other_parameters() {
+        let config = RenderConfig::default();
+        let mut query = serde_json::json!({"amp": "1", "q": "x"});
+        retain_keyed(&config, &mut query);
+        assert_eq!(query, serde_json::json!({"amp": "1"}));
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used() {
+        let cache = RenderCache::new(2);
+        let version = Some(SystemTime::UNIX_EPOCH);
+        // The first lookup records the version
+        assert!(matches!(
+            cache.lookup(version, "a", "", ExpiryPolicy::Wait).await,
+            Lookup::Miss(Some(_))
+        ));
+        cache.insert(version, "a", "", page("a"));
+        cache.insert(version, "b", "", page("b"));
+        assert!(matches!(
+            cache.lookup(version, "a", "", ExpiryPolicy::Wait).await,
+            Lookup::Hit(_)
+        ));
+        cache.insert(version, "c", "", page("c"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(matches!(
+            cache.lookup(version, "b", "", ExpiryPolicy::Wait).await,
+            Lookup::Miss(Some(_))
+        ));
+        assert!(matches!(
+            cache.lookup(version, "a", "", ExpiryPolicy::Wait).await,
+            Lookup::Hit(_)
+        ));
+    }
+
+    /// How `CONCURRENT` simultaneous lookups of a page went, by kind, with
+    /// an `expired` render cached or, without, on a cache that hasn't seen
+    /// the version yet. The one that gets to render waits for the others to
+    /// wait or be served before storing a fresh render.
+    async fn miss_under_load(
+        on_expiry: ExpiryPolicy,
+        expired: bool,
+    ) -> BTreeMap<&'static str, usize> {
+        const CONCURRENT: usize = 8;
+        let cache = Arc::new(RenderCache::new(4));
+        let version = Some(SystemTime::UNIX_EPOCH);
+        if expired {
+            cache.lookup(version, "index", "", on_expiry).await;
+            let expired = CachedPage {
+                expires: Some(Instant::now()),
+                ..page("old")
+            };
+            cache.insert(version, "index", "", expired);
+        }
+
+        let barrier = Arc::new(tokio::sync::Barrier::new(CONCURRENT));
+        let served = Arc::new(AtomicUsize::new(0));
+        let mut tasks = Vec::new();
+        for _ in 0..CONCURRENT {
+            let (cache, barrier, served) = (cache.clone(), barrier.clone(), served.clone());
+            tasks.push(tokio::spawn(async move {
+                barrier.wait().await;
+                match cache.lookup(version, "index", "", on_expiry).await {
+                    Lookup::Miss(Some(refresh)) => {
+                        // Every other lookup is waiting on it or was served
+                        while refresh._done.receiver_count() + served.load(Ordering::SeqCst)
+                            < CONCURRENT
+                        {
+                            tokio::task::yield_now().await;
+                        }
+                        cache.insert(version, "index", "", page("new"));
+                        "refresh"
+                    }
+                    Lookup::Miss(None) => "miss",
+                    Lookup::Hit(cached) => {
+                        assert_eq!(cached.body, "new");
+                        "hit"
+                    }
+                    Lookup::Stale(cached) => {
+                        assert_eq!(cached.body, "old");
+                        served.fetch_add(1, Ordering::SeqCst);
+                        "stale"
+                    }
+                }
+            }));
+        }
+
+        let mut outcomes = BTreeMap::new();
+        for task in tasks {
+            *outcomes.entry(task.await.unwrap()).or_default() += 1;
+        }
+        outcomes
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn an_expired_render_is_rendered_once_while_others_wait() {
+        let outcomes = miss_under_load(ExpiryPolicy::Wait, true).await;
+        assert_eq!(outcomes, BTreeMap::from([("hit", 7), ("refresh", 1)]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn an_expired_render_is_served_stale_while_one_renders() {
+        let outcomes = miss_under_load(ExpiryPolicy::Stale, true).await;
+        assert_eq!(outcomes, BTreeMap::from([("refresh", 1), ("stale", 7)]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn a_cold_page_is_rendered_once_while_others_wait() {
+        // Nothing stale to serve, so every policy waits
+        for on_expiry in [ExpiryPolicy::Wait, ExpiryPolicy::Stale] {
+            let outcomes = miss_under_load(on_expiry, false).await;
+            assert_eq!(outcomes, BTreeMap::from([("hit", 7), ("refresh", 1)]));
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_requests_render_when_the_render_is_not_stored() {
+        let cache = Arc::new(RenderCache::new(4));
+        let version = Some(SystemTime::UNIX_EPOCH);
+        let Lookup::Miss(Some(refresh)) =
+            cache.lookup(version, "index", "", ExpiryPolicy::Wait).await
+        else {
+            panic!("the first lookup should render");
+        };
+        let waiting = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                matches!(
+                    cache.lookup(version, "index", "", ExpiryPolicy::Wait).await,
+                    Lookup::Miss(None)
+                )
+            })
+        };
+        while refresh._done.receiver_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+        drop(refresh);
+        assert!(waiting.await.unwrap());
+    }
+}
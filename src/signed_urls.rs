@@ -0,0 +1,219 @@
+//! Time-limited links to gated downloads. The `signedUrl` helper links to a
+//! file below `/static/` with an expiry and an HMAC-SHA256 signature of the
+//! path and expiry, and files under the `signed` prefixes of `[static]` are
+//! only served with a matching, unexpired signature.
+
+use crate::auth;
+use crate::config::{Config, StaticConfig};
+use crate::static_files;
+use actix_web::web;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Query parameter carrying the expiry, in Unix seconds.
+pub const EXPIRES_PARAM: &str = "expires";
+
+/// Query parameter carrying the hex signature.
+pub const SIGNATURE_PARAM: &str = "signature";
+
+/// SHA-256 block size, which HMAC pads the key to.
+const BLOCK_SIZE: usize = 64;
+
+/// HMAC-SHA256, as in RFC 2104.
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0; BLOCK_SIZE];
+    match key.len() > BLOCK_SIZE {
+        true => block[..32].copy_from_slice(&Sha256::digest(key)),
+        false => block[..key.len()].copy_from_slice(key),
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .to_vec()
+}
+
+/// The path as signed, without empty segments, so `/downloads//a.zip` and
+/// `downloads/a.zip` are the same file.
+pub fn normalize(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The hex signature of `path` expiring at `expires`.
+pub fn signature(secret: &str, path: &str, expires: i64) -> String {
+    let message = format!("{}\n{}", normalize(path), expires);
+    hmac(secret.as_bytes(), message.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// `path` with its longest existing ancestor canonicalized and the rest
+/// appended unchanged, so a path that doesn't exist compares like one that
+/// does.
+fn canonical(path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = match parent.as_os_str().is_empty() {
+                    true => Path::new("."),
+                    false => parent,
+                };
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Whether the file `path`, below `/static/`, is only served with a
+/// signature. The file and the `signed` prefixes are compared as
+/// canonicalized paths below the static directory, component by component,
+/// so `downloadsx/` isn't under `downloads`, and a symlink or different
+/// letter case on a case-insensitive file system doesn't get around one.
+pub fn is_signed(config: &Config, path: &str) -> bool {
+    if config.static_files.signed.is_empty() {
+        return false;
+    }
+    let static_dir = config.static_dir();
+    // Not served at all, so there is nothing to gate
+    let Some(file) = static_files::resolve(&static_dir, path) else {
+        return false;
+    };
+    let file = canonical(&file);
+    config.static_files.signed.iter().any(|prefix| {
+        static_files::resolve(&static_dir, prefix)
+            .is_some_and(|prefix| file.starts_with(canonical(&prefix)))
+    })
+}
+
+/// Checks the signature and expiry in `query` for `path`, returning why the
+/// file may not be served. Without a secret nothing is served.
+pub fn verify(config: &StaticConfig, path: &str, query: &str) -> Result<(), &'static str> {
+    let Some(secret) = config.signing_secret() else {
+        return Err("no signing secret is configured");
+    };
+    let parameters = web::Query::<Vec<(String, String)>>::from_query(query)
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    let parameter = |name: &str| {
+        parameters
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let (Some(expires), Some(given)) = (parameter(EXPIRES_PARAM), parameter(SIGNATURE_PARAM))
+    else {
+        return Err("the link is not signed");
+    };
+    let expires: i64 = expires.parse().map_err(|_| "the expiry is invalid")?;
+
+    let expected = signature(&secret, path, expires);
+    if !auth::tokens_match(given.as_bytes(), expected.as_bytes()) {
+        return Err("the signature is invalid");
+    }
+    if expires < Utc::now().timestamp() {
+        return Err("the link has expired");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The HMAC-SHA256 vectors of RFC 4231, except case 5, which truncates
+    /// the output.
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        let large_key = [0xaa; 131];
+        let cases: [(&[u8], &[u8], &str); 6] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 20],
+                &[0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                &(1..=25).collect::<Vec<u8>>(),
+                &[0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                &large_key,
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &large_key,
+                b"This is a test using a larger than block-size key and a larger than \
+                  block-size data. The key needs to be hashed before being used by the \
+                  HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for (key, message, expected) in cases {
+            assert_eq!(hex(&hmac(key, message)), expected);
+        }
+    }
+
+    #[test]
+    fn signed_prefixes_match_whole_directories() {
+        let root = std::env::temp_dir().join(format!("hbs-composer-signed-{}", std::process::id()));
+        let static_dir = root.join("static");
+        std::fs::create_dir_all(static_dir.join("downloads")).unwrap();
+        std::fs::create_dir_all(static_dir.join("downloadsx")).unwrap();
+        std::fs::write(static_dir.join("downloads/a.zip"), "a").unwrap();
+        std::fs::write(static_dir.join("downloadsx/b.zip"), "b").unwrap();
+        let mut config = Config {
+            root: root.clone(),
+            ..Config::default()
+        };
+        config.static_files.signed = vec!["downloads/".to_string()];
+
+        assert!(is_signed(&config, "downloads/a.zip"));
+        assert!(is_signed(&config, "/downloads//a.zip"));
+        assert!(is_signed(&config, "downloads/missing.zip"));
+        assert!(!is_signed(&config, "downloadsx/b.zip"));
+        assert!(!is_signed(&config, "app.css"));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(static_dir.join("downloads"), static_dir.join("public"))
+                .unwrap();
+            assert!(is_signed(&config, "public/a.zip"));
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::config::Config;
+use crate::render;
+use crate::sites::{self, Registry};
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// The public origin for absolute URLs, without a trailing slash.
+pub fn base_url(req: &HttpRequest, config: &Config) -> String {
+    match &config.site.base_url {
+        Some(base_url) => base_url.trim_end_matches('/').to_string(),
+        None => {
+            let info = req.connection_info();
+            format!("{}://{}", info.scheme(), info.host())
+        }
+    }
+}
+
+/// Serves `/sitemap.xml` listing every public page that isn't excluded by
+/// its front matter.
+pub async fn sitemap(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    registry: Option<web::Data<Registry>>,
+) -> Result<HttpResponse> {
+    let site = match sites::site(&config, registry.as_ref()).await {
+        Ok(site) => site,
+        Err(e) => {
+            eprintln!("Failed to register templates: {}", e);
+            return Ok(HttpResponse::InternalServerError().body(render::error_body(
+                &config,
+                "Failed to load templates",
+                &e,
+            )));
+        }
+    };
+
+    let base_url = base_url(&req, &config);
+    let mut pages: Vec<_> = site.pages.iter().filter(|page| page.in_sitemap()).collect();
+    pages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for page in pages {
+        let url = format!("{}{}", base_url, page.url(config.routing.trailing_slash));
+        xml.push_str(&format!("  <url><loc>{}</loc></url>\n", escape_xml(&url)));
+    }
+    xml.push_str("</urlset>\n");
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(xml))
+}
@@ -0,0 +1,292 @@
+//! Sites served by `Host` header, declared with `[[sites]]`. Each has its own
+//! directory, config (see `Config::for_site`), globals, template registry,
+//! render cache and `/ready` status; requests for any other host get the
+//! site in the working directory.
+
+use crate::config::Config;
+use crate::data::Globals;
+use crate::fallback;
+use crate::health::Health;
+use crate::helpers;
+use crate::remote_partials;
+use crate::render::Site;
+use crate::render_cache::{self, RenderCache};
+use actix_web::guard::{self, Guard};
+use actix_web::http::header;
+use actix_web::web;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+
+/// A site's templates, built at startup and kept between requests. In dev
+/// they are built again once the sources change; otherwise only once the
+/// remote partials were reloaded.
+#[derive(Default)]
+pub struct Registry {
+    /// The site, with the sources' version it was built from.
+    built: Mutex<Option<(Option<SystemTime>, Arc<Site>)>>,
+}
+
+impl Registry {
+    /// The kept site, rebuilt first when it is out of date. A failed build
+    /// keeps nothing, so the next request tries again.
+    pub async fn site(&self, config: &Config) -> Result<Arc<Site>, Box<dyn std::error::Error>> {
+        let version = match config.is_dev() {
+            true => render_cache::version(config).await,
+            false => remote_partials::refreshed_at(),
+        };
+        // Held while building, so concurrent requests wait for one build
+        let mut built = self.built.lock().await;
+        if let Some((built_version, site)) = &*built
+            && *built_version == version
+        {
+            return Ok(site.clone());
+        }
+        *built = None;
+        let site = Arc::new(Site::load(config).await?);
+        fallback::check_overlays(config).await;
+        *built = Some((version, site.clone()));
+        Ok(site)
+    }
+}
+
+/// The templates for a request: the kept registry of a `[[sites]]` site,
+/// or a fresh load of the working directory's site.
+pub async fn site(
+    config: &Config,
+    registry: Option<&web::Data<Registry>>,
+) -> Result<Arc<Site>, Box<dyn std::error::Error>> {
+    match registry {
+        Some(registry) => registry.site(config).await,
+        None => Ok(Arc::new(Site::load(config).await?)),
+    }
+}
+
+#[derive(Clone)]
+pub struct HostSite {
+    /// Lowercased, without port.
+    hosts: Vec<String>,
+    pub config: web::Data<Config>,
+    pub globals: web::Data<Globals>,
+    pub cache: web::Data<RenderCache>,
+    pub health: web::Data<Health>,
+    pub registry: web::Data<Registry>,
+}
+
+impl HostSite {
+    /// Matches requests whose `Host` is one of the site's hosts, on any port.
+    pub fn guard(&self) -> impl Guard + use<> {
+        let hosts = self.hosts.clone();
+        guard::fn_guard(move |ctx| {
+            let host = ctx
+                .head()
+                .headers()
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .or_else(|| ctx.head().uri.host())
+                .unwrap_or_default();
+            hosts.contains(&strip_port(host).to_ascii_lowercase())
+        })
+    }
+}
+
+/// `example.com:8080` → `example.com`, keeping IPv6 literals intact.
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if port.bytes().all(|byte| byte.is_ascii_digit())
+                && (name.ends_with(']') || !name.contains(':')) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
+
+/// Loads the globals and builds the registry of every `[[sites]]` entry,
+/// and checks its templates and data for `/ready`.
+pub async fn load(config: &Config) -> Result<Vec<HostSite>, String> {
+    let mut sites = Vec::new();
+    for site in &config.sites {
+        let site_config = config.for_site(site);
+        helpers::validate_declared(&site_config).map_err(|e| {
+            format!(
+                "{}: invalid [[helpers]] settings: {}",
+                site.root.display(),
+                e
+            )
+        })?;
+        let globals = Globals::load(&site_config.data).await.map_err(|e| {
+            format!(
+                "{}: failed to load globals file: {}",
+                site.root.display(),
+                e
+            )
+        })?;
+        let cache = RenderCache::new(site_config.render.cache_capacity);
+        let registry = web::Data::new(Registry::default());
+        let health = Health::default();
+        health.check_templates(&site_config, Some(&registry)).await;
+        health.check_data(&site_config).await;
+        sites.push(HostSite {
+            hosts: site
+                .hosts
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            config: web::Data::new(site_config),
+            globals: web::Data::new(globals),
+            cache: web::Data::new(cache),
+            health: web::Data::new(health),
+            registry,
+        });
+    }
+    Ok(sites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeclaredHelperConfig, Environment, HostSiteConfig};
+    use crate::live_reload;
+    use actix_web::test::{TestRequest, call_and_read_body, init_service};
+    use actix_web::{App, HttpResponse};
+    use serde_json::{Map, Value};
+    use std::time::Duration;
+
+    fn host_site(hosts: &[&str], name: &str) -> HostSite {
+        let mut globals = Map::new();
+        globals.insert("name".to_string(), Value::from(name));
+        HostSite {
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            config: web::Data::new(Config::default()),
+            globals: web::Data::new(Globals(globals)),
+            cache: web::Data::new(RenderCache::new(1)),
+            health: web::Data::new(Health::default()),
+            registry: web::Data::new(Registry::default()),
+        }
+    }
+
+    async fn site_name(globals: web::Data<Globals>) -> HttpResponse {
+        let name = globals.0.get("name").and_then(Value::as_str);
+        HttpResponse::Ok().body(name.unwrap_or_default().to_string())
+    }
+
+    #[test]
+    fn strip_port_keeps_ipv6_literals() {
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port("::1"), "::1");
+    }
+
+    #[actix_web::test]
+    async fn hosts_get_their_site_and_others_the_default() {
+        let sites = [
+            host_site(&["example.com", "www.example.com"], "example"),
+            host_site(&["blog.test"], "blog"),
+        ];
+        let app = init_service(
+            App::new()
+                .configure(|cfg| {
+                    for site in &sites {
+                        cfg.service(
+                            web::scope("")
+                                .guard(site.guard())
+                                .app_data(site.globals.clone())
+                                .route("/", web::get().to(site_name)),
+                        );
+                    }
+                })
+                .app_data(web::Data::new(Globals::default()))
+                .route("/", web::get().to(site_name)),
+        )
+        .await;
+
+        for (host, expected) in [
+            (Some("example.com"), "example"),
+            (Some("WWW.Example.com:8080"), "example"),
+            (Some("blog.test"), "blog"),
+            (Some("example.com.test"), ""),
+            (Some("other.test"), ""),
+            (None, ""),
+        ] {
+            let mut request = TestRequest::get().uri("/");
+            if let Some(host) = host {
+                request = request.insert_header((header::HOST, host));
+            }
+            let body = call_and_read_body(&app, request.to_request()).await;
+            assert_eq!(body, expected.as_bytes(), "{:?}", host);
+        }
+    }
+
+    #[tokio::test]
+    async fn registry_is_kept_until_a_dev_source_changes() {
+        let root =
+            std::env::temp_dir().join(format!("hbs-composer-registry-{}", std::process::id()));
+        let pages = root.join("templates/pages");
+        std::fs::create_dir_all(&pages).unwrap();
+        std::fs::write(pages.join("index.hbs"), "index").unwrap();
+        let mut config = Config {
+            root: root.clone(),
+            ..Config::default()
+        };
+
+        let registry = Registry::default();
+        let first = registry.site(&config).await.unwrap();
+        std::fs::write(pages.join("about.hbs"), "about").unwrap();
+        let kept = registry.site(&config).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &kept));
+        assert!(!kept.handlebars.has_template("pages/about"));
+
+        config.environment = Some(Environment::Dev);
+        let rebuilt = registry.site(&config).await.unwrap();
+        assert!(rebuilt.handlebars.has_template("pages/about"));
+        std::fs::write(pages.join("contact.hbs"), "contact").unwrap();
+        // Seen once the sources are next polled
+        let deadline = std::time::Instant::now() + live_reload::POLL_INTERVAL * 3;
+        let rebuilt = loop {
+            let site = registry.site(&config).await.unwrap();
+            if site.handlebars.has_template("pages/contact") || std::time::Instant::now() > deadline
+            {
+                break site;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        assert!(rebuilt.handlebars.has_template("pages/contact"));
+        assert!(Arc::ptr_eq(
+            &rebuilt,
+            &registry.site(&config).await.unwrap()
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn declared_helpers_are_checked_for_each_site() {
+        let root =
+            std::env::temp_dir().join(format!("hbs-composer-helpers-{}", std::process::id()));
+        let config = Config {
+            sites: vec![HostSiteConfig {
+                hosts: vec!["blog.test".to_string()],
+                root: root.clone(),
+                base_url: None,
+            }],
+            helpers: vec![DeclaredHelperConfig::Map {
+                name: "if".to_string(),
+                values: Default::default(),
+                default: None,
+            }],
+            ..Config::default()
+        };
+        let error = load(&config).await.err().unwrap();
+        assert_eq!(
+            error,
+            format!(
+                "{}: invalid [[helpers]] settings: helper 'if' is already built in",
+                root.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Pages whose data load or render takes longer than `slow_threshold_ms`
+//! under `[render]` are logged as warnings, with both timings, and the most
+//! recent are kept for `/_debug/slow`, slowest first.
+
+use crate::admin;
+use crate::config::Config;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+pub const DEBUG_ENDPOINT: &str = "/_debug/slow";
+
+/// How many slow renders are kept; older ones are dropped first.
+const KEPT: usize = 50;
+
+struct SlowRender {
+    /// The page template, e.g. `pages/blog/post`.
+    template_name: String,
+    /// The requested path, with the query string.
+    path: String,
+    data_load: Duration,
+    render: Duration,
+    at: SystemTime,
+}
+
+impl SlowRender {
+    fn total(&self) -> Duration {
+        self.data_load + self.render
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "template": self.template_name,
+            "path": self.path,
+            "data_load_ms": millis(self.data_load),
+            "render_ms": millis(self.render),
+            "total_ms": millis(self.total()),
+            "at": DateTime::<Utc>::from(self.at).to_rfc3339(),
+        })
+    }
+}
+
+static RECENT: Mutex<VecDeque<SlowRender>> = Mutex::new(VecDeque::new());
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Logs and keeps the render of `template_name` for `req` when either
+/// timing is past the threshold.
+pub fn check(
+    config: &Config,
+    req: &HttpRequest,
+    template_name: &str,
+    data_load: Duration,
+    render: Duration,
+) {
+    let Some(threshold) = config.render.slow_threshold_ms.map(Duration::from_millis) else {
+        return;
+    };
+    if data_load <= threshold && render <= threshold {
+        return;
+    }
+    let path = match req.query_string() {
+        "" => req.path().to_string(),
+        query => format!("{}?{}", req.path(), query),
+    };
+    log::warn!(
+        "Slow render of '{}' for {}: data load {:.1} ms, render {:.1} ms (threshold {} ms)",
+        template_name,
+        path,
+        millis(data_load),
+        millis(render),
+        threshold.as_millis()
+    );
+
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == KEPT {
+        recent.pop_front();
+    }
+    recent.push_back(SlowRender {
+        template_name: template_name.to_string(),
+        path,
+        data_load,
+        render,
+        at: SystemTime::now(),
+    });
+}
+
+pub async fn debug(req: HttpRequest, config: web::Data<Config>) -> Result<HttpResponse> {
+    if !admin::is_authorized(&req, &config) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut renders: Vec<&SlowRender> = recent.iter().collect();
+    renders.sort_by_key(|render| Reverse(render.total()));
+    let renders: Vec<Value> = renders.iter().map(|render| render.to_json()).collect();
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(json!({
+            "threshold_ms": config.render.slow_threshold_ms,
+            "renders": renders,
+        })))
+}
@@ -0,0 +1,184 @@
+use crate::config::{Config, StaticConfig};
+use crate::render;
+use crate::signed_urls;
+use actix_files::NamedFile;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{self, ContentEncoding, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, Result, mime, web};
+use std::path::{Path, PathBuf};
+
+/// Directory served under `/static`.
+pub const STATIC_DIR: &str = "static";
+
+/// Precompressed variants by preference, with the suffix of their sibling file.
+const VARIANTS: [(ContentEncoding, &str, &str); 2] = [
+    (ContentEncoding::Brotli, "br", ".br"),
+    (ContentEncoding::Gzip, "gzip", ".gz"),
+];
+
+/// Maps a request path below `/static/` to a file in `static_dir`, rejecting
+/// `..` and hidden segments the way `actix_files::Files` does by default.
+pub fn resolve(static_dir: &Path, path: &str) -> Option<PathBuf> {
+    let mut resolved = static_dir.to_path_buf();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        if segment.starts_with('.') || segment.contains('\\') {
+            return None;
+        }
+        resolved.push(segment);
+    }
+    Some(resolved)
+}
+
+/// Whether `/static` needs `serve` rather than `actix_files::Files`, which
+/// can't serve variants, override content types or check signatures.
+pub fn needs_handler(config: &StaticConfig) -> bool {
+    config.precompressed || !config.mime_types.is_empty() || !config.signed.is_empty()
+}
+
+/// Problems with the `mime_types` overrides, for the startup summary.
+pub fn warnings(config: &StaticConfig) -> Vec<String> {
+    let signing = match !config.signed.is_empty() && config.signing_secret().is_none() {
+        true => Some(
+            "[static] signed is set without a signing secret, so those files are never served"
+                .to_string(),
+        ),
+        false => None,
+    };
+    config
+        .mime_types
+        .iter()
+        .filter(|(_, content_type)| content_type.parse::<mime::Mime>().is_err())
+        .map(|(extension, content_type)| {
+            format!(
+                "[static.mime_types] {} is not a valid content type for .{}",
+                content_type, extension
+            )
+        })
+        .chain(signing)
+        .collect()
+}
+
+/// The configured content type for `extension`, if any. Extensions match
+/// case-insensitively, with or without a leading dot in the config.
+fn mime_override(config: &StaticConfig, extension: &str) -> Option<mime::Mime> {
+    config
+        .mime_types
+        .iter()
+        .find(|(configured, _)| {
+            configured
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(extension)
+        })
+        .and_then(|(_, content_type)| content_type.parse().ok())
+}
+
+/// Whether `Accept-Encoding` allows `encoding`, explicitly or through `*`,
+/// with a non-zero quality.
+fn accepts(req: &HttpRequest, encoding: &str) -> bool {
+    let Some(accept) = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let mut wildcard = false;
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality > 0.0;
+        }
+        if name == "*" {
+            wildcard = quality > 0.0;
+        }
+    }
+    wildcard
+}
+
+/// Serves `static/` like `actix_files::Files`, but with the content types
+/// from `mime_types` and, with `precompressed`, a sibling `.br` or `.gz`
+/// file when one exists and the client accepts that encoding.
+/// `Last-Modified` is then the variant's modification time. Files under a
+/// `signed` prefix need a valid signature, or get a 403.
+pub async fn serve(
+    req: HttpRequest,
+    path: web::Path<String>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse> {
+    if signed_urls::is_signed(&config, &path)
+        && let Err(reason) = signed_urls::verify(&config.static_files, &path, req.query_string())
+    {
+        return Ok(render::error_response(
+            &config,
+            &req,
+            StatusCode::FORBIDDEN,
+            "invalid_signature",
+            "This link is invalid or has expired",
+            &reason,
+        ));
+    }
+
+    let Some(file_path) =
+        resolve(&config.static_dir(), &path).filter(|file_path| file_path.is_file())
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let extension = file_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Unknown extensions are `application/octet-stream`
+    let overridden = mime_override(&config.static_files, &extension);
+    let content_type = overridden
+        .clone()
+        .unwrap_or_else(|| actix_files::file_extension_to_mime(&extension));
+
+    let mut file = None;
+    let variants = match config.static_files.precompressed {
+        true => &VARIANTS[..],
+        false => &[],
+    };
+    for &(encoding, name, suffix) in variants {
+        let mut variant = file_path.clone().into_os_string();
+        variant.push(suffix);
+        let variant = PathBuf::from(variant);
+
+        if accepts(&req, name) && Path::new(&variant).is_file() {
+            file = Some(
+                NamedFile::open_async(&variant)
+                    .await?
+                    .set_content_type(content_type.clone())
+                    .set_content_encoding(encoding)
+                    .disable_content_disposition(),
+            );
+            break;
+        }
+    }
+    let file = match (file, overridden) {
+        (Some(file), _) => file,
+        (None, Some(content_type)) => NamedFile::open_async(&file_path)
+            .await?
+            .set_content_type(content_type)
+            .disable_content_disposition(),
+        (None, None) => NamedFile::open_async(&file_path).await?,
+    };
+
+    let mut response = file
+        .prefer_utf8(true)
+        .use_last_modified(config.static_files.last_modified)
+        .into_response(&req);
+    if config.static_files.precompressed {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+    Ok(response)
+}
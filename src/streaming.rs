@@ -0,0 +1,216 @@
+use crate::config::Config;
+use crate::limits;
+use crate::live_reload;
+use actix_web::web::Bytes;
+use futures_util::StreamExt;
+use handlebars::{Handlebars, RenderError, RenderErrorReason};
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Size of the chunks sent once a response is streaming.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks buffered between the renderer and the connection; the renderer
+/// waits when the client reads slowly, which bounds memory per response.
+const CHANNEL_CAPACITY: usize = 4;
+
+enum Message {
+    Chunk(Bytes),
+    /// Rendering finished. Carries the whole output when it stayed below the
+    /// threshold and nothing was streamed.
+    Done(Result<Option<String>, RenderError>),
+}
+
+/// Collects output up to the threshold, then sends it on in chunks.
+struct ChunkWriter {
+    buffer: Vec<u8>,
+    threshold: usize,
+    streaming: bool,
+    /// Bytes written so far, checked against `max_output_bytes`.
+    written: usize,
+    limit: usize,
+    exceeded: bool,
+    sender: mpsc::Sender<Message>,
+}
+
+impl ChunkWriter {
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = Bytes::from(std::mem::take(&mut self.buffer));
+        self.sender
+            .blocking_send(Message::Chunk(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.written += data.len();
+        if self.written > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("output limit reached"));
+        }
+        self.buffer.extend_from_slice(data);
+        let limit = match self.streaming {
+            true => CHUNK_SIZE,
+            false => self.threshold,
+        };
+        if self.buffer.len() > limit {
+            self.streaming = true;
+            self.send()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A rendered page, either complete or still being produced.
+pub enum Rendered {
+    Complete(String),
+    Streaming(Box<dyn futures_util::Stream<Item = Result<Bytes, io::Error>> + Unpin>),
+}
+
+/// Whether stream mode can be used: post-processing other than live reload
+/// needs the whole document.
+pub fn is_streamable(config: &Config) -> bool {
+    config.render.stream_threshold.is_some() && !config.minify() && !config.html.heading_ids
+}
+
+/// Renders on a blocking thread. Output up to `stream_threshold` bytes is
+/// returned complete; beyond that it is streamed as it is produced. An
+/// error after streaming has started can only end the response early.
+pub async fn render(
+    handlebars: Arc<Handlebars<'static>>,
+    template_name: String,
+    context: Map<String, Value>,
+    config: &Config,
+) -> Result<Rendered, RenderError> {
+    let threshold = config.render.stream_threshold.unwrap_or(usize::MAX);
+    let live_reload = config.live_reload();
+    let limit = config.render.max_output_bytes;
+    let (sender, mut receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+    let name = template_name.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter {
+            buffer: Vec::new(),
+            threshold,
+            streaming: false,
+            written: 0,
+            limit,
+            exceeded: false,
+            sender: sender.clone(),
+        };
+        let result = match handlebars.render_to_write(&name, &context, &mut writer) {
+            Err(_) if writer.exceeded => Err(limits::output_too_large(limit)),
+            result => result,
+        }
+        .and_then(|_| match writer.streaming {
+            true => {
+                if live_reload {
+                    writer
+                        .buffer
+                        .extend_from_slice(live_reload::SCRIPT.as_bytes());
+                }
+                writer.send().map_err(RenderErrorReason::from)?;
+                Ok(None)
+            }
+            false => Ok(Some(String::from_utf8_lossy(&writer.buffer).into_owned())),
+        });
+        let _ = sender.blocking_send(Message::Done(result));
+    });
+
+    let first = match receiver.recv().await {
+        Some(Message::Done(Ok(Some(complete)))) => return Ok(Rendered::Complete(complete)),
+        Some(Message::Done(Err(e))) => return Err(e),
+        Some(Message::Chunk(chunk)) => chunk,
+        Some(Message::Done(Ok(None))) | None => Bytes::new(),
+    };
+
+    log::debug!("Streaming '{}'", template_name);
+    let rest = futures_util::stream::unfold(receiver, move |mut receiver| {
+        let template_name = template_name.clone();
+        async move {
+            match receiver.recv().await? {
+                Message::Chunk(chunk) => Some((Ok(chunk), receiver)),
+                Message::Done(Ok(_)) => None,
+                Message::Done(Err(e)) => {
+                    eprintln!(
+                        "Template rendering error for '{}' while streaming: {}",
+                        template_name, e
+                    );
+                    Some((Err(io::Error::other(e.to_string())), receiver))
+                }
+            }
+        }
+    });
+
+    Ok(Rendered::Streaming(Box::new(Box::pin(
+        futures_util::stream::once(async move { Ok(first) }).chain(rest),
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn setup(items: usize) -> (Arc<Handlebars<'static>>, Map<String, Value>) {
+        let mut handlebars = Handlebars::new();
+        handlebars
+            .register_template_string("page", "{{#each items}}0123456789{{/each}}")
+            .unwrap();
+        let Value::Object(context) = json!({ "items": vec![0; items] }) else {
+            unreachable!()
+        };
+        (Arc::new(handlebars), context)
+    }
+
+    fn config(stream_threshold: usize, max_output_bytes: usize) -> Config {
+        let mut config = Config::default();
+        config.render.stream_threshold = Some(stream_threshold);
+        config.render.max_output_bytes = max_output_bytes;
+        config
+    }
+
+    #[tokio::test]
+    async fn output_past_the_limit_fails_before_streaming() {
+        let (handlebars, context) = setup(10);
+        let result = render(handlebars, "page".to_string(), context, &config(64, 32)).await;
+        let Err(error) = result else {
+            panic!("rendered past max_output_bytes")
+        };
+        assert!(error.to_string().contains("max_output_bytes"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn output_past_the_limit_ends_the_stream() {
+        let (handlebars, context) = setup(20);
+        let result = render(handlebars, "page".to_string(), context, &config(8, 64)).await;
+        let Ok(Rendered::Streaming(mut stream)) = result else {
+            panic!("expected a streaming response")
+        };
+
+        let mut streamed = 0;
+        let mut failed = false;
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(chunk) => streamed += chunk.len(),
+                Err(e) => {
+                    assert!(e.to_string().contains("max_output_bytes"), "{}", e);
+                    failed = true;
+                }
+            }
+        }
+        assert!(failed, "the stream ended without an error");
+        assert!(
+            streamed > 0 && streamed <= 64,
+            "streamed {} bytes",
+            streamed
+        );
+    }
+}
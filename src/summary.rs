@@ -0,0 +1,174 @@
+use crate::admin;
+use crate::config::{CONFIG_FILE, Config};
+use crate::data;
+use crate::inspector;
+use crate::preview;
+use crate::render_api;
+use std::collections::BTreeMap;
+
+/// Optional cargo features, as reported in the summary.
+const FEATURES: [(&str, bool); 7] = [
+    ("ini", cfg!(feature = "ini")),
+    ("yaml", cfg!(feature = "yaml")),
+    ("toml", cfg!(feature = "toml")),
+    ("csv", cfg!(feature = "csv")),
+    ("markdown", cfg!(feature = "markdown")),
+    ("xml", cfg!(feature = "xml")),
+    ("scripts", cfg!(feature = "scripts")),
+];
+
+fn on_off(enabled: bool) -> &'static str {
+    match enabled {
+        true => "on",
+        false => "off",
+    }
+}
+
+/// `12 (JSON 4, YAML 8)`, or `0` when empty.
+fn counts(counts: &BTreeMap<&str, usize>) -> String {
+    let total: usize = counts.values().sum();
+    if total == 0 {
+        return "0".to_string();
+    }
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|(name, count)| format!("{} {}", name, count))
+        .collect();
+    format!("{} ({})", total, parts.join(", "))
+}
+
+/// Prints what the server found and how it is configured as one block.
+/// Templates and data are still re-read on every request; this is a
+/// snapshot of what the first request will see. `templates` is the startup
+/// check from `template_cache`.
+pub async fn print(
+    config: &Config,
+    listening: &[String],
+    workers: usize,
+    templates: &Result<Vec<String>, String>,
+    warnings: Vec<String>,
+) {
+    let mut warnings = warnings;
+
+    let templates = match templates {
+        Ok(names) => {
+            let mut by_directory = BTreeMap::new();
+            for name in names {
+                let directory = match name.split_once('/') {
+                    Some((directory, _)) => directory,
+                    None => "(root)",
+                };
+                *by_directory.entry(directory).or_default() += 1;
+            }
+            counts(&by_directory)
+        }
+        Err(e) => {
+            warnings.push(format!("templates failed to load: {}", e));
+            "failed".to_string()
+        }
+    };
+
+    let data_files = match data::count_data_files(config).await {
+        Ok(found) => {
+            if found.disabled > 0 {
+                warnings.push(format!(
+                    "{} data files are skipped because their format is compiled out",
+                    found.disabled
+                ));
+            }
+            counts(&found.by_format)
+        }
+        Err(e) => {
+            warnings.push(format!("data files could not be counted: {}", e));
+            "failed".to_string()
+        }
+    };
+
+    let features: Vec<&str> = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+
+    let preview = match (preview::is_enabled(config), &config.preview.token) {
+        (true, _) => "/_preview/<page>",
+        (false, Some(_)) => "disabled in prod",
+        (false, None) => "off",
+    };
+
+    println!("hbs-composer {}", env!("CARGO_PKG_VERSION"));
+    let sources: Vec<String> = config
+        .sources
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    match sources.is_empty() {
+        true => println!("  Config:       defaults (no {})", CONFIG_FILE),
+        false => println!("  Config:       {}", sources.join(" + ")),
+    }
+    println!("  Listening:    {}", listening.join(", "));
+    println!(
+        "  Environment:  {} (minify {}, strict {}, error details {}, live reload {})",
+        config.environment_name(),
+        on_off(config.minify()),
+        on_off(config.strict()),
+        on_off(config.error_details()),
+        on_off(config.live_reload())
+    );
+    println!("  Workers:      {}", workers);
+    for site in &config.sites {
+        println!(
+            "  Site:         {} from {}",
+            site.hosts.join(", "),
+            site.root.display()
+        );
+    }
+    println!("  Templates:    {}", templates);
+    if !config.remote_partials.is_empty() {
+        let names: Vec<&str> = config
+            .remote_partials
+            .iter()
+            .map(|partial| partial.name.as_str())
+            .collect();
+        println!("  Remote:       {}", names.join(", "));
+    }
+    println!("  Data files:   {}", data_files);
+    if let Some(globals) = &config.data.globals {
+        println!("  Globals:      {}", globals.display());
+    }
+    println!("  Features:     {}", features.join(", "));
+    match inspector::is_enabled(config) {
+        true => println!("  Render cache: off (inspector on)"),
+        false => println!("  Render cache: {}", on_off(config.render.cache)),
+    }
+    match config.rate_limit.requests {
+        Some(requests) if requests > 0 => println!(
+            "  Rate limit:   {} requests per {}s, burst {}",
+            requests,
+            config.rate_limit.window,
+            config.rate_limit.burst.unwrap_or(requests)
+        ),
+        _ => println!("  Rate limit:   off"),
+    }
+    println!("  Preview:      {}", preview);
+    match admin::is_enabled(config) {
+        true => println!("  Admin:        {}", admin::RELOAD_ENDPOINT),
+        false => println!("  Admin:        off"),
+    }
+    match render_api::is_enabled(config) {
+        true => println!("  Render API:   /_render/<template>"),
+        false => println!("  Render API:   off"),
+    }
+    match warnings.is_empty() {
+        true => println!("  Warnings:     none"),
+        false => {
+            println!("  Warnings:     {}", warnings.len());
+            for warning in &warnings {
+                println!("    - {}", warning);
+            }
+        }
+    }
+    println!(
+        "Templates and data are re-read on every request; RUST_LOG=hbs_composer=debug logs each file"
+    );
+}
@@ -0,0 +1,109 @@
+//! Remembers a successful startup check of the templates in `CACHE_FILE`,
+//! keyed by a hash of every file below `templates/`. While nothing has
+//! changed, later startups take the template list from the cache instead of
+//! parsing every template again. Only the names are stored, never compiled
+//! templates, so requests still load and parse templates as usual.
+
+use crate::build::{collect_files, manifest_path};
+use crate::config::Config;
+use crate::fragments;
+use crate::helpers;
+use crate::remote_partials;
+use crate::render::Site;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+pub const CACHE_FILE: &str = ".hbs-composer/templates.json";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Cache {
+    /// Another version may parse templates differently.
+    version: String,
+    /// SHA-256 of each file below `templates/`, by path, of each fragment,
+    /// by name, and of each remote partial, by URL.
+    sources: BTreeMap<String, String>,
+    /// The registered template names, sorted.
+    templates: Vec<String>,
+}
+
+fn hash(content: &[u8]) -> String {
+    let digest = Sha256::digest(content);
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Fragments are included by name and remote partials by URL.
+async fn source_hashes(
+    config: &Config,
+) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+    let templates_dir = config.templates_dir();
+    let mut sources = BTreeMap::new();
+    for file in collect_files(&templates_dir, PathBuf::new()).await? {
+        let content = fs::read(templates_dir.join(&file)).await?;
+        sources.insert(manifest_path(&file), hash(&content));
+    }
+    for (name, path) in fragments::files(config).await {
+        let content = fs::read(&path).await?;
+        sources.insert(format!("fragment:{}", name), hash(&content));
+    }
+    for (url, source) in remote_partials::sources(config) {
+        sources.insert(url, hash(source.as_bytes()));
+    }
+    Ok(sources)
+}
+
+async fn read_cache() -> Option<Cache> {
+    let content = fs::read(CACHE_FILE).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+async fn write_cache(cache: &Cache) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = Path::new(CACHE_FILE).parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(CACHE_FILE, serde_json::to_vec_pretty(cache)?).await?;
+    Ok(())
+}
+
+/// The registered template names, from the cache when the sources are
+/// unchanged, otherwise by loading the templates. A failed load is never
+/// cached.
+pub async fn template_names(config: &Config) -> Result<Vec<String>, String> {
+    let sources = match config.render.template_cache {
+        true => source_hashes(config).await.ok(),
+        false => None,
+    };
+    if let Some(sources) = &sources
+        && let Some(cache) = read_cache().await
+        && cache.version == env!("CARGO_PKG_VERSION")
+        && cache.sources == *sources
+    {
+        log::debug!("Templates unchanged, using {}", CACHE_FILE);
+        return Ok(cache.templates);
+    }
+
+    let site = Site::load(config).await.map_err(|e| e.to_string())?;
+    let mut templates: Vec<String> = site
+        .handlebars
+        .get_templates()
+        .keys()
+        .filter(|name| !name.starts_with(helpers::DECLARED_TEMPLATE_PREFIX))
+        .cloned()
+        .collect();
+    templates.sort();
+
+    if let Some(sources) = sources {
+        let cache = Cache {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            sources,
+            templates: templates.clone(),
+        };
+        if let Err(e) = write_cache(&cache).await {
+            eprintln!("Warning: could not write {}: {}", CACHE_FILE, e);
+        }
+    }
+
+    Ok(templates)
+}
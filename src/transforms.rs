@@ -0,0 +1,362 @@
+//! Transforms run over the assembled context before a page is rendered. Each
+//! implements `ContextTransform`; `chain` builds the list declared as
+//! `[[transforms]]` in the config, and further transforms can be added there.
+//! An entry with `routes` only runs for the pages whose path matches them.
+
+use crate::config::{Config, RoutePatterns, SortOrder, TransformConfig};
+use crate::derived::insert_path;
+use crate::helpers::{group_by, lookup, parse_date, parse_version, reading_minutes, sort_by};
+use crate::render::route_path;
+use handlebars::{JsonRender, JsonTruthy};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Changes the context for a request to `route`, e.g. `blog/post`.
+pub trait ContextTransform: Send + Sync {
+    fn transform(&self, context: &mut Map<String, Value>, route: &str);
+}
+
+/// The transforms declared in the config, in declaration order.
+pub fn chain(config: &Config) -> Vec<Box<dyn ContextTransform>> {
+    config
+        .transforms
+        .iter()
+        .map(|entry| -> Box<dyn ContextTransform> {
+            let transform: Box<dyn ContextTransform> = match &entry.transform {
+                TransformConfig::SortByDate { key, field, order } => Box::new(SortByDate {
+                    key: key.clone(),
+                    field: field.clone(),
+                    order: *order,
+                }),
+                TransformConfig::ReadingTime {
+                    key,
+                    field,
+                    words_per_minute,
+                } => Box::new(ReadingTime {
+                    key: key.clone(),
+                    field: field.clone(),
+                    words_per_minute: words_per_minute
+                        .unwrap_or(config.reading_time.words_per_minute()),
+                }),
+                TransformConfig::Releases { key } => Box::new(Releases { key: key.clone() }),
+                TransformConfig::Sort { key, field, order } => Box::new(Sort {
+                    key: key.clone(),
+                    field: field.clone(),
+                    order: *order,
+                }),
+                TransformConfig::Limit { key, count, offset } => Box::new(Limit {
+                    key: key.clone(),
+                    count: *count,
+                    offset: *offset,
+                }),
+                TransformConfig::Group {
+                    key,
+                    field,
+                    into,
+                    order,
+                } => Box::new(Group {
+                    key: key.clone(),
+                    field: field.clone(),
+                    into: into.clone(),
+                    order: *order,
+                }),
+                TransformConfig::Filter {
+                    key,
+                    field,
+                    equals,
+                    exclude,
+                } => Box::new(Filter {
+                    key: key.clone(),
+                    field: field.clone(),
+                    equals: equals.clone(),
+                    exclude: *exclude,
+                }),
+            };
+            Box::new(Scoped {
+                routes: entry.routes.clone(),
+                transform,
+            })
+        })
+        .collect()
+}
+
+pub fn apply(
+    transforms: &[Box<dyn ContextTransform>],
+    context: &mut Map<String, Value>,
+    route: &str,
+) {
+    for transform in transforms {
+        transform.transform(context, route);
+    }
+}
+
+/// Runs `transform` only for the pages whose path `routes` matches.
+struct Scoped {
+    routes: RoutePatterns,
+    transform: Box<dyn ContextTransform>,
+}
+
+impl ContextTransform for Scoped {
+    fn transform(&self, context: &mut Map<String, Value>, route: &str) {
+        if self.routes.matches(&route_path(route)) {
+            self.transform.transform(context, route);
+        }
+    }
+}
+
+/// The value at a dotted `path`, for changing it in place.
+fn lookup_mut<'a>(context: &'a mut Map<String, Value>, path: &str) -> Option<&'a mut Value> {
+    let mut segments = path.split('.');
+    let mut value = context.get_mut(segments.next()?)?;
+    for segment in segments {
+        value = match value {
+            Value::Object(map) => map.get_mut(segment)?,
+            Value::Array(items) => items.get_mut(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Sorts the array at `key` by the date in each item's `field`. Items whose
+/// date is missing or unparseable go last, keeping their order.
+struct SortByDate {
+    key: String,
+    field: String,
+    order: SortOrder,
+}
+
+impl ContextTransform for SortByDate {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        let Some(Value::Array(items)) = lookup_mut(context, &self.key) else {
+            eprintln!(
+                "Warning: sort_by_date transform found no array at '{}'",
+                self.key
+            );
+            return;
+        };
+
+        let date =
+            |item: &Value| lookup(item, &self.field).and_then(|date| parse_date(&date.render()));
+        items.sort_by(|a, b| match (date(a), date(b)) {
+            (Some(a), Some(b)) if self.order == SortOrder::Desc => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+}
+
+/// Adds `reading_time` in minutes, as the `readingTime` helper estimates it,
+/// to each item of the array at `key`, or to the object there.
+struct ReadingTime {
+    key: String,
+    field: String,
+    words_per_minute: u32,
+}
+
+impl ReadingTime {
+    fn annotate(&self, item: &mut Value) {
+        let Some(text) = lookup(item, &self.field).map(JsonRender::render) else {
+            return;
+        };
+        if let Value::Object(map) = item {
+            let minutes = reading_minutes(&text, self.words_per_minute);
+            map.insert("reading_time".to_string(), Value::from(minutes));
+        }
+    }
+}
+
+impl ContextTransform for ReadingTime {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        match lookup_mut(context, &self.key) {
+            Some(Value::Array(items)) => items.iter_mut().for_each(|item| self.annotate(item)),
+            Some(item @ Value::Object(_)) => self.annotate(item),
+            _ => eprintln!(
+                "Warning: reading_time transform found no array or object at '{}'",
+                self.key
+            ),
+        }
+    }
+}
+
+/// Entries already warned about, so each is reported once.
+static SKIPPED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Keeps the release notes in the array at `key` that are objects with a
+/// semantic `version` and, when they have one, a valid `date`, sorted newest
+/// version first, then newest date. The first gets `latest: true` and the
+/// others `latest: false`. Skipped entries are warned about once.
+struct Releases {
+    key: String,
+}
+
+impl Releases {
+    /// Why `release` can't be listed, if it can't.
+    fn problem(release: &Value) -> Option<&'static str> {
+        let Value::Object(release) = release else {
+            return Some("not an object");
+        };
+        match release.get("version") {
+            Some(version) if parse_version(&version.render()).is_some() => {}
+            Some(_) => return Some("`version` isn't a semantic version"),
+            None => return Some("no `version`"),
+        }
+        match release.get("date") {
+            Some(date) if parse_date(&date.render()).is_none() => Some("invalid `date`"),
+            _ => None,
+        }
+    }
+}
+
+impl ContextTransform for Releases {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        let Some(Value::Array(releases)) = lookup_mut(context, &self.key) else {
+            eprintln!(
+                "Warning: releases transform found no array at '{}'",
+                self.key
+            );
+            return;
+        };
+
+        let mut skipped = SKIPPED.lock().unwrap_or_else(|e| e.into_inner());
+        let skipped = skipped.get_or_insert_with(HashSet::new);
+        releases.retain(|release| {
+            let Some(problem) = Releases::problem(release) else {
+                return true;
+            };
+            let entry = format!("{}: {}", self.key, release);
+            if skipped.insert(entry.clone()) {
+                eprintln!("Warning: skipping release {} ({})", entry, problem);
+            }
+            false
+        });
+
+        let version = |release: &Value| parse_version(&release["version"].render());
+        let date = |release: &Value| {
+            release
+                .get("date")
+                .and_then(|date| parse_date(&date.render()))
+        };
+        releases.sort_by(|a, b| {
+            version(b)
+                .cmp(&version(a))
+                .then_with(|| date(b).cmp(&date(a)))
+        });
+        for (index, release) in releases.iter_mut().enumerate() {
+            if let Value::Object(release) = release {
+                release.insert("latest".to_string(), Value::Bool(index == 0));
+            }
+        }
+    }
+}
+
+/// Sorts the array at `key` by each item's `field`, like the `sortBy`
+/// helper: numbers numerically, items without the field last.
+struct Sort {
+    key: String,
+    field: String,
+    order: SortOrder,
+}
+
+impl ContextTransform for Sort {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        let Some(Value::Array(items)) = lookup_mut(context, &self.key) else {
+            eprintln!("Warning: sort transform found no array at '{}'", self.key);
+            return;
+        };
+        let sorted = sort_by(
+            std::mem::take(items),
+            &self.field,
+            self.order == SortOrder::Desc,
+        );
+        *items = sorted;
+    }
+}
+
+/// Keeps `count` items of the array at `key`, starting at `offset`.
+struct Limit {
+    key: String,
+    count: usize,
+    offset: usize,
+}
+
+impl ContextTransform for Limit {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        let Some(Value::Array(items)) = lookup_mut(context, &self.key) else {
+            eprintln!("Warning: limit transform found no array at '{}'", self.key);
+            return;
+        };
+        items.drain(..self.offset.min(items.len()));
+        items.truncate(self.count);
+    }
+}
+
+/// Groups the array at `key` into `{key, items}` objects by each item's
+/// `field`, like the `groupBy` helper, replacing it or setting `into`.
+struct Group {
+    key: String,
+    field: String,
+    into: Option<String>,
+    order: SortOrder,
+}
+
+impl ContextTransform for Group {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        let Some(Value::Array(items)) = lookup_mut(context, &self.key) else {
+            eprintln!("Warning: group transform found no array at '{}'", self.key);
+            return;
+        };
+        let descending = self.order == SortOrder::Desc;
+        let Some(into) = &self.into else {
+            *items = group_by(std::mem::take(items), &self.field, descending);
+            return;
+        };
+        let groups = group_by(items.clone(), &self.field, descending);
+        if let Err(segment) = insert_path(context, into, Value::Array(groups)) {
+            eprintln!(
+                "Warning: group transform can't set '{}': '{}' is not an object",
+                into, segment
+            );
+        }
+    }
+}
+
+/// Keeps the items of the array at `key` whose `field` matches, or with
+/// `exclude` drops them. A field matches `equals` when their rendered
+/// values are equal, so `2024` matches `"2024"`, or when it is an array
+/// holding such a value; without `equals`, when it is truthy.
+struct Filter {
+    key: String,
+    field: String,
+    equals: Option<Value>,
+    exclude: bool,
+}
+
+impl Filter {
+    fn matches(&self, item: &Value) -> bool {
+        let Some(value) = lookup(item, &self.field) else {
+            return false;
+        };
+        match (&self.equals, value) {
+            (None, value) => value.is_truthy(false),
+            (Some(equals), Value::Array(values)) => {
+                values.iter().any(|value| value.render() == equals.render())
+            }
+            (Some(equals), value) => value.render() == equals.render(),
+        }
+    }
+}
+
+impl ContextTransform for Filter {
+    fn transform(&self, context: &mut Map<String, Value>, _: &str) {
+        let Some(Value::Array(items)) = lookup_mut(context, &self.key) else {
+            eprintln!("Warning: filter transform found no array at '{}'", self.key);
+            return;
+        };
+        items.retain(|item| self.matches(item) != self.exclude);
+    }
+}